@@ -0,0 +1,7 @@
+extern crate smash;
+
+use smash::cli;
+
+fn main() {
+    cli::run()
+}
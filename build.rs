@@ -1,12 +1,47 @@
-use std::process::Command;
+use std::{env, ffi::OsStr, fs, path::Path, process::Command};
+
+/// Declare every module in `src/targets`, so that adding a target touches
+/// only its own file. A module is a `.rs` file, or a directory with a
+/// `mod.rs`.
+fn declare_targets() {
+    println!("cargo:rerun-if-changed=src/targets");
+
+    let dir = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("src/targets");
+    let mut modules = vec![];
+    for entry in fs::read_dir(&dir).unwrap() {
+        let path = entry.unwrap().path();
+        let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+        if path.is_dir() && path.join("mod.rs").is_file() {
+            modules.push((name, path.join("mod.rs")));
+        } else if path.extension() == Some(OsStr::new("rs")) && name != "mod" {
+            modules.push((name, path));
+        }
+    }
+    modules.sort();
+
+    let declarations: String = modules
+        .iter()
+        .map(|(name, path)| {
+            format!(
+                "#[path = {:?}]\npub mod {};\n",
+                path.to_str().unwrap(),
+                name
+            )
+        })
+        .collect();
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("targets.rs");
+    fs::write(out, declarations).unwrap();
+}
 
 fn main() {
     println!("cargo:rerun-if-changed=build.rs");
     println!("cargo:rerun-if-changed=make.sh");
 
+    declare_targets();
+
     let arg = "./make.sh";
-    Command::new("sh").args(&[arg]).status().unwrap();
+    Command::new("sh").arg(arg).status().unwrap();
 
-    Command::new("touch").args(&["build.rs"]).status().unwrap();
-    Command::new("touch").args(&["make.sh"]).status().unwrap();
+    Command::new("touch").arg("build.rs").status().unwrap();
+    Command::new("touch").arg("make.sh").status().unwrap();
 }
@@ -1,8 +1,10 @@
 package main
 
 import (
+	"encoding/binary"
 	"fmt"
 	"io"
+	"math"
 	"os"
 
 	"github.com/ethereum/go-ethereum/common"
@@ -65,15 +67,15 @@ func (c *command) Run() *response {
 
 	if precompile, ok := precompilesMap[common.BytesToAddress([]byte{uint8(c.address)})]; ok {
 
-		buf, gas, err := precompile.Run(c.body, common.Address{0}, nil, 10_000_000)
+		res.gas = precompile.RequiredGas(c.body)
+		// as much gas as any input needs, as geth's Run takes none
+		buf, _, err := precompile.Run(c.body, common.Address{0}, nil, math.MaxUint64)
 		if err != nil {
 			res.isErr = true
 			res.body = []byte(err.Error())
-			res.gas = gas
 		} else {
 			res.isErr = false
 			res.body = buf
-			res.gas = gas
 		}
 	} else {
 		res.isErr = true
@@ -99,11 +101,18 @@ func (r *response) WriteTo(writer io.Writer) (int64, error) {
 		return 0, err
 	}
 
+	gas := make([]byte, 8)
+	binary.BigEndian.PutUint64(gas, r.gas)
+	_, err = writer.Write(gas)
+	if err != nil {
+		return 0, err
+	}
+
 	_, err = writer.Write(r.body)
 	if err != nil {
 		return 0, err
 	}
-	return 3 + int64(len(r.body)), nil
+	return 11 + int64(len(r.body)), nil
 }
 
 func main() {
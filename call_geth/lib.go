@@ -1,6 +1,7 @@
 package main
 
 import (
+	"encoding/binary"
 	"fmt"
 	"io"
 	"os"
@@ -16,8 +17,8 @@ type command struct {
 
 type response struct {
 	isErr bool
-	// gas   uint
-	body []byte
+	gas   uint64
+	body  []byte
 }
 
 func readSafe(reader io.Reader, desired uint) ([]byte, error) {
@@ -59,21 +60,26 @@ func (c *command) ReadFrom(reader io.Reader) (int64, error) {
 }
 
 func (c *command) Run() *response {
-	precompilesMap := vm.PrecompiledContractsIstanbul
+	address := common.BytesToAddress([]byte{uint8(c.address)})
 
 	var res response
 
-	if precompile, ok := precompilesMap[common.BytesToAddress([]byte{uint8(c.address)})]; ok {
+	// The EIP-2537 precompiles are only in the YoloV2 set
+	precompile, ok := vm.PrecompiledContractsIstanbul[address]
+	if !ok {
+		precompile, ok = vm.PrecompiledContractsYoloV2[address]
+	}
+
+	if ok {
 
+		res.gas = precompile.RequiredGas(c.body)
 		buf, err := precompile.Run(c.body)
 		if err != nil {
 			res.isErr = true
 			res.body = []byte(err.Error())
-			// res.gas = 0
 		} else {
 			res.isErr = false
 			res.body = buf
-			// res.gas = 0
 		}
 	} else {
 		res.isErr = true
@@ -99,11 +105,18 @@ func (r *response) WriteTo(writer io.Writer) (int64, error) {
 		return 0, err
 	}
 
+	gas := make([]byte, 8)
+	binary.BigEndian.PutUint64(gas, r.gas)
+	_, err = writer.Write(gas)
+	if err != nil {
+		return 0, err
+	}
+
 	_, err = writer.Write(r.body)
 	if err != nil {
 		return 0, err
 	}
-	return 3 + int64(len(r.body)), nil
+	return 11 + int64(len(r.body)), nil
 }
 
 func main() {
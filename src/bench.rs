@@ -0,0 +1,141 @@
+use lain::{
+    prelude::*,
+    rand::{rngs::StdRng, SeedableRng},
+};
+use std::{collections::BTreeMap, fmt, time::Duration};
+
+use crate::{fuzzer::Fuzzer, stats, traits::Target};
+
+/// Lower bounds of the input size buckets, in bytes, after the first bucket
+/// from 0. Inputs from the last bound up share a final bucket.
+const BUCKETS: [usize; 5] = [64, 256, 1024, 4096, 16384];
+
+/// The lower bound of the bucket containing `len`.
+fn bucket(len: usize) -> usize {
+    BUCKETS
+        .iter()
+        .rev()
+        .copied()
+        .find(|lower| len >= *lower)
+        .unwrap_or(0)
+}
+
+fn bucket_name(lower: usize) -> String {
+    match BUCKETS.iter().find(|upper| **upper > lower) {
+        Some(upper) => format!("{}-{}", lower, upper - 1),
+        None => format!("{}+", lower),
+    }
+}
+
+/// Latency samples for a single executor and input size bucket.
+#[derive(Clone, Debug, Default)]
+pub struct Samples(Vec<Duration>);
+
+impl Samples {
+    /// The number of samples.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// True if there are no samples.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The latency at percentile `p`, between 0 and 100. Zero if there are no
+    /// samples.
+    pub fn percentile(&self, p: f64) -> Duration {
+        let mut sorted = self.0.clone();
+        sorted.sort();
+        let index = ((p / 100.0) * sorted.len().saturating_sub(1) as f64).round() as usize;
+        sorted.get(index).copied().unwrap_or_default()
+    }
+}
+
+/// Latency report for a target. The report is written in a stable, line-based
+/// format, so that reports from different client versions can be diffed.
+#[derive(Clone, Debug)]
+pub struct BenchReport {
+    /// The target name
+    pub target: &'static str,
+    /// The seed the corpus was generated from
+    pub seed: u64,
+    /// The number of inputs in the corpus
+    pub count: usize,
+    /// Samples by executor, then by the lower bound of the input size bucket
+    pub samples: BTreeMap<String, BTreeMap<usize, Samples>>,
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "# smash bench target={} seed={} count={}",
+            self.target, self.seed, self.count
+        )?;
+        writeln!(
+            f,
+            "{:<16}{:<14}{:>8}{:>12}{:>12}{:>12}{:>12}",
+            "executor", "bucket", "calls", "p50_us", "p90_us", "p99_us", "max_us"
+        )?;
+        for (executor, buckets) in self.samples.iter() {
+            for (bucket, samples) in buckets.iter() {
+                writeln!(
+                    f,
+                    "{:<16}{:<14}{:>8}{:>12}{:>12}{:>12}{:>12}",
+                    executor,
+                    bucket_name(*bucket),
+                    samples.len(),
+                    samples.percentile(50.0).as_micros(),
+                    samples.percentile(90.0).as_micros(),
+                    samples.percentile(99.0).as_micros(),
+                    samples.percentile(100.0).as_micros(),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T> Fuzzer<T>
+where
+    T: Target<Rng = StdRng>,
+{
+    /// Run a fixed corpus of `count` inputs, generated from the fuzzer seed,
+    /// through every executor the target calls, after one warm-up input. Runs
+    /// on a single thread, so that calls do not compete for CPU.
+    pub fn bench(&self, count: usize) -> BenchReport {
+        let mut target = T::new(self.config().cloned());
+        let mut ctx = self.context();
+        let mut mutator = Mutator::new(StdRng::seed_from_u64(self.seed()));
+
+        let mut report = BenchReport {
+            target: T::name(),
+            seed: self.seed(),
+            count,
+            samples: Default::default(),
+        };
+
+        // the first call on each executor includes its start-up time
+        target.run_raw(&mut ctx, &target.generate_serialized(&mut mutator));
+        ctx.take_measurements();
+
+        for _ in 0..count {
+            let input = target.generate_serialized(&mut mutator);
+            target.run_raw(&mut ctx, &input);
+
+            for (command, measurement) in ctx.take_measurements() {
+                report
+                    .samples
+                    .entry(stats::executor_name(command))
+                    .or_default()
+                    .entry(bucket(input.len()))
+                    .or_default()
+                    .0
+                    .push(measurement.elapsed);
+            }
+        }
+
+        report
+    }
+}
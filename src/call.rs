@@ -2,53 +2,188 @@ use std::{
     fmt,
     io::{Read, Write},
     process::{Child, Command, Stdio},
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
-use crate::errors::{CommunicationError, CommunicationResult};
+use crate::{
+    errors::{CommunicationError, CommunicationResult},
+    mock::{Handler, Transcript},
+    stats::{self, Latency},
+};
+
+/// Wall-clock time and reported gas for a single call.
+#[derive(Copy, Clone, Debug)]
+pub struct Measurement {
+    /// Wall-clock time, including the round trip to the executor
+    pub elapsed: Duration,
+    /// Gas the executor reported for the input
+    pub gas: u64,
+}
+
+/// An executor's answer to a single call.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Response {
+    /// The output, or the error message
+    pub result: Result<Vec<u8>, String>,
+    /// Gas the executor reported for the input
+    pub gas: u64,
+}
+
+impl Response {
+    /// A successful response, costing no gas.
+    pub fn ok(output: Vec<u8>) -> Self {
+        Self {
+            result: Ok(output),
+            gas: 0,
+        }
+    }
+
+    /// An error response, costing no gas.
+    pub fn err(message: impl Into<String>) -> Self {
+        Self {
+            result: Err(message.into()),
+            gas: 0,
+        }
+    }
 
-pub(crate) struct Caller(&'static str, Child);
+    /// Set the gas the response reports.
+    pub fn set_gas(mut self, gas: u64) -> Self {
+        self.gas = gas;
+        self
+    }
+}
+
+enum Executor {
+    Process(Child),
+    Mock(Handler),
+}
+
+pub(crate) struct Caller {
+    command: &'static str,
+    executor: Executor,
+    latency: Arc<Latency>,
+    last: Option<Measurement>,
+    transcript: Option<Transcript>,
+}
 
 impl fmt::Debug for Caller {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Caller")
-         .field("command", &self.0)
+         .field("command", &self.command)
          .finish()
     }
 }
 
 impl Drop for Caller {
     fn drop(&mut self) {
-        // causes panic on drop if the child panicked
-        self.1.kill().expect("wasn't running");
+        if let Executor::Process(child) = &mut self.executor {
+            // causes panic on drop if the child panicked
+            child.kill().expect("wasn't running");
+        }
     }
 }
 
+/// The command for the celo executor.
+pub(crate) const CELO: &str = "./call_celo/call_celo";
+
+/// The command for the geth executor.
+pub(crate) const GETH: &str = "./call_geth/call_geth";
+
 impl Caller {
-    fn new(cmd: &'static str) -> Self {
-        Caller(
-            cmd,
-            Command::new(cmd)
-                .stdin(Stdio::piped())
-                .stdout(Stdio::piped())
-                .spawn()
-                .expect("!child"),
-        )
+    pub(crate) fn new(cmd: &'static str) -> Self {
+        let child = Command::new(cmd)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("!child");
+        Self::with_executor(cmd, Executor::Process(child))
+    }
+
+    /// Instantiate a caller answering from a Rust closure. `name` stands in
+    /// for the command in statistics.
+    pub(crate) fn mock(name: &'static str, handler: Handler) -> Self {
+        Self::with_executor(name, Executor::Mock(handler))
+    }
+
+    fn with_executor(command: &'static str, executor: Executor) -> Self {
+        Caller {
+            command,
+            executor,
+            latency: stats::executor_latency(command),
+            last: None,
+            transcript: None,
+        }
     }
 
     pub fn new_celo() -> Self {
-        Self::new("./call_celo/call_celo")
+        Self::new(CELO)
     }
 
     pub fn new_geth() -> Self {
-        Self::new("./call_geth/call_geth")
+        Self::new(GETH)
+    }
+
+    /// True if the child process has not exited. Mocks are always running.
+    pub fn is_running(&mut self) -> bool {
+        match &mut self.executor {
+            Executor::Process(child) => matches!(child.try_wait(), Ok(None)),
+            Executor::Mock(_) => true,
+        }
+    }
+
+    /// True if this caller answers from a Rust closure.
+    pub fn is_mock(&self) -> bool {
+        matches!(self.executor, Executor::Mock(_))
     }
 
     pub fn run_precompile(&mut self, address: u8, input: &[u8]) -> CommunicationResult<Vec<u8>> {
-        let stdin = self.1.stdin.as_mut().expect("!stdin");
-        write_precompile_call(stdin, address, input)?;
+        let started = Instant::now();
+        let response = match &mut self.executor {
+            Executor::Process(child) => {
+                let stdin = child.stdin.as_mut().expect("!stdin");
+                write_precompile_call(stdin, address, input)?;
+
+                let stdout = child.stdout.as_mut().expect("!stdout");
+                read_precompile_result(stdout)?
+            }
+            Executor::Mock(handler) => handler(address, input),
+        };
+        let elapsed = started.elapsed();
+        self.latency.record(elapsed);
+        self.last = Some(Measurement {
+            elapsed,
+            gas: response.gas,
+        });
+        if let Some(transcript) = &mut self.transcript {
+            transcript.insert(address, input, response.clone());
+        }
+        response.result.map_err(CommunicationError::RemoteError)
+    }
 
-        let stdout = self.1.stdout.as_mut().expect("!stdout");
-        read_precompile_result(stdout)
+    /// The command this caller runs.
+    pub fn command(&self) -> &'static str {
+        self.command
+    }
+
+    /// The measurement of the last call, if any since the last take.
+    pub fn measurement(&self) -> Option<Measurement> {
+        self.last
+    }
+
+    /// Take the measurement of the last call, if any since the last take.
+    pub fn take_measurement(&mut self) -> Option<Measurement> {
+        self.last.take()
+    }
+
+    /// Start recording calls into a transcript.
+    pub fn record(&mut self) {
+        self.transcript.get_or_insert_with(Default::default);
+    }
+
+    /// Take the calls recorded since `record`, and stop recording.
+    pub fn take_transcript(&mut self) -> Transcript {
+        self.transcript.take().unwrap_or_default()
     }
 }
 
@@ -62,7 +197,7 @@ where
     Ok(())
 }
 
-fn read_precompile_result<R>(r: &mut R) -> CommunicationResult<Vec<u8>>
+fn read_precompile_result<R>(r: &mut R) -> CommunicationResult<Response>
 where
     R: Read,
 {
@@ -73,14 +208,16 @@ where
     let mut is_err = [0u8];
     r.read_exact(&mut is_err)?;
 
+    let mut gas = [0u8; 8];
+    r.read_exact(&mut gas)?;
+    let gas = u64::from_be_bytes(gas);
+
     let mut body = vec![0u8; body_size];
     r.read_exact(&mut body[..body_size])?;
 
     if is_err[0] == 1 {
-        Err(CommunicationError::RemoteError(
-            String::from_utf8(body).expect("!string"),
-        ))
+        Ok(Response::err(String::from_utf8(body).expect("!string")).set_gas(gas))
     } else {
-        Ok(body)
+        Ok(Response::ok(body).set_gas(gas))
     }
 }
\ No newline at end of file
@@ -0,0 +1,242 @@
+use std::{fmt, time::Duration};
+
+use crate::{
+    cli::{self, Mode, Opts, ParallelOpts, RegressionOpts, TestdataOpts},
+    fuzzer::{self, RunReport},
+    registry::{self, Registration},
+};
+
+/// A campaign cycles through registered targets, giving each a time slice per
+/// round. Fuzzer threads reuse the same executors across targets.
+#[derive(Clone)]
+pub struct Campaign {
+    verbose_errors: bool,
+    threads: usize,
+    slice: Duration,
+    rounds: Option<usize>,
+    mode: Option<Mode>,
+    parallel: ParallelOpts,
+    regression: RegressionOpts,
+    testdata: TestdataOpts,
+    targets: Vec<(&'static Registration, u32)>,
+}
+
+impl Default for Campaign {
+    fn default() -> Self {
+        Self {
+            verbose_errors: false,
+            threads: 4,
+            slice: Duration::from_secs(300),
+            rounds: None,
+            mode: None,
+            parallel: Default::default(),
+            regression: Default::default(),
+            testdata: Default::default(),
+            targets: registry::targets().into_iter().map(|r| (r, 1)).collect(),
+        }
+    }
+}
+
+impl Campaign {
+    /// Instantiate a new campaign over all registered targets. Alias for
+    /// `Default::default()`
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Set to true for verbose, false for silent. Verbose errors are printed to
+    /// terminal.
+    pub fn set_verbose_errors(mut self, v: bool) -> Self {
+        self.verbose_errors = v;
+        self
+    }
+
+    /// Set the number of threads
+    pub fn set_threads(mut self, t: usize) -> Self {
+        self.threads = t;
+        self
+    }
+
+    /// Set the unweighted time slice each target receives per round
+    pub fn set_slice(mut self, slice: Duration) -> Self {
+        self.slice = slice;
+        self
+    }
+
+    /// Set the number of rounds. If `None`, the campaign runs until
+    /// interrupted.
+    pub fn set_rounds(mut self, rounds: Option<usize>) -> Self {
+        self.rounds = rounds;
+        self
+    }
+
+    /// Set the mode to run targets in. Targets that don't support it run in
+    /// `Mode::Valid`. If `None`, each target runs in its preferred mode.
+    pub fn set_mode(mut self, mode: Option<Mode>) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Set the shard and sync dir options, which apply to every target.
+    pub fn set_parallel(mut self, parallel: ParallelOpts) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    /// Set the baseline builds, used when targets run in `Mode::Regression`.
+    pub fn set_regression(mut self, regression: RegressionOpts) -> Self {
+        self.regression = regression;
+        self
+    }
+
+    /// Set the go-ethereum test data. If set, every target's executors are
+    /// checked against their known answers before the campaign starts, and the
+    /// first slice of each target is seeded with its vectors.
+    pub fn set_testdata(mut self, testdata: TestdataOpts) -> Self {
+        self.testdata = testdata;
+        self
+    }
+
+    /// Restrict the campaign to the named targets, in the given order.
+    pub fn set_targets(mut self, names: &[&str]) -> Result<Self, String> {
+        self.targets = names
+            .iter()
+            .map(|name| {
+                registry::find(name)
+                    .map(|r| (r, 1))
+                    .ok_or_else(|| format!("unknown target: {}", name))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(self)
+    }
+
+    /// Set the weight of a target. Its time slice is multiplied by the weight.
+    pub fn set_weight(mut self, name: &str, weight: u32) -> Result<Self, String> {
+        let entry = self
+            .targets
+            .iter_mut()
+            .find(|(r, _)| r.name() == name)
+            .ok_or_else(|| format!("target not in campaign: {}", name))?;
+        entry.1 = weight;
+        Ok(self)
+    }
+
+    fn mode_for(&self, registration: &Registration) -> Mode {
+        match self.mode {
+            Some(mode) if registration.supports(mode) => mode,
+            Some(_) => Mode::Valid,
+            None => registration.preferred_mode(),
+        }
+    }
+
+    /// Run the campaign, and produce a combined report.
+    pub fn run(&self) -> CampaignReport {
+        let mut report = CampaignReport {
+            rounds: 0,
+            targets: self
+                .targets
+                .iter()
+                .map(|(r, _)| TargetReport {
+                    name: r.name(),
+                    mode: self.mode_for(r),
+                    slices: 0,
+                    run: Default::default(),
+                })
+                .collect(),
+        };
+
+        if let Some(dir) = &self.testdata.testdata {
+            let mut passed = true;
+            for (registration, weight) in self.targets.iter() {
+                if *weight > 0 {
+                    passed &= cli::known_answers(registration, dir);
+                }
+            }
+            if !passed {
+                println!("Known answer checks failed, not starting campaign");
+                return report;
+            }
+        }
+
+        while self.rounds.is_none_or(|r| report.rounds < r) && !fuzzer::interrupted() {
+            for ((registration, weight), entry) in
+                self.targets.iter().zip(report.targets.iter_mut())
+            {
+                if *weight == 0 || fuzzer::interrupted() {
+                    continue;
+                }
+
+                let opts = Opts {
+                    verbose_errors: self.verbose_errors,
+                    threads: self.threads,
+                    mode: entry.mode,
+                    duration: Some((self.slice * *weight).as_secs()),
+                    parallel: self.parallel.clone(),
+                    bench: Default::default(),
+                    gas: Default::default(),
+                    regression: self.regression.clone(),
+                    testdata: if entry.slices == 0 {
+                        self.testdata.clone()
+                    } else {
+                        Default::default()
+                    },
+                };
+                if let Some(run) = registration.run(&opts) {
+                    entry.slices += 1;
+                    entry.run.iterations += run.iterations;
+                    entry.run.failed += run.failed;
+                    entry.run.elapsed += run.elapsed;
+                }
+            }
+            report.rounds += 1;
+        }
+
+        report
+    }
+}
+
+/// Per-target totals over a campaign.
+#[derive(Clone, Debug)]
+pub struct TargetReport {
+    /// The target name
+    pub name: &'static str,
+    /// The mode the target ran in
+    pub mode: Mode,
+    /// The number of time slices the target ran
+    pub slices: usize,
+    /// Totals across all slices
+    pub run: RunReport,
+}
+
+/// Combined report for a campaign.
+#[derive(Clone, Debug)]
+pub struct CampaignReport {
+    /// The number of rounds started
+    pub rounds: usize,
+    /// Per-target totals
+    pub targets: Vec<TargetReport>,
+}
+
+impl fmt::Display for CampaignReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Campaign report after {} rounds", self.rounds)?;
+        writeln!(
+            f,
+            "{:<16}{:<10}{:>8}{:>14}{:>10}{:>12}",
+            "target", "mode", "slices", "iterations", "failed", "seconds"
+        )?;
+        for t in self.targets.iter() {
+            writeln!(
+                f,
+                "{:<16}{:<10}{:>8}{:>14}{:>10}{:>12}",
+                t.name,
+                t.mode.name(),
+                t.slices,
+                t.run.iterations,
+                t.run.failed,
+                t.run.elapsed.as_secs()
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -1,11 +1,99 @@
-use clap::Clap;
+use clap::{App, AppSettings, Clap, FromArgMatches, IntoApp};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
 
-use crate::{fuzzer::Fuzzer, traits::*};
+use crate::{
+    campaign::Campaign,
+    corpus::{Shard, SyncDir},
+    export,
+    fuzzer::{Fuzzer, RunReport},
+    registry::{self, Registration},
+    regression::{self, Baseline},
+    testdata,
+    traits::*,
+};
 
-// TODO: express this with less boileplate
+/// Fuzzer operation mode.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Mode {
+    /// Run valid inputs.
+    Valid,
+    /// Run valid inputs against a control.
+    Control,
+    /// Run mixed valid and invalid inputs.
+    Mixed,
+    /// Run invalid inputs only.
+    Invalid,
+    /// Measure executor latency on a seeded corpus.
+    Bench,
+    /// Flag inputs that are slow for the gas they cost.
+    Gas,
+    /// Compare executors to baseline builds of the same client.
+    Regression,
+    /// Check relations between the results of related inputs.
+    Metamorphic,
+}
+
+impl Mode {
+    /// The name of the mode, as accepted by `--mode`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mode::Valid => "valid",
+            Mode::Control => "control",
+            Mode::Mixed => "mixed",
+            Mode::Invalid => "invalid",
+            Mode::Bench => "bench",
+            Mode::Gas => "gas",
+            Mode::Regression => "regression",
+            Mode::Metamorphic => "metamorphic",
+        }
+    }
+
+    /// A short description of the mode.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Mode::Valid => "run valid inputs",
+            Mode::Control => "run valid inputs against control",
+            Mode::Mixed => "run with invalid inputs",
+            Mode::Invalid => "run invalid only",
+            Mode::Bench => "benchmark executor latency",
+            Mode::Gas => "flag underpriced inputs",
+            Mode::Regression => "compare against baseline executors",
+            Mode::Metamorphic => "check relations between related inputs",
+        }
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.name(), self.description())
+    }
+}
 
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "valid" => Ok(Mode::Valid),
+            "control" => Ok(Mode::Control),
+            "mixed" => Ok(Mode::Mixed),
+            "invalid" => Ok(Mode::Invalid),
+            "bench" => Ok(Mode::Bench),
+            "gas" => Ok(Mode::Gas),
+            "regression" => Ok(Mode::Regression),
+            "metamorphic" => Ok(Mode::Metamorphic),
+            _ => Err(format!("unknown mode: {}", s)),
+        }
+    }
+}
+
+/// Options for running a single target.
 #[derive(Clap)]
-#[clap(version = "1.0", author = "James Prestwich <prestwich@clabs.co>")]
 pub struct Opts {
     /// Set to print errors as they occur. Note: this currently will report
     /// false positives during thread shutdown.
@@ -16,110 +104,546 @@ pub struct Opts {
     #[clap(short, long, default_value = "4")]
     pub threads: usize,
 
-    /// Operation mode. 0 to run valid inputs. 1 to run valid inputs against a
-    /// control. 2 to run mixed valid and invalid inputs. 3 to run invalid
-    /// inputs only.
-    #[clap(short, long, default_value = "0")]
-    pub mode: usize,
+    /// Operation mode. Run `smash list` to see the modes each target
+    /// supports.
+    #[clap(short, long, default_value = "valid")]
+    pub mode: Mode,
+
+    /// Stop after this many seconds. If not set, runs until interrupted.
+    #[clap(short, long)]
+    pub duration: Option<u64>,
+
+    #[clap(flatten)]
+    pub parallel: ParallelOpts,
+
+    #[clap(flatten)]
+    pub bench: BenchOpts,
+
+    #[clap(flatten)]
+    pub gas: GasOpts,
+
+    #[clap(flatten)]
+    pub regression: RegressionOpts,
+
+    #[clap(flatten)]
+    pub testdata: TestdataOpts,
+}
+
+/// Options for go-ethereum test data.
+#[derive(Clap, Clone, Debug, Default)]
+pub struct TestdataOpts {
+    /// Path to `core/vm/testdata/precompiles` in a local go-ethereum checkout.
+    /// Executors are checked against the known answers before fuzzing starts,
+    /// and the inputs seed the corpus.
+    #[clap(long)]
+    pub testdata: Option<PathBuf>,
+}
+
+/// Options for the `bench` mode.
+#[derive(Clap, Clone, Debug)]
+pub struct BenchOpts {
+    /// The number of inputs in the benchmark corpus.
+    #[clap(long, default_value = "1000")]
+    pub count: usize,
+
+    /// Write the benchmark report to this file instead of stdout.
+    #[clap(long)]
+    pub out: Option<PathBuf>,
+
+    /// Also record the executors' responses to the corpus in this directory,
+    /// as `<target>.celo.txt` and `<target>.geth.txt`, for replay in tests.
+    #[clap(long)]
+    pub transcripts: Option<PathBuf>,
+}
+
+impl Default for BenchOpts {
+    fn default() -> Self {
+        Self {
+            count: 1000,
+            out: None,
+            transcripts: None,
+        }
+    }
+}
+
+/// Options for the `gas` mode.
+#[derive(Clap, Clone, Debug)]
+pub struct GasOpts {
+    /// Directory to save inputs that are slow for the gas they cost.
+    #[clap(long, default_value = "anomalies")]
+    pub anomaly_dir: PathBuf,
+}
+
+impl Default for GasOpts {
+    fn default() -> Self {
+        Self {
+            anomaly_dir: "anomalies".into(),
+        }
+    }
+}
+
+/// Options for the `regression` mode.
+#[derive(Clap, Clone, Debug)]
+pub struct RegressionOpts {
+    /// Path to a baseline build of the celo executor.
+    #[clap(long, parse(from_os_str = regression::command))]
+    pub baseline_celo: Option<&'static str>,
+
+    /// Path to a baseline build of the geth executor.
+    #[clap(long, parse(from_os_str = regression::command))]
+    pub baseline_geth: Option<&'static str>,
+
+    /// Directory to save inputs that regressed.
+    #[clap(long, default_value = "regressions")]
+    pub regression_dir: PathBuf,
+}
+
+impl Default for RegressionOpts {
+    fn default() -> Self {
+        Self {
+            baseline_celo: None,
+            baseline_geth: None,
+            regression_dir: "regressions".into(),
+        }
+    }
+}
+
+impl RegressionOpts {
+    /// The baseline, if any baseline build was set.
+    pub fn baseline(&self) -> Option<Baseline> {
+        if self.baseline_celo.is_none() && self.baseline_geth.is_none() {
+            return None;
+        }
+        let mut baseline = Baseline::new();
+        if let Some(command) = self.baseline_celo {
+            baseline = baseline.set_celo(command);
+        }
+        if let Some(command) = self.baseline_geth {
+            baseline = baseline.set_geth(command);
+        }
+        Some(baseline)
+    }
+}
+
+/// Options for running several instances in parallel.
+#[derive(Clap, Clone, Debug, Default)]
+pub struct ParallelOpts {
+    /// Directory shared with other instances. Corpus entries and findings are
+    /// saved there, and entries from other instances are replayed.
+    #[clap(long)]
+    pub sync_dir: Option<PathBuf>,
+
+    /// The name of this instance in the sync dir. Must be unique among
+    /// instances. Defaults to the hostname and process id.
+    #[clap(long)]
+    pub instance: Option<String>,
+
+    /// Run only part of the seed space, as `i/n`. Instances with distinct
+    /// shards do not duplicate work.
+    #[clap(long)]
+    pub shard: Option<Shard>,
+}
+
+impl ParallelOpts {
+    /// The sync dir, if one was set.
+    pub fn sync(&self) -> Option<SyncDir> {
+        let instance = self
+            .instance
+            .clone()
+            .unwrap_or_else(SyncDir::default_instance);
+        self.sync_dir
+            .as_ref()
+            .map(|path| SyncDir::new(path, instance))
+    }
+}
+
+/// Options for exporting saved entries as test vectors.
+#[derive(Clap)]
+pub struct ExportOpts {
+    /// The target to export.
+    pub target: String,
+
+    /// Directory to search for saved entries: a sync dir, an anomaly dir or a
+    /// regression dir. May be repeated.
+    #[clap(long, required = true)]
+    pub from: Vec<PathBuf>,
+
+    /// Directory to write `<target>.json` and `<target>.rs` to.
+    #[clap(short, long, default_value = "export")]
+    pub out: PathBuf,
+}
+
+/// Options for running a campaign across several targets.
+#[derive(Clap)]
+pub struct CampaignOpts {
+    /// Set to print errors as they occur. Note: this currently will report
+    /// false positives during thread shutdown.
+    #[clap(short, long)]
+    pub verbose_errors: bool,
+
+    /// The number of fuzzer threads to run.
+    #[clap(short, long, default_value = "4")]
+    pub threads: usize,
+
+    /// Comma-separated list of targets to run. Defaults to all registered
+    /// targets.
+    #[clap(long)]
+    pub targets: Option<String>,
+
+    /// Seconds each target runs per round, before weighting.
+    #[clap(short, long, default_value = "300")]
+    pub slice: u64,
+
+    /// Comma-separated list of `target=weight` pairs. A target's time slice is
+    /// multiplied by its weight. Unlisted targets have weight 1.
+    #[clap(short, long)]
+    pub weights: Option<String>,
+
+    /// The number of rounds to run. If not set, runs until interrupted.
+    #[clap(short, long)]
+    pub rounds: Option<usize>,
+
+    /// Operation mode. Targets that don't support it run in `valid` mode. If
+    /// not set, each target runs in the most thorough mode it supports.
+    #[clap(short, long)]
+    pub mode: Option<Mode>,
+
+    #[clap(flatten)]
+    pub parallel: ParallelOpts,
+
+    #[clap(flatten)]
+    pub regression: RegressionOpts,
+
+    #[clap(flatten)]
+    pub testdata: TestdataOpts,
 }
 
-fn mode_name(mode: usize) -> &'static str {
-    match mode {
-        0 => "0 (run valid inputs)",
-        1 => "1 (run valid inputs against control)",
-        2 => "2 (run with invalid inputs)",
-        3 => "3 (run invalid only)",
-        _ => "unknown mode",
+impl CampaignOpts {
+    /// Build a campaign from these options.
+    pub fn campaign(&self) -> Result<Campaign, String> {
+        // bench mode measures a fixed corpus, and has no work to do in slices
+        if self.mode == Some(Mode::Bench) {
+            return Err("bench mode is not supported in campaigns".to_owned());
+        }
+
+        let mut campaign = Campaign::new()
+            .set_verbose_errors(self.verbose_errors)
+            .set_threads(self.threads)
+            .set_slice(Duration::from_secs(self.slice))
+            .set_rounds(self.rounds)
+            .set_mode(self.mode)
+            .set_parallel(self.parallel.clone())
+            .set_regression(self.regression.clone())
+            .set_testdata(self.testdata.clone());
+
+        if let Some(targets) = &self.targets {
+            let names: Vec<_> = targets.split(',').map(str::trim).collect();
+            campaign = campaign.set_targets(&names)?;
+        }
+
+        if let Some(weights) = &self.weights {
+            for pair in weights.split(',') {
+                let mut split = pair.splitn(2, '=');
+                let name = split.next().unwrap_or_default().trim();
+                let weight = split
+                    .next()
+                    .and_then(|w| w.trim().parse().ok())
+                    .ok_or_else(|| format!("invalid weight: {}", pair))?;
+                campaign = campaign.set_weight(name, weight)?;
+            }
+        }
+
+        Ok(campaign)
     }
 }
 
-fn setup<T>() -> (Opts, Fuzzer<T>)
+fn setup<T>(opts: &Opts) -> Option<Fuzzer<T>>
 where
     T: Target,
 {
-    let opts = Opts::parse();
-
-    let fuzzer = T::new_fuzzer()
+    let mut fuzzer = T::new_fuzzer()
         .set_verbose_errors(opts.verbose_errors)
-        .set_threads(opts.threads);
+        .set_threads(opts.threads)
+        .set_duration(opts.duration.map(Duration::from_secs))
+        .set_shard(opts.parallel.shard)
+        .set_sync(opts.parallel.sync());
 
     println!(
         "Running {} on mode {} with {} threads",
         T::name(),
-        mode_name(opts.mode),
+        opts.mode,
         opts.threads
     );
+    if let Some(shard) = fuzzer.shard() {
+        println!("Running shard {}", shard);
+    }
+    if let Some(sync) = fuzzer.sync() {
+        println!(
+            "Syncing with {} as {}",
+            sync.path().display(),
+            sync.instance()
+        );
+    }
+
+    if let Some(dir) = &opts.testdata.testdata {
+        if !known_answers(registry::find(T::name())?, dir) {
+            return None;
+        }
+        let seeds = testdata::seeds::<T>(dir).expect("couldn't load test data");
+        if !seeds.is_empty() {
+            println!("Seeding with {} test vectors", seeds.len());
+        }
+        fuzzer = fuzzer.set_seeds(seeds);
+    }
+
+    Some(fuzzer)
+}
+
+/// Check a target's executors against its known answers, and print the
+/// results. Returns true if all answers were correct, or the target has no
+/// test vectors.
+pub fn known_answers(registration: &Registration, dir: &Path) -> bool {
+    match registration.known_answers(dir) {
+        Ok(answers) if answers.checked == 0 => true,
+        Ok(answers) => {
+            print!("{}", answers);
+            answers.passed()
+        }
+        Err(e) => {
+            println!("{}: couldn't load test data: {}", registration.name(), e);
+            false
+        }
+    }
+}
+
+fn bench<T>(fuzzer: Fuzzer<T>, opts: &BenchOpts) -> Option<RunReport>
+where
+    T: Target<Rng = lain::rand::rngs::StdRng>,
+{
+    let report = fuzzer.bench(opts.count);
+    match &opts.out {
+        Some(path) => {
+            std::fs::write(path, report.to_string()).expect("could not write bench report");
+            println!("Wrote bench report to {}", path.display());
+        }
+        None => print!("{}", report),
+    }
+    if let Some(dir) = &opts.transcripts {
+        let (celo, geth) = fuzzer.transcribe(opts.count);
+        for (executor, transcript) in [("celo", celo), ("geth", geth)].iter() {
+            let path = dir.join(format!("{}.{}.txt", T::name(), executor));
+            transcript.save(&path).expect("could not write transcript");
+            println!("Wrote {} calls to {}", transcript.len(), path.display());
+        }
+    }
+    None
+}
+
+fn gas<T>(fuzzer: Fuzzer<T>, opts: &GasOpts) -> Option<RunReport>
+where
+    T: Target<Rng = lain::rand::rngs::StdRng>,
+{
+    let report = fuzzer.run_gas(&opts.anomaly_dir);
+    print!("{}", report);
+    Some(report.run)
+}
+
+fn regression<T>(fuzzer: Fuzzer<T>, opts: &RegressionOpts) -> Option<RunReport>
+where
+    T: Target<Rng = lain::rand::rngs::StdRng>,
+{
+    match opts.baseline() {
+        Some(baseline) => {
+            println!(
+                "Comparing against {} and {}",
+                baseline.celo(),
+                baseline.geth()
+            );
+            Some(fuzzer.run_regression(&baseline, &opts.regression_dir))
+        }
+        None => {
+            println!("Regression mode needs --baseline-celo or --baseline-geth");
+            None
+        }
+    }
+}
 
-    (opts, fuzzer)
+fn unsupported<T>(mode: Mode) -> Option<RunReport>
+where
+    T: Target,
+{
+    let supported = registry::find(T::name())
+        .map(|r| r.modes().iter().map(Mode::name).collect::<Vec<_>>().join(", "))
+        .unwrap_or_default();
+    println!(
+        "Unsupported mode: {}. Supported for {} are: {}",
+        mode,
+        T::name(),
+        supported
+    );
+    None
 }
 
-pub fn target<T>()
+pub fn target<T>(opts: &Opts) -> Option<RunReport>
 where
     T: Target<Rng = lain::rand::rngs::StdRng>,
 {
-    let (opts, fuzzer) = setup::<T>();
+    let fuzzer = setup::<T>(opts)?;
 
     match opts.mode {
-        0 => fuzzer.run(),
-        _ => println!(
-            "Unsupported mode: {}. Supported for {} is 0",
-            mode_name(opts.mode),
-            T::name()
-        ),
+        Mode::Valid => Some(fuzzer.run()),
+        Mode::Bench => bench(fuzzer, &opts.bench),
+        Mode::Gas => gas(fuzzer, &opts.gas),
+        Mode::Regression => regression(fuzzer, &opts.regression),
+        mode => unsupported::<T>(mode),
     }
 }
 
-pub fn target_with_control<T>()
+pub fn target_with_control<T>(opts: &Opts) -> Option<RunReport>
 where
     T: TargetWithControl + Target<Rng = lain::rand::rngs::StdRng>,
 {
-    let (opts, fuzzer) = setup::<T>();
+    let fuzzer = setup::<T>(opts)?;
 
     match opts.mode {
-        0 => fuzzer.run(),
-        1 => fuzzer.run_against_control(),
-        _ => println!(
-            "Unsupported mode: {}. Supported for {} are 0 & 1",
-            mode_name(opts.mode),
-            T::name()
-        ),
+        Mode::Valid => Some(fuzzer.run()),
+        Mode::Control => Some(fuzzer.run_against_control()),
+        Mode::Bench => bench(fuzzer, &opts.bench),
+        Mode::Gas => gas(fuzzer, &opts.gas),
+        Mode::Regression => regression(fuzzer, &opts.regression),
+        mode => unsupported::<T>(mode),
     }
 }
 
-pub fn produce_invalid<T>()
+pub fn produce_invalid<T>(opts: &Opts) -> Option<RunReport>
 where
     T: ProduceInvalid + Target<Rng = lain::rand::rngs::StdRng>,
 {
-    let (opts, fuzzer) = setup::<T>();
+    let fuzzer = setup::<T>(opts)?;
 
     match opts.mode {
-        0 => fuzzer.run(),
-        // 1 => fuzzer.run_against_control(),
-        2 => fuzzer.run_mixed(),
-        3 => fuzzer.run_invalid(),
-        _ => println!(
-            "Unsupported mode: {}. Supported for {} are 0, 2, & 3",
-            mode_name(opts.mode),
-            T::name()
-        ),
+        Mode::Valid => Some(fuzzer.run()),
+        Mode::Mixed => Some(fuzzer.run_mixed()),
+        Mode::Invalid => Some(fuzzer.run_invalid()),
+        Mode::Bench => bench(fuzzer, &opts.bench),
+        Mode::Gas => gas(fuzzer, &opts.gas),
+        Mode::Regression => regression(fuzzer, &opts.regression),
+        mode => unsupported::<T>(mode),
     }
 }
 
-pub fn produce_invalid_with_control<T>()
+pub fn produce_invalid_with_control<T>(opts: &Opts) -> Option<RunReport>
 where
     T: TargetWithControl + ProduceInvalid + Target<Rng = lain::rand::rngs::StdRng>,
 {
-    let (opts, fuzzer) = setup::<T>();
+    let fuzzer = setup::<T>(opts)?;
 
     match opts.mode {
-        0 => fuzzer.run(),
-        1 => fuzzer.run_against_control(),
-        2 => fuzzer.run_mixed(),
-        3 => fuzzer.run_invalid(),
-        _ => println!(
-            "Unsupported mode: {}. Supported for {} are 0, 1, 2, & 3",
-            mode_name(opts.mode),
-            T::name()
+        Mode::Valid => Some(fuzzer.run()),
+        Mode::Control => Some(fuzzer.run_against_control()),
+        Mode::Mixed => Some(fuzzer.run_mixed()),
+        Mode::Invalid => Some(fuzzer.run_invalid()),
+        Mode::Bench => bench(fuzzer, &opts.bench),
+        Mode::Gas => gas(fuzzer, &opts.gas),
+        Mode::Regression => regression(fuzzer, &opts.regression),
+        mode => unsupported::<T>(mode),
+    }
+}
+
+pub fn metamorphic<T>(opts: &Opts) -> Option<RunReport>
+where
+    T: Metamorphic + Target<Rng = lain::rand::rngs::StdRng>,
+{
+    let fuzzer = setup::<T>(opts)?;
+
+    match opts.mode {
+        Mode::Metamorphic => Some(fuzzer.run_metamorphic()),
+        mode => unsupported::<T>(mode),
+    }
+}
+
+pub fn export<T>(opts: &ExportOpts)
+where
+    T: TargetWithControl,
+{
+    match export::export::<T>(&opts.from, &opts.out) {
+        Ok((exported, skipped)) => println!(
+            "Exported {} vectors for {} to {}, skipped {} entries without a control result",
+            exported,
+            T::name(),
+            opts.out.display(),
+            skipped
         ),
+        Err(e) => eprintln!("Failed to export {}: {}", T::name(), e),
+    }
+}
+
+fn list() {
+    let targets = registry::targets();
+    let width = targets.iter().map(|r| r.name().len()).max().unwrap_or(0) + 2;
+    for registration in targets {
+        let modes: Vec<_> = registration.modes().iter().map(Mode::name).collect();
+        println!(
+            "{:<width$}{}",
+            registration.name(),
+            modes.join(", "),
+            width = width
+        );
+    }
+}
+
+/// Entry point for the `smash` binary. Each registered target is a
+/// subcommand, `smash list` prints the targets and their modes,
+/// `smash campaign` cycles through several targets, and `smash export` turns
+/// saved entries into test vectors.
+pub fn run() {
+    let names: Vec<_> = registry::targets().iter().map(|r| r.name()).collect();
+    let mut app = App::new("smash")
+        .version("1.0")
+        .author("James Prestwich <prestwich@clabs.co>")
+        .about("Simple precompile fuzzer with geth and celo bindings")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .setting(AppSettings::VersionlessSubcommands)
+        .subcommand(App::new("list").about("List targets and their supported modes"))
+        .subcommand(
+            CampaignOpts::into_app()
+                .name("campaign")
+                .about("Cycle through several targets in one process"),
+        )
+        .subcommand(
+            ExportOpts::into_app()
+                .name("export")
+                .about("Export saved entries as go-ethereum test vectors and Rust tests")
+                .mut_arg("target", |arg| arg.possible_values(&names)),
+        );
+
+    for registration in registry::targets() {
+        let modes: Vec<_> = registration.modes().iter().map(Mode::name).collect();
+        app = app.subcommand(
+            Opts::into_app()
+                .name(registration.name())
+                .about("Fuzz this target")
+                .mut_arg("mode", |arg| arg.possible_values(&modes)),
+        );
+    }
+
+    match app.get_matches().subcommand() {
+        Some(("list", _)) => list(),
+        Some(("campaign", matches)) => match CampaignOpts::from_arg_matches(matches).campaign() {
+            Ok(campaign) => println!("{}", campaign.run()),
+            Err(e) => eprintln!("{}", e),
+        },
+        Some(("export", matches)) => {
+            let opts = ExportOpts::from_arg_matches(matches);
+            let registration = registry::find(&opts.target).expect("targets are registered");
+            if !registration.export(&opts) {
+                eprintln!("{} has no control to compute expected outputs", opts.target);
+            }
+        }
+        Some((name, matches)) => {
+            let registration = registry::find(name).expect("subcommands are registered targets");
+            registration.run(&Opts::from_arg_matches(matches));
+        }
+        None => unreachable!("subcommand is required"),
     }
 }
@@ -0,0 +1,264 @@
+use sha2::Digest;
+use std::{
+    collections::{HashSet, VecDeque},
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex, Weak},
+    time::Duration,
+};
+
+use crate::errors::{CommunicationError, CommunicationResult};
+
+/// How often instances scan the sync directory for new entries.
+const SYNC_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A deterministic partition of the seed space. Shard `i/n` starts at the
+/// `i`th of `n` equally sized regions, so parallel runs with the same base
+/// seed do not repeat each other's inputs.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Shard {
+    index: u64,
+    count: u64,
+}
+
+impl Shard {
+    /// Instantiate a shard. Errors if `index >= count`.
+    pub fn new(index: u64, count: u64) -> Result<Self, String> {
+        if index >= count {
+            return Err(format!(
+                "shard index {} out of range for {} shards",
+                index, count
+            ));
+        }
+        Ok(Self { index, count })
+    }
+
+    /// The first seed of this shard's region, offset from `base`.
+    pub fn seed(&self, base: u64) -> u64 {
+        base.wrapping_add(self.index * (u64::MAX / self.count))
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+impl FromStr for Shard {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut split = s.splitn(2, '/');
+        let mut parse = || -> Option<u64> { split.next()?.trim().parse().ok() };
+        match (parse(), parse()) {
+            (Some(index), Some(count)) => Self::new(index, count),
+            _ => Err(format!("invalid shard, expected i/n: {}", s)),
+        }
+    }
+}
+
+/// A directory shared between smash instances, on one host or on several
+/// hosts sharing a filesystem. Each instance writes only to its own
+/// subdirectory and reads everyone else's:
+///
+/// `<path>/<instance>/<target>/{corpus,findings,violations}/<sha256 of input>`
+#[derive(Clone, Debug)]
+pub struct SyncDir {
+    path: PathBuf,
+    instance: String,
+}
+
+impl SyncDir {
+    /// Instantiate a sync dir. `instance` must be unique among the instances
+    /// sharing `path`.
+    pub fn new(path: impl Into<PathBuf>, instance: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            instance: instance.into(),
+        }
+    }
+
+    /// A default instance name: the host, the pid and a random suffix, as
+    /// hosts may share a name or pid, or not set `$HOSTNAME`.
+    pub fn default_instance() -> String {
+        let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "smash".to_owned());
+        format!(
+            "{}-{}-{:08x}",
+            host,
+            std::process::id(),
+            rand::random::<u32>()
+        )
+    }
+
+    /// Getter for the shared path.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Getter for the instance name.
+    pub fn instance(&self) -> &str {
+        &self.instance
+    }
+}
+
+/// A target's view of a sync dir. It records this instance's corpus entries,
+/// findings and invariant violations, and queues entries from other instances
+/// to be replayed.
+#[derive(Debug)]
+pub struct Corpus {
+    sync: SyncDir,
+    target: &'static str,
+    seen: Mutex<HashSet<String>>,
+    imported: Mutex<HashSet<PathBuf>>,
+    queue: Mutex<VecDeque<Vec<u8>>>,
+}
+
+impl Corpus {
+    /// Open the corpus for `target`, creating this instance's directories, and
+    /// start syncing in the background. Syncing stops when the last `Arc` is
+    /// dropped.
+    pub fn open(sync: &SyncDir, target: &'static str) -> io::Result<Arc<Self>> {
+        let corpus = Arc::new(Self {
+            sync: sync.clone(),
+            target,
+            seen: Default::default(),
+            imported: Default::default(),
+            queue: Default::default(),
+        });
+        fs::create_dir_all(corpus.own_dir("corpus"))?;
+        fs::create_dir_all(corpus.own_dir("findings"))?;
+        fs::create_dir_all(corpus.own_dir("violations"))?;
+
+        let weak = Arc::downgrade(&corpus);
+        std::thread::spawn(move || sync_loop(weak));
+
+        Ok(corpus)
+    }
+
+    fn own_dir(&self, kind: &str) -> PathBuf {
+        self.sync
+            .path
+            .join(&self.sync.instance)
+            .join(self.target)
+            .join(kind)
+    }
+
+    fn write(&self, kind: &str, input: &[u8]) -> io::Result<()> {
+        let name = hex::encode(sha2::Sha256::digest(input));
+        let dir = self.own_dir(kind);
+        let path = dir.join(&name);
+        if path.exists() {
+            return Ok(());
+        }
+        // write then rename, so that other instances never read partial files
+        let tmp = dir.join(format!(".{}", name));
+        fs::write(&tmp, input)?;
+        fs::rename(&tmp, &path)
+    }
+
+    /// Record an input that produced an error.
+    pub fn add_finding(&self, input: &[u8]) {
+        if let Err(e) = self.write("findings", input) {
+            eprintln!("Failed to save finding: {}", e);
+        }
+    }
+
+    /// Record an input whose results violated one of the target's
+    /// invariants.
+    pub fn add_violation(&self, input: &[u8]) {
+        if let Err(e) = self.write("violations", input) {
+            eprintln!("Failed to save violation: {}", e);
+        }
+    }
+
+    /// Record an input and its outcome. The input is added to the corpus if
+    /// this instance has not seen the outcome before.
+    pub fn observe(&self, input: &[u8], outcome: String) {
+        if self.seen.lock().unwrap().insert(outcome) {
+            if let Err(e) = self.write("corpus", input) {
+                eprintln!("Failed to save corpus entry: {}", e);
+            }
+        }
+    }
+
+    /// Pop the next entry imported from another instance, if any.
+    pub fn next_import(&self) -> Option<Vec<u8>> {
+        self.queue.lock().unwrap().pop_front()
+    }
+
+    /// Scan other instances' directories for entries not yet imported, and
+    /// queue them for replay. Returns the number of new entries. Entries that
+    /// can't be read are logged, and retried on the next sync.
+    pub fn sync(&self) -> io::Result<usize> {
+        let mut new = vec![];
+        for instance in fs::read_dir(&self.sync.path)? {
+            let instance = instance?;
+            if instance.file_name() == self.sync.instance.as_str() {
+                continue;
+            }
+            for kind in ["corpus", "findings", "violations"].iter() {
+                let dir = instance.path().join(self.target).join(kind);
+                let entries = match fs::read_dir(&dir) {
+                    Ok(entries) => entries,
+                    Err(_) => continue,
+                };
+                for entry in entries {
+                    let path = match entry {
+                        Ok(entry) => entry.path(),
+                        Err(e) => {
+                            eprintln!("Failed to list {}: {}", dir.display(), e);
+                            continue;
+                        }
+                    };
+                    let hidden = path
+                        .file_name()
+                        .is_none_or(|n| n.to_string_lossy().starts_with('.'));
+                    if hidden || self.imported.lock().unwrap().contains(&path) {
+                        continue;
+                    }
+                    match fs::read(&path) {
+                        Ok(input) => {
+                            self.imported.lock().unwrap().insert(path);
+                            new.push(input);
+                        }
+                        Err(e) => eprintln!("Failed to import {}: {}", path.display(), e),
+                    }
+                }
+            }
+        }
+
+        let count = new.len();
+        self.queue.lock().unwrap().extend(new);
+        Ok(count)
+    }
+}
+
+fn sync_loop(corpus: Weak<Corpus>) {
+    loop {
+        match corpus.upgrade() {
+            Some(corpus) => {
+                if let Err(e) = corpus.sync() {
+                    eprintln!("Failed to sync {}: {}", corpus.sync.path.display(), e);
+                }
+            }
+            None => return,
+        };
+        std::thread::sleep(SYNC_INTERVAL);
+    }
+}
+
+/// Summarize a set of experimental results as a string. Inputs with the same
+/// outcome took similar paths through the executors.
+pub fn outcome(results: &[CommunicationResult<Vec<u8>>]) -> String {
+    results
+        .iter()
+        .map(|r| match r {
+            Ok(buf) => format!("ok:{}", buf.len()),
+            Err(CommunicationError::RemoteError(e)) => format!("err:{}", e),
+            Err(CommunicationError::IoError(_)) => "io".to_owned(),
+        })
+        .collect::<Vec<_>>()
+        .join("|")
+}
@@ -21,11 +21,37 @@ pub enum ComparisonError {
     ErrNotEqual(String, String),
     LeftErr(String, Vec<u8>),
     RightErr(Vec<u8>, String),
+    GasNotEqual(u64, u64),
     NoComp,
 }
 
 impl ComparisonError {
-    fn strings(&self) -> (String, String, String) {
+    /// The name of the variant.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ComparisonError::OkNotEqual(..) => "OkNotEqual",
+            ComparisonError::ErrNotEqual(..) => "ErrNotEqual",
+            ComparisonError::LeftErr(..) => "LeftErr",
+            ComparisonError::RightErr(..) => "RightErr",
+            ComparisonError::GasNotEqual(..) => "GasNotEqual",
+            ComparisonError::NoComp => "NoComp",
+        }
+    }
+
+    /// Render the error with outputs shown by `display`, e.g. as decoded
+    /// curve points. `Display` shows them as hex.
+    pub fn describe(&self, display: &dyn Fn(&[u8]) -> String) -> String {
+        if *self == ComparisonError::NoComp {
+            return "\nComparisonError::NoComp".to_owned();
+        }
+        let (variant, left, right) = self.strings(display);
+        format!(
+            "ComparisonError {} {{\n\tleft:  {}\n\tright: {}\n}}\n",
+            variant, left, right
+        )
+    }
+
+    fn strings(&self, display: &dyn Fn(&[u8]) -> String) -> (String, String, String) {
         let wrap_err = |e: &str| -> String {
             let mut s = "Err:\t".to_owned();
             s.push_str(e);
@@ -33,35 +59,50 @@ impl ComparisonError {
         };
 
         match self {
-            ComparisonError::OkNotEqual(left, right) => (
-                "OkNotEqual".to_owned(),
-                hex::encode(&left),
-                hex::encode(&right),
-            ),
+            ComparisonError::OkNotEqual(left, right) => {
+                ("OkNotEqual".to_owned(), display(left), display(right))
+            }
             ComparisonError::ErrNotEqual(left, right) => {
                 ("ErrNotEqual".to_owned(), wrap_err(left), wrap_err(right))
             }
             ComparisonError::LeftErr(left, right) => {
-                ("LeftErr".to_owned(), wrap_err(left), hex::encode(&right))
+                ("LeftErr".to_owned(), wrap_err(left), display(right))
             }
             ComparisonError::RightErr(left, right) => {
-                ("RightErr".to_owned(), hex::encode(left), wrap_err(right))
+                ("RightErr".to_owned(), display(left), wrap_err(right))
             }
+            ComparisonError::GasNotEqual(left, right) => (
+                "GasNotEqual".to_owned(),
+                format!("{} gas", left),
+                format!("{} gas", right),
+            ),
             ComparisonError::NoComp => ("NoComp".to_owned(), "".to_owned(), "".to_owned()),
         }
     }
 }
 
+/// A property a target's results must always have, and did not.
+#[derive(Debug, Eq, PartialEq, Clone, Error)]
+#[error("Invariant {name} violated: {message}")]
+pub struct InvariantViolation {
+    /// A short name for the property
+    pub name: &'static str,
+    /// What the results did instead
+    pub message: String,
+}
+
+impl InvariantViolation {
+    /// Instantiate a violation of the named property.
+    pub fn new(name: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            name,
+            message: message.into(),
+        }
+    }
+}
+
 impl std::fmt::Display for ComparisonError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        if *self == ComparisonError::NoComp {
-            write!(f, "\nComparisonError::NoComp")
-        } else {
-            let (variant, left, right) = self.strings();
-            writeln!(f, "ComparisonError {} {{", variant)?;
-            writeln!(f, "\tleft:  {}", left)?;
-            writeln!(f, "\tright: {}", right)?;
-            writeln!(f, "}}")
-        }
+        write!(f, "{}", self.describe(&|output| hex::encode(output)))
     }
 }
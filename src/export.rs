@@ -0,0 +1,196 @@
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    call,
+    traits::{TargetWithControl, ThreadContext},
+};
+
+/// A test vector, in the shape of go-ethereum's precompile test data.
+#[derive(Clone, Debug)]
+pub struct Vector {
+    /// A unique name, from the input variant and hash
+    pub name: String,
+    /// The serialized input
+    pub input: Vec<u8>,
+    /// The control's output
+    pub expected: Vec<u8>,
+    /// The gas reported by the celo executor, or by geth if the target does
+    /// not call celo
+    pub gas: u64,
+}
+
+/// Find entries saved for `target` under `dirs`. This accepts every layout
+/// smash writes: sync dirs, anomaly dirs and regression dirs. Entries are
+/// files named by the sha256 of their contents, below a directory named after
+/// the target.
+pub fn find_entries(dirs: &[PathBuf], target: &str) -> io::Result<BTreeMap<String, Vec<u8>>> {
+    let mut entries = BTreeMap::new();
+    for dir in dirs.iter() {
+        walk(dir, target, false, &mut entries)?;
+    }
+    Ok(entries)
+}
+
+fn walk(
+    dir: &Path,
+    target: &str,
+    in_target: bool,
+    entries: &mut BTreeMap<String, Vec<u8>>,
+) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if path.is_dir() {
+            walk(&path, target, in_target || name == target, entries)?;
+        } else if in_target && name.len() == 64 && hex::decode(&name).is_ok() {
+            entries.insert(name, fs::read(&path)?);
+        }
+    }
+    Ok(())
+}
+
+/// Build vectors from saved entries. Entries the target can't parse, or that
+/// its control rejects, are skipped.
+pub fn vectors<T>(entries: &BTreeMap<String, Vec<u8>>) -> Vec<Vector>
+where
+    T: TargetWithControl,
+{
+    let mut target = T::new(None);
+    let mut ctx = ThreadContext::default();
+
+    entries
+        .iter()
+        .filter_map(|(hash, input)| {
+            let parsed = target.parse(input)?;
+            let expected = target.run_control(&parsed).ok()?;
+            let name = format!("{}_{}", target.variant(&parsed), &hash[..8]);
+
+            target.run_raw(&mut ctx, input);
+            let measurements = ctx.take_measurements();
+            let gas = measurements
+                .iter()
+                .find(|(command, _)| *command == call::CELO)
+                .or_else(|| measurements.first())
+                .map_or(0, |(_, m)| m.gas);
+
+            Some(Vector {
+                name,
+                input: input.clone(),
+                expected,
+                gas,
+            })
+        })
+        .collect()
+}
+
+/// A vector in go-ethereum's JSON layout.
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct GoVector<'a> {
+    input: String,
+    expected: String,
+    name: &'a str,
+    gas: u64,
+    no_benchmark: bool,
+}
+
+/// Render vectors as go-ethereum `testdata/precompiles/*.json`.
+pub fn go_vectors(vectors: &[Vector]) -> String {
+    let entries: Vec<_> = vectors
+        .iter()
+        .map(|v| GoVector {
+            input: hex::encode(&v.input),
+            expected: hex::encode(&v.expected),
+            name: &v.name,
+            gas: v.gas,
+            no_benchmark: false,
+        })
+        .collect();
+    let mut json = serde_json::to_string_pretty(&entries).expect("vectors serialize");
+    json.push('\n');
+    json
+}
+
+// Test names are Rust identifiers, whatever the target and variant names hold
+fn test_name(target: &str, vector: &str) -> String {
+    format!("{}_{}", target, vector)
+        .to_ascii_lowercase()
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | '0'..='9' | '_' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+/// Render vectors as Rust tests that check the target's control. Written to
+/// `tests/`, they keep fixed control bugs fixed.
+pub fn rust_tests<T>(vectors: &[Vector]) -> String
+where
+    T: TargetWithControl,
+{
+    let mut out = format!(
+        "//! Regression tests for {}, exported by `smash export`.
+
+use smash::traits::{{Target, TargetWithControl}};
+
+fn check(input: &str, expected: &str) {{
+    let target = {}::default();
+    let input = target
+        .parse(&hex::decode(input).unwrap())
+        .expect(\"unparseable input\");
+    assert_eq!(hex::encode(target.run_control(&input).unwrap()), expected);
+}}
+",
+        T::name(),
+        std::any::type_name::<T>()
+    );
+    for v in vectors.iter() {
+        out.push_str(&format!(
+            "
+#[test]
+fn {}() {{
+    check(
+        \"{}\",
+        \"{}\",
+    );
+}}
+",
+            test_name(T::name(), &v.name),
+            hex::encode(&v.input),
+            hex::encode(&v.expected)
+        ));
+    }
+    out
+}
+
+/// Export the entries saved for a target under `from` into `out`, as
+/// `<target>.json` and `<target>.rs`. Returns the number of vectors and of
+/// entries skipped.
+pub fn export<T>(from: &[PathBuf], out: &Path) -> io::Result<(usize, usize)>
+where
+    T: TargetWithControl,
+{
+    let entries = find_entries(from, T::name())?;
+    let vectors = vectors::<T>(&entries);
+
+    fs::create_dir_all(out)?;
+    fs::write(
+        out.join(format!("{}.json", T::name())),
+        go_vectors(&vectors),
+    )?;
+    fs::write(
+        out.join(format!("{}.rs", T::name())),
+        rust_tests::<T>(&vectors),
+    )?;
+
+    Ok((vectors.len(), entries.len() - vectors.len()))
+}
@@ -1,24 +1,53 @@
 use lain::{prelude::*, rand::rngs::StdRng};
+use lazy_static::lazy_static;
 use std::{
     marker::PhantomData,
+    path::Path,
     sync::{self, atomic},
+    time::{Duration, Instant},
 };
 
-use crate::traits::{ProduceInvalid, Target, TargetWithControl};
+use crate::{
+    corpus::{self, Corpus, Shard, SyncDir},
+    errors::{CommunicationError, CommunicationResult, InvariantViolation},
+    gas::{GasReport, GasStats},
+    invariants,
+    mock::MockContexts,
+    regression::{self, Baseline},
+    stats::{Outcome, Stats},
+    traits::{Metamorphic, ProduceInvalid, Target, TargetWithControl, ThreadContext},
+};
 
 /// A simple Fuzzer configuration object. It is parameterized with a `Target`
 /// to simplify set up.
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub struct Fuzzer<T>
 where
     T: Target,
 {
     verbose_errors: bool,
     threads: usize,
+    duration: Option<Duration>,
+    seed: u64,
+    shard: Option<Shard>,
+    sync: Option<SyncDir>,
+    seeds: Vec<Vec<u8>>,
+    mock: Option<MockContexts>,
     config: Option<T::Config>,
     _danny: PhantomData<T>,
 }
 
+/// Summary of a single fuzzer run.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RunReport {
+    /// Total iterations run
+    pub iterations: usize,
+    /// Iterations that produced an error
+    pub failed: usize,
+    /// Wall-clock duration of the run
+    pub elapsed: Duration,
+}
+
 impl<T> Default for Fuzzer<T>
 where
     T: Target,
@@ -27,8 +56,14 @@ where
         Self {
             verbose_errors: false,
             threads: 4,
+            duration: None,
+            seed: 42,
+            shard: None,
+            sync: None,
+            seeds: vec![],
+            mock: None,
             config: None,
-            _danny: PhantomData
+            _danny: PhantomData,
         }
     }
 }
@@ -65,31 +100,145 @@ where
         self
     }
 
+    /// Getter for fuzzer `duration` setting.
+    pub fn duration(&self) -> Option<Duration> {
+        self.duration
+    }
+
+    /// Set the maximum run duration. If `None`, the fuzzer runs until
+    /// interrupted.
+    pub fn set_duration(mut self, duration: Option<Duration>) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// Getter for fuzzer `seed` setting.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Set the base seed. Runs with the same seed and shard generate the same
+    /// inputs.
+    pub fn set_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Getter for fuzzer `shard` setting.
+    pub fn shard(&self) -> Option<Shard> {
+        self.shard
+    }
+
+    /// Set the shard. Runs with distinct shards of the same seed do not
+    /// duplicate work.
+    pub fn set_shard(mut self, shard: Option<Shard>) -> Self {
+        self.shard = shard;
+        self
+    }
+
+    /// Getter for fuzzer `sync` setting.
+    pub fn sync(&self) -> Option<&SyncDir> {
+        self.sync.as_ref()
+    }
+
+    /// Set the sync dir. If set, corpus entries and findings are saved there,
+    /// and entries from other instances are replayed.
+    pub fn set_sync(mut self, sync: Option<SyncDir>) -> Self {
+        self.sync = sync;
+        self
+    }
+
+    /// Getter for fuzzer `seeds` setting.
+    pub fn seeds(&self) -> &[Vec<u8>] {
+        &self.seeds
+    }
+
+    /// Set the seed corpus. Seeds are replayed at the start of each run, and
+    /// fail if the executors disagree.
+    pub fn set_seeds(mut self, seeds: Vec<Vec<u8>>) -> Self {
+        self.seeds = seeds;
+        self
+    }
+
+    /// Getter for fuzzer `mock` setting.
+    pub fn mock(&self) -> Option<&MockContexts> {
+        self.mock.as_ref()
+    }
+
+    /// Set mock executors. If set, each thread calls a mock context instead of
+    /// the executors, including in place of the baseline in regression runs.
+    pub fn set_mock(mut self, mock: Option<MockContexts>) -> Self {
+        self.mock = mock;
+        self
+    }
+
+    /// A context for a single-threaded run: a mock if set, or else the
+    /// executors.
+    pub(crate) fn context(&self) -> ThreadContext {
+        self.mock
+            .as_ref()
+            .map_or_else(ThreadContext::default, |m| m.context())
+    }
+
+    fn settings(&self) -> RunSettings {
+        RunSettings {
+            threads: self.threads,
+            duration: self.duration,
+            seed: self.shard.map_or(self.seed, |s| s.seed(self.seed)),
+            stats: sync::Arc::new(Stats::new(T::name())),
+            in_place: !self.verbose_errors,
+            mock: self.mock.clone(),
+        }
+    }
+
+    fn imports(&self) -> Imports {
+        Imports {
+            seeds: sync::Mutex::new(self.seeds.iter().rev().cloned().collect()),
+            corpus: self
+                .sync
+                .as_ref()
+                .map(|s| Corpus::open(s, T::name()).expect("couldn't open sync dir")),
+        }
+    }
+
     /// Getter for fuzzer `config` setting.
-    pub fn config(&self) -> Option<T::Config> {
-        self.config
+    pub fn config(&self) -> Option<&T::Config> {
+        self.config.as_ref()
     }
 
-    /// Set the number of config
+    /// Set the target config. Each fuzzer thread receives its own clone.
     pub fn set_config(mut self, config: Option<T::Config>) -> Self {
         self.config = config;
         self
     }
 
     /// Run valid inputs.
-    pub fn run(&self)
+    pub fn run(&self) -> RunReport
     where
         T: Target<Rng = StdRng>,
     {
         let verbose_errors = self.verbose_errors;
-        let config = self.config;
+        let config = self.config.clone();
+        let imports = self.imports();
+        let settings = self.settings();
+        let stats = settings.stats.clone();
+
+        _run(settings, move |mutator, worker: &mut Worker<T>| {
+            let (target, ctx) = worker.get(&config);
+            if let Some(res) = replay(&imports, &stats, target, ctx, verbose_errors) {
+                return res;
+            }
 
-        _run(self.threads, move |mutator, ctx| {
-            let mut target = T::new(config);
             let input = target.generate(mutator);
+            ctx.take_measurements();
+            let started = Instant::now();
             let res = target.run_experimental(ctx, &input);
+            let elapsed = started.elapsed();
+            let violations = check_invariants(&imports, target, ctx, &input, &res, verbose_errors);
+            let outcome = Outcome::of(&res, elapsed).with_violations(&violations);
+            stats.record(target.variant(&input), outcome);
 
-            let mut is_err = false;
+            let mut is_err = !violations.is_empty();
 
             // TODO: check that all Ok results contain equal values
 
@@ -108,12 +257,168 @@ where
                 is_err = true;
             });
 
+            record(&imports, &input, || corpus::outcome(&res), is_err);
+
+            if is_err {
+                return Err(());
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Run valid inputs, and flag those whose time per gas is an outlier for
+    /// their executor and variant, twice in a row. Flagged inputs are saved
+    /// under `dir`.
+    pub fn run_gas(&self, dir: &Path) -> GasReport
+    where
+        T: Target<Rng = StdRng>,
+    {
+        let verbose_errors = self.verbose_errors;
+        let config = self.config.clone();
+        let imports = self.imports();
+        let settings = self.settings();
+        let stats = settings.stats.clone();
+        let gas = sync::Arc::new(GasStats::new(T::name(), dir));
+        let gas_stats = gas.clone();
+
+        let run = _run(settings, move |mutator, worker: &mut Worker<T>| {
+            let (target, ctx) = worker.get(&config);
+            if let Some(res) = replay(&imports, &stats, target, ctx, verbose_errors) {
+                return res;
+            }
+
+            let input = target.generate(mutator);
+            let variant = target.variant(&input);
+            ctx.take_measurements();
+            let started = Instant::now();
+            let res = target.run_experimental(ctx, &input);
+            let elapsed = started.elapsed();
+            let violations = check_invariants(&imports, target, ctx, &input, &res, verbose_errors);
+            let outcome = Outcome::of(&res, elapsed).with_violations(&violations);
+            stats.record(variant, outcome);
+
+            let mut buf = vec![];
+            input.binary_serialize::<_, lain::byteorder::BigEndian>(&mut buf);
+
+            let mut is_err = !violations.is_empty();
+            for (command, measurement) in ctx.take_measurements() {
+                let sigmas = match gas.observe(command, variant, measurement) {
+                    Some(sigmas) => sigmas,
+                    None => continue,
+                };
+                target.run_raw(ctx, &buf);
+                let repeat = ctx
+                    .take_measurements()
+                    .into_iter()
+                    .find(|(c, _)| *c == command);
+                if let Some((_, repeat)) = repeat {
+                    if !gas.confirm(command, variant, repeat) {
+                        continue;
+                    }
+                    is_err = true;
+                    let saved = gas.save(variant, &buf);
+                    if verbose_errors {
+                        println!(
+                            "Slow input for {} ({:.1} sigmas, {:?} for {} gas):\n\t{}",
+                            variant,
+                            sigmas,
+                            measurement.elapsed,
+                            measurement.gas,
+                            hex::encode(&buf)
+                        );
+                    }
+                    if let Err(e) = saved {
+                        eprintln!("Failed to save slow input: {}", e);
+                    }
+                }
+            }
+
+            record(&imports, &input, || corpus::outcome(&res), is_err);
+
             if is_err {
                 return Err(());
             }
 
             Ok(())
         });
+
+        GasReport {
+            target: T::name(),
+            run,
+            ratios: gas_stats.ratios(),
+        }
+    }
+
+    /// Run valid inputs on the current executors and on a baseline, and
+    /// report any difference in output or gas as a regression. Regressions
+    /// are saved under `dir`.
+    pub fn run_regression(&self, baseline: &Baseline, dir: &Path) -> RunReport
+    where
+        T: Target<Rng = StdRng>,
+    {
+        let baseline = *baseline;
+        let verbose_errors = self.verbose_errors;
+        let config = self.config.clone();
+        let imports = self.imports();
+        let settings = self.settings();
+        let stats = settings.stats.clone();
+        let dir = dir.to_owned();
+
+        _run(settings, move |mutator, worker: &mut Worker<T>| {
+            let (target, ctx, base) = worker.get_with_baseline(&config, &baseline);
+            if let Some(res) = replay(&imports, &stats, target, ctx, verbose_errors) {
+                return res;
+            }
+
+            let input = target.generate(mutator);
+            ctx.take_measurements();
+            base.take_measurements();
+            let started = Instant::now();
+            let current = target.run_experimental(ctx, &input);
+            let previous = target.run_experimental(base, &input);
+            let violations =
+                check_invariants(&imports, target, ctx, &input, &current, verbose_errors);
+            let res = regression::compare(
+                current,
+                previous,
+                &ctx.take_measurements(),
+                &base.take_measurements(),
+            );
+            let outcome = Outcome::of_comparisons(&res, started.elapsed());
+            stats.record(target.variant(&input), outcome.with_violations(&violations));
+
+            let mut buf = vec![];
+            input.binary_serialize::<_, lain::byteorder::BigEndian>(&mut buf);
+
+            let regressed = res.iter().any(|r| r.is_err());
+            let is_err = regressed || !violations.is_empty();
+            if regressed {
+                if verbose_errors {
+                    println!("Regression on input:\n\t{}", hex::encode(&buf));
+                    res.iter().filter_map(|r| r.as_ref().err()).for_each(|e| {
+                        println!("{}", e.describe(&|output| target.display_output(output)))
+                    });
+                }
+                if let Err(e) = regression::save(&dir, T::name(), &buf, &res) {
+                    eprintln!("Failed to save regression: {}", e);
+                }
+            }
+
+            let outcome = || {
+                res.iter()
+                    .map(|r| r.as_ref().err().map_or("ok", |e| e.name()))
+                    .collect::<Vec<_>>()
+                    .join("|")
+            };
+            record(&imports, &input, outcome, is_err);
+
+            if is_err {
+                Err(())
+            } else {
+                Ok(())
+            }
+        })
     }
 }
 
@@ -122,44 +427,87 @@ where
     T: ProduceInvalid,
 {
     /// Run invalid inputs.
-    pub fn run_invalid(&self)
+    pub fn run_invalid(&self) -> RunReport
     where
         T: ProduceInvalid<Rng = StdRng>,
     {
-        let config = self.config;
+        let config = self.config.clone();
+        let imports = self.imports();
+        let settings = self.settings();
+        let stats = settings.stats.clone();
+
+        _run(settings, move |mutator, worker: &mut Worker<T>| {
+            let (target, ctx) = worker.get(&config);
+            if let Some(res) = replay(&imports, &stats, target, ctx, false) {
+                return res;
+            }
 
-        _run(self.threads, move |mutator, ctx| {
-            let mut target = T::new(config);
-            // okay as long as it doesn't panic
+            // okay as long as it doesn't panic, and keeps the invariants
             let input = target.generate_invalid(mutator);
-            target.run_experimental(ctx, &input);
+            ctx.take_measurements();
+            let started = Instant::now();
+            let res = target.run_experimental(ctx, &input);
+            let elapsed = started.elapsed();
+            let violations = check_invariants(&imports, target, ctx, &input, &res, false);
+            let outcome = Outcome::of(&res, elapsed).with_violations(&violations);
+            stats.record(target.variant(&input), outcome);
+            record(&imports, &input, || corpus::outcome(&res), false);
 
-            Ok(())
-        });
+            if violations.is_empty() {
+                Ok(())
+            } else {
+                Err(())
+            }
+        })
     }
 
     /// Run a mix of valid and invalid inputs.
-    pub fn run_mixed(&self)
+    pub fn run_mixed(&self) -> RunReport
     where
         T: ProduceInvalid<Rng = StdRng>,
     {
         let verbose_errors = self.verbose_errors;
-        let config = self.config;
+        let config = self.config.clone();
+        let imports = self.imports();
+        let settings = self.settings();
+        let stats = settings.stats.clone();
 
-        _run(self.threads, move |mutator, ctx| {
-            let mut target = T::new(config);
+        _run(settings, move |mutator, worker: &mut Worker<T>| {
+            let (target, ctx) = worker.get(&config);
+            if let Some(res) = replay(&imports, &stats, target, ctx, verbose_errors) {
+                return res;
+            }
 
             if mutator.gen_chance(0.1) {
                 let input = target.generate_invalid(mutator);
-                // okay as long as it doesn't panic
-                target.run_experimental(ctx, &input);
-                Ok(())
+                // okay as long as it doesn't panic, and keeps the invariants
+                ctx.take_measurements();
+                let started = Instant::now();
+                let res = target.run_experimental(ctx, &input);
+                let elapsed = started.elapsed();
+                let violations =
+                    check_invariants(&imports, target, ctx, &input, &res, verbose_errors);
+                let outcome = Outcome::of(&res, elapsed).with_violations(&violations);
+                stats.record(target.variant(&input), outcome);
+                record(&imports, &input, || corpus::outcome(&res), false);
+                if violations.is_empty() {
+                    Ok(())
+                } else {
+                    Err(())
+                }
             } else {
                 let input = target.generate(mutator);
+                ctx.take_measurements();
+                let started = Instant::now();
                 let res = target.run_experimental(ctx, &input);
+                let elapsed = started.elapsed();
+                let violations =
+                    check_invariants(&imports, target, ctx, &input, &res, verbose_errors);
+                let outcome = Outcome::of(&res, elapsed).with_violations(&violations);
+                stats.record(target.variant(&input), outcome);
                 let errs = res.iter().filter(|r| r.is_err());
 
-                let mut is_err = false;
+                let mut is_err = !violations.is_empty();
                 errs.for_each(|e| {
                     let mut buf = vec![];
                     input.binary_serialize::<_, lain::byteorder::BigEndian>(&mut buf);
@@ -173,6 +521,7 @@ where
                     }
                     is_err = true;
                 });
+                record(&imports, &input, || corpus::outcome(&res), is_err);
                 if is_err {
                     return Err(());
                 }
@@ -188,22 +537,35 @@ where
     T: TargetWithControl,
 {
     /// Run valid inputs and compare to the control result.
-    pub fn run_against_control(&self)
+    pub fn run_against_control(&self) -> RunReport
     where
         T: TargetWithControl<Rng = StdRng>,
     {
         let verbose_errors = self.verbose_errors;
-        let config = self.config;
+        let config = self.config.clone();
+        let imports = self.imports();
+        let settings = self.settings();
+        let stats = settings.stats.clone();
 
-        _run(self.threads, move |mutator, ctx| {
-            let mut target = T::new(config);
+        _run(settings, move |mutator, worker: &mut Worker<T>| {
+            let (target, ctx) = worker.get(&config);
+            if let Some(res) = replay(&imports, &stats, target, ctx, verbose_errors) {
+                return res;
+            }
 
             let input = target.generate(mutator);
-            let res = target.compare(ctx, &input);
+            ctx.take_measurements();
+            let started = Instant::now();
+            let experimental = target.run_experimental(ctx, &input);
+            let violations =
+                check_invariants(&imports, target, ctx, &input, &experimental, verbose_errors);
+            let res = target.compare_results(&input, experimental);
+            let outcome = Outcome::of_comparisons(&res, started.elapsed());
+            stats.record(target.variant(&input), outcome.with_violations(&violations));
 
             let errs = res.iter().filter(|r| r.is_err());
 
-            let mut is_err = false;
+            let mut is_err = !violations.is_empty();
             errs.for_each(|e| {
                 if verbose_errors {
                     let mut buf = vec![];
@@ -211,60 +573,393 @@ where
                     let message = format!(
                         "Error on input:\n\t{}\n{}",
                         hex::encode(&buf),
-                        e.as_ref().unwrap_err()
+                        e.as_ref()
+                            .unwrap_err()
+                            .describe(&|output| target.display_output(output))
                     );
                     println!("{}", &message);
                 }
                 is_err = true;
             });
 
+            let outcome = || {
+                res.iter()
+                    .map(|r| r.as_ref().err().map_or("ok", |e| e.name()))
+                    .collect::<Vec<_>>()
+                    .join("|")
+            };
+            record(&imports, &input, outcome, is_err);
+
+            if is_err {
+                Err(())
+            } else {
+                Ok(())
+            }
+        })
+    }
+}
+
+impl<T> Fuzzer<T>
+where
+    T: Metamorphic,
+{
+    /// Run valid inputs and their follow-ups, and check the target's
+    /// metamorphic relations between their results. Violations are reported as
+    /// invariant violations of the base input.
+    pub fn run_metamorphic(&self) -> RunReport
+    where
+        T: Metamorphic<Rng = StdRng>,
+    {
+        let verbose_errors = self.verbose_errors;
+        let config = self.config.clone();
+        let imports = self.imports();
+        let settings = self.settings();
+        let stats = settings.stats.clone();
+
+        _run(settings, move |mutator, worker: &mut Worker<T>| {
+            let (target, ctx) = worker.get(&config);
+            if let Some(res) = replay(&imports, &stats, target, ctx, verbose_errors) {
+                return res;
+            }
+
+            let input = target.generate(mutator);
+            ctx.take_measurements();
+            let started = Instant::now();
+            let res = target.run_experimental(ctx, &input);
+            let mut violations =
+                check_invariants(&imports, target, ctx, &input, &res, verbose_errors);
+
+            let mut results = vec![res];
+            for follow_up in target.follow_ups(&input).iter() {
+                results.push(target.run_experimental(ctx, follow_up));
+            }
+            let relations = target.relations(&input, &results);
+            report_violations(&imports, &input, &relations, verbose_errors);
+            violations.extend(relations);
+
+            let outcome = Outcome::of(&results[0], started.elapsed()).with_violations(&violations);
+            stats.record(target.variant(&input), outcome);
+
+            let is_err = !violations.is_empty();
+            record(&imports, &input, || corpus::outcome(&results[0]), is_err);
+
             if is_err {
                 Err(())
             } else {
                 Ok(())
             }
+        })
+    }
+}
+
+/// Inputs to replay before generating new ones: seeds, then entries imported
+/// from other instances.
+struct Imports {
+    // in reverse order, so that seeds pop in order
+    seeds: sync::Mutex<Vec<Vec<u8>>>,
+    corpus: Option<sync::Arc<Corpus>>,
+}
+
+impl Imports {
+    fn next(&self) -> Option<Vec<u8>> {
+        let seed = self.seeds.lock().unwrap().pop();
+        seed.or_else(|| self.corpus.as_ref()?.next_import())
+    }
+}
+
+/// Replay a seed or an entry imported from another instance, if one is
+/// queued. Replays fail if an executor crashes, if the executors disagree, or
+/// if the target parses the input and its invariants are violated.
+fn replay<T>(
+    imports: &Imports,
+    stats: &Stats,
+    target: &mut T,
+    ctx: &mut ThreadContext,
+    verbose_errors: bool,
+) -> Option<Result<(), ()>>
+where
+    T: Target,
+{
+    let input = imports.next()?;
+    ctx.take_measurements();
+    let started = Instant::now();
+    let res: Vec<CommunicationResult<Vec<u8>>> = target.run_raw(ctx, &input);
+    let elapsed = started.elapsed();
+    let violations = target
+        .parse(&input)
+        .map_or_else(Vec::new, |parsed| invariants_of(target, ctx, &parsed, &res));
+    stats.record(
+        "replay",
+        Outcome::of(&res, elapsed).with_violations(&violations),
+    );
+
+    let crashed = res
+        .iter()
+        .any(|r| matches!(r, Err(CommunicationError::IoError(_))));
+    let outcomes: Vec<_> = res
+        .iter()
+        .map(|r| corpus::outcome(std::slice::from_ref(r)))
+        .collect();
+    let disagree = outcomes.windows(2).any(|w| w[0] != w[1]);
+
+    if crashed || disagree || !violations.is_empty() {
+        if verbose_errors {
+            println!(
+                "Error replaying input:\n\t{}\n{}",
+                hex::encode(&input),
+                outcomes.join("\n")
+            );
+            violations.iter().for_each(|v| println!("{}", v));
+        }
+        return Some(Err(()));
+    }
+    Some(Ok(()))
+}
+
+/// Check a target's invariants on the results of an input. Inputs that
+/// violate them are saved to the sync dir, apart from other findings.
+fn check_invariants<T>(
+    imports: &Imports,
+    target: &T,
+    ctx: &ThreadContext,
+    input: &T::Intermediate,
+    results: &[CommunicationResult<Vec<u8>>],
+    verbose_errors: bool,
+) -> Vec<InvariantViolation>
+where
+    T: Target,
+{
+    let violations = invariants_of(target, ctx, input, results);
+    report_violations(imports, input, &violations, verbose_errors);
+    violations
+}
+
+/// The target's invariants, and its expected gas against the measurements of
+/// the executors' last calls. Callers take any earlier measurements before
+/// running the input, so only its own calls are checked.
+fn invariants_of<T>(
+    target: &T,
+    ctx: &ThreadContext,
+    input: &T::Intermediate,
+    results: &[CommunicationResult<Vec<u8>>],
+) -> Vec<InvariantViolation>
+where
+    T: Target,
+{
+    let mut violations = target.invariants(input, results);
+    if let Some(gas) = target.expected_gas(input) {
+        violations.extend(invariants::gas(&ctx.measurements(), gas));
+    }
+    violations
+}
+
+/// Print violations if `verbose_errors`, and save the input to the sync dir.
+fn report_violations<I>(
+    imports: &Imports,
+    input: &I,
+    violations: &[InvariantViolation],
+    verbose_errors: bool,
+) where
+    I: BinarySerialize,
+{
+    if violations.is_empty() {
+        return;
+    }
+    let mut buf = vec![];
+    input.binary_serialize::<_, lain::byteorder::BigEndian>(&mut buf);
+    if verbose_errors {
+        println!("Invariant violated on input:\n\t{}", hex::encode(&buf));
+        violations.iter().for_each(|v| println!("{}", v));
+    }
+    if let Some(corpus) = &imports.corpus {
+        corpus.add_violation(&buf);
+    }
+}
+
+/// Save an input to the corpus if its outcome is new, and to the findings if
+/// it produced an error.
+fn record<I, O>(imports: &Imports, input: &I, outcome: O, is_err: bool)
+where
+    I: BinarySerialize,
+    O: FnOnce() -> String,
+{
+    if let Some(corpus) = &imports.corpus {
+        let mut buf = vec![];
+        input.binary_serialize::<_, lain::byteorder::BigEndian>(&mut buf);
+        corpus.observe(&buf, outcome());
+        if is_err {
+            corpus.add_finding(&buf);
+        }
+    }
+}
+
+static INTERRUPTED: atomic::AtomicBool = atomic::AtomicBool::new(false);
+static CTRLC_HANDLER: sync::Once = sync::Once::new();
+
+lazy_static! {
+    // Executors are expensive to start, so fuzzer threads return them here
+    // when they exit. Subsequent runs in the same process reuse them.
+    static ref CONTEXT_POOL: sync::Mutex<Vec<ThreadContext>> = Default::default();
+}
+
+/// True if the process has received CTRL-C. Once interrupted, all subsequent
+/// runs exit immediately.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(atomic::Ordering::Relaxed)
+}
+
+fn install_ctrlc_handler() {
+    // ctrlc permits only one handler per process
+    CTRLC_HANDLER.call_once(|| {
+        ctrlc::set_handler(|| INTERRUPTED.store(true, atomic::Ordering::Relaxed))
+            .expect("couldn't set CTRL-C handler");
+    });
+}
+
+/// Per-thread fuzzer state. The context and target are instantiated on the
+/// first iteration, so that each thread clones the config once and then holds
+/// it.
+pub(crate) struct Worker<T>
+where
+    T: Target,
+{
+    ctx: Option<ThreadContext>,
+    baseline: Option<ThreadContext>,
+    mock: Option<MockContexts>,
+    target: Option<T>,
+}
+
+impl<T> Default for Worker<T>
+where
+    T: Target,
+{
+    fn default() -> Self {
+        Self {
+            ctx: None,
+            baseline: None,
+            mock: None,
+            target: None,
+        }
+    }
+}
+
+impl<T> Drop for Worker<T>
+where
+    T: Target,
+{
+    fn drop(&mut self) {
+        if let Some(mut ctx) = self.ctx.take() {
+            if !ctx.is_mock() && ctx.is_running() {
+                CONTEXT_POOL.lock().unwrap().push(ctx);
+            }
+        }
+    }
+}
+
+impl<T> Worker<T>
+where
+    T: Target,
+{
+    /// Instantiate the context, if this is the first iteration: a mock if
+    /// set, or else executors from the pool.
+    fn init(&mut self, mock: &Option<MockContexts>) {
+        if self.ctx.is_none() {
+            self.mock = mock.clone();
+            self.ctx = Some(match mock {
+                Some(mock) => mock.context(),
+                None => CONTEXT_POOL.lock().unwrap().pop().unwrap_or_default(),
+            });
+        }
+    }
+
+    fn get(&mut self, config: &Option<T::Config>) -> (&mut T, &mut ThreadContext) {
+        let target = self.target.get_or_insert_with(|| T::new(config.clone()));
+        (target, self.ctx.as_mut().expect("initialized by _run"))
+    }
+
+    /// As `get`, and also a context running the baseline executors. Baseline
+    /// contexts are not returned to the pool.
+    fn get_with_baseline(
+        &mut self,
+        config: &Option<T::Config>,
+        baseline: &Baseline,
+    ) -> (&mut T, &mut ThreadContext, &mut ThreadContext) {
+        let mock = &self.mock;
+        let base = self.baseline.get_or_insert_with(|| match mock {
+            Some(mock) => mock.context(),
+            None => ThreadContext::with_executors(baseline.celo(), baseline.geth()),
         });
+        let target = self.target.get_or_insert_with(|| T::new(config.clone()));
+        let ctx = self.ctx.as_mut().expect("initialized by _run");
+        (target, ctx, base)
     }
 }
 
-pub(crate) fn _run<C, F>(threads: usize, callback: F)
+/// Settings for a single call to `_run`.
+pub(crate) struct RunSettings {
+    threads: usize,
+    duration: Option<Duration>,
+    seed: u64,
+    stats: sync::Arc<Stats>,
+    // redraw the status panel in place, rather than printing new lines
+    in_place: bool,
+    mock: Option<MockContexts>,
+}
+
+/// State shared by all fuzzer threads. lain copies its callback into each
+/// thread, so we keep ours here instead. This allows it to own non-`Copy`
+/// data, like the target config.
+pub(crate) struct RunState<F> {
+    stop_progress: atomic::AtomicBool,
+    stats: sync::Arc<Stats>,
+    mock: Option<MockContexts>,
+    callback: F,
+}
+
+pub(crate) fn _run<T, F>(settings: RunSettings, callback: F) -> RunReport
 where
-    C: Default + 'static,
-    F: Fn(&mut Mutator<StdRng>, &mut C) -> Result<(), ()> + Send + Sync + Copy + 'static,
+    T: Target,
+    F: Fn(&mut Mutator<StdRng>, &mut Worker<T>) -> Result<(), ()> + Send + Sync + 'static,
 {
-    let stop_progress = atomic::AtomicBool::new(false);
-    let stop_progress = sync::RwLock::from(stop_progress);
-    let stop_progress = sync::Arc::from(stop_progress);
+    let RunSettings {
+        threads,
+        duration,
+        seed,
+        stats,
+        in_place,
+        mock,
+    } = settings;
 
-    let mut driver = lain::driver::FuzzerDriver::<atomic::AtomicBool>::new(threads);
-    driver.set_global_context(stop_progress.clone());
-    driver.set_seed(42);
+    let state = RunState {
+        stop_progress: atomic::AtomicBool::new(false),
+        stats: stats.clone(),
+        mock,
+        callback,
+    };
+    let state = sync::RwLock::from(state);
+    let state = sync::Arc::from(state);
+
+    let mut driver = lain::driver::FuzzerDriver::<RunState<F>>::new(threads);
+    driver.set_global_context(state.clone());
+    driver.set_seed(seed);
     let driver = sync::Arc::from(driver);
     // driver.set_to_reproduce_mode(31150, 31200);
 
     // set up ctrl+c handling
-    let ctrlc_driver = driver.clone();
-    let ctrlc_driver_stop_progress = stop_progress.clone();
-    ctrlc::set_handler(move || {
-        ctrlc_driver_stop_progress
-            .write()
-            .unwrap()
-            .store(true, atomic::Ordering::Relaxed);
-        ctrlc_driver.signal_exit();
-    })
-    .expect("couldn't set CTRL-C handler");
-
-    lain::driver::start_fuzzer(driver.clone(), move |mutator, ctx, stop_progress| {
-        let res = callback(mutator, ctx);
-        if res.is_err()
-            && stop_progress
-                .unwrap()
-                .read()
-                .unwrap()
-                .load(atomic::Ordering::Relaxed)
-        {
+    install_ctrlc_handler();
+    let started = Instant::now();
+
+    lain::driver::start_fuzzer(driver.clone(), |mutator, ctx: &mut Worker<T>, state| {
+        let state: &sync::Arc<sync::RwLock<RunState<F>>> = state.unwrap();
+        let state = state.read().unwrap();
+        ctx.init(&state.mock);
+        let res = (state.callback)(mutator, ctx);
+        if res.is_err() && state.stop_progress.load(atomic::Ordering::Relaxed) {
             Ok(()) // silence errors during shutdown
         } else {
+            if res.is_err() {
+                state.stats.record_finding();
+            }
             res
         }
     });
@@ -279,22 +974,46 @@ where
         let red = Style::new().red();
 
         let term = Term::stdout();
-        // let term = Term::buffered_stdout();
+        let in_place = in_place && term.is_term();
+        let interval = if in_place {
+            Duration::from_millis(1000)
+        } else {
+            Duration::from_millis(5000)
+        };
+
+        let mut last_report: Option<(Instant, usize)> = None;
+        let mut drawn = 0;
         loop {
-            let msg = format!(
-                "Done {} iterations, {} failed iterations",
-                green.apply_to(format!("{}", progress_driver.num_iterations())),
-                red.apply_to(format!("{}", progress_driver.num_failed_iterations()))
-            );
-            let _ = term.write_line(&msg);
-            std::thread::sleep(std::time::Duration::from_millis(5000));
-            // let _ = term.clear_line();
-
-            let stop = stop_progress
-                .read()
-                .unwrap()
-                .load(atomic::Ordering::Relaxed);
-            if stop {
+            if last_report.is_none_or(|(t, _)| t.elapsed() >= interval) {
+                let iterations = progress_driver.num_iterations();
+                let failed = progress_driver.num_failed_iterations();
+                let rate = last_report.map_or(0.0, |(t, prev)| {
+                    (iterations - prev) as f64 / t.elapsed().as_secs_f64()
+                });
+
+                let mut panel = stats.panel(iterations, failed, rate);
+                let style = if failed == 0 { &green } else { &red };
+                panel[1] = style.apply_to(&panel[1]).to_string();
+
+                if in_place && drawn > 0 {
+                    let _ = term.clear_last_lines(drawn);
+                }
+                for line in panel.iter() {
+                    let _ = term.write_line(line);
+                }
+                drawn = panel.len();
+                last_report = Some((Instant::now(), iterations));
+            }
+            std::thread::sleep(Duration::from_millis(100));
+
+            let timed_out = duration.is_some_and(|d| started.elapsed() >= d);
+            if interrupted() || timed_out {
+                state
+                    .read()
+                    .unwrap()
+                    .stop_progress
+                    .store(true, atomic::Ordering::Relaxed);
+                progress_driver.signal_exit();
                 break;
             }
         }
@@ -308,4 +1027,10 @@ where
         driver.num_iterations(),
         driver.num_failed_iterations()
     );
+
+    RunReport {
+        iterations: driver.num_iterations(),
+        failed: driver.num_failed_iterations(),
+        elapsed: started.elapsed(),
+    }
 }
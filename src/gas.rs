@@ -0,0 +1,184 @@
+use sha2::Digest;
+use std::{
+    collections::BTreeMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use crate::{call::Measurement, fuzzer::RunReport, stats};
+
+/// Calls are not flagged until their executor and variant have this many
+/// samples.
+pub const MIN_SAMPLES: u64 = 1000;
+
+/// Calls whose time per gas is this many standard deviations above the mean,
+/// in log space, are flagged.
+pub const OUTLIER_SIGMAS: f64 = 4.0;
+
+/// Running time-per-gas statistics for one executor and input variant. Ratios
+/// are kept in log space, as call times are heavy-tailed.
+#[derive(Clone, Debug, Default)]
+pub struct Ratio {
+    count: u64,
+    mean: f64,
+    m2: f64,
+    max: f64,
+    flagged: u64,
+}
+
+impl Ratio {
+    fn add(&mut self, x: f64) {
+        self.count += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (x - self.mean);
+        self.max = if self.count == 1 { x } else { self.max.max(x) };
+    }
+
+    fn std_dev(&self) -> f64 {
+        if self.count < 2 {
+            return 0.0;
+        }
+        (self.m2 / (self.count - 1) as f64).sqrt()
+    }
+
+    /// Standard deviations `x` lies above the mean, once there are enough
+    /// samples to tell.
+    fn sigmas(&self, x: f64) -> Option<f64> {
+        let std_dev = self.std_dev();
+        if self.count < MIN_SAMPLES || std_dev == 0.0 {
+            return None;
+        }
+        Some((x - self.mean) / std_dev)
+    }
+
+    /// The number of calls recorded.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The geometric mean time per gas, in nanoseconds.
+    pub fn mean(&self) -> f64 {
+        self.mean.exp()
+    }
+
+    /// The maximum time per gas, in nanoseconds.
+    pub fn max(&self) -> f64 {
+        self.max.exp()
+    }
+
+    /// The number of calls flagged as outliers, and confirmed on a repeat.
+    pub fn flagged(&self) -> u64 {
+        self.flagged
+    }
+}
+
+/// Time-per-gas ratios for a target, by executor and input variant, shared by
+/// all fuzzer threads. Flagged inputs are saved under
+/// `<dir>/<target>/<variant>/<sha256 of input>`.
+#[derive(Debug)]
+pub struct GasStats {
+    target: &'static str,
+    dir: PathBuf,
+    ratios: Mutex<BTreeMap<(String, &'static str), Ratio>>,
+}
+
+impl GasStats {
+    /// Instantiate empty stats for a target, saving flagged inputs in `dir`.
+    pub fn new(target: &'static str, dir: &Path) -> Self {
+        Self {
+            target,
+            dir: dir.to_owned(),
+            ratios: Default::default(),
+        }
+    }
+
+    /// Record a call. Returns the number of standard deviations its time per
+    /// gas lies above the mean, if that makes it an outlier. Calls reporting
+    /// zero gas are treated as costing 1 gas.
+    pub fn observe(
+        &self,
+        command: &str,
+        variant: &'static str,
+        measurement: Measurement,
+    ) -> Option<f64> {
+        let x = log_ratio(measurement);
+        let mut ratios = self.ratios.lock().unwrap();
+        let ratio = ratios
+            .entry((stats::executor_name(command), variant))
+            .or_default();
+        let sigmas = ratio.sigmas(x).filter(|s| *s >= OUTLIER_SIGMAS);
+        ratio.add(x);
+        sigmas
+    }
+
+    /// Check a repeated call of an outlier, without recording it. Most
+    /// outliers are scheduling noise, and are not slow a second time. Counts
+    /// the call as flagged if it is still an outlier.
+    pub fn confirm(&self, command: &str, variant: &'static str, measurement: Measurement) -> bool {
+        let x = log_ratio(measurement);
+        let mut ratios = self.ratios.lock().unwrap();
+        let ratio = ratios
+            .entry((stats::executor_name(command), variant))
+            .or_default();
+        let confirmed = ratio.sigmas(x).is_some_and(|s| s >= OUTLIER_SIGMAS);
+        if confirmed {
+            ratio.flagged += 1;
+        }
+        confirmed
+    }
+
+    /// Save a flagged input.
+    pub fn save(&self, variant: &'static str, input: &[u8]) -> io::Result<PathBuf> {
+        let dir = self.dir.join(self.target).join(variant);
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(hex::encode(sha2::Sha256::digest(input)));
+        fs::write(&path, input)?;
+        Ok(path)
+    }
+
+    /// The ratios recorded so far.
+    pub fn ratios(&self) -> BTreeMap<(String, &'static str), Ratio> {
+        self.ratios.lock().unwrap().clone()
+    }
+}
+
+fn log_ratio(measurement: Measurement) -> f64 {
+    (measurement.elapsed.as_nanos() as f64 / measurement.gas.max(1) as f64).ln()
+}
+
+/// Summary of a gas run.
+#[derive(Clone, Debug)]
+pub struct GasReport {
+    /// The target name
+    pub target: &'static str,
+    /// The fuzzer run
+    pub run: RunReport,
+    /// Ratios by executor and input variant
+    pub ratios: BTreeMap<(String, &'static str), Ratio>,
+}
+
+impl fmt::Display for GasReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Time per gas for {}", self.target)?;
+        writeln!(
+            f,
+            "{:<16}{:<18}{:>10}{:>14}{:>14}{:>10}",
+            "executor", "variant", "calls", "mean_ns/gas", "max_ns/gas", "flagged"
+        )?;
+        for ((executor, variant), ratio) in self.ratios.iter() {
+            writeln!(
+                f,
+                "{:<16}{:<18}{:>10}{:>14.2}{:>14.2}{:>10}",
+                executor,
+                variant,
+                ratio.count(),
+                ratio.mean(),
+                ratio.max(),
+                ratio.flagged()
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,61 @@
+use crate::{
+    call::Measurement,
+    errors::{CommunicationError, CommunicationResult, InvariantViolation},
+};
+
+/// Check that every successful output is `len` bytes long.
+pub fn output_length(
+    results: &[CommunicationResult<Vec<u8>>],
+    len: usize,
+) -> Vec<InvariantViolation> {
+    results
+        .iter()
+        .filter_map(|r| r.as_ref().ok())
+        .filter(|output| output.len() != len)
+        .map(|output| {
+            InvariantViolation::new(
+                "output_length",
+                format!("expected {} bytes, got {}", len, output.len()),
+            )
+        })
+        .collect()
+}
+
+/// Check that no executor returned an error. For inputs every executor must
+/// accept. Crashes are reported as such, not as violations.
+pub fn accepted(results: &[CommunicationResult<Vec<u8>>]) -> Vec<InvariantViolation> {
+    results
+        .iter()
+        .filter_map(|r| match r {
+            Err(CommunicationError::RemoteError(e)) => Some(e),
+            _ => None,
+        })
+        .map(|e| InvariantViolation::new("accepted", format!("returned error {}", e)))
+        .collect()
+}
+
+/// Check that every executor returned an error. For inputs every executor
+/// must reject.
+pub fn rejected(results: &[CommunicationResult<Vec<u8>>]) -> Vec<InvariantViolation> {
+    results
+        .iter()
+        .filter_map(|r| r.as_ref().ok())
+        .map(|output| {
+            InvariantViolation::new("rejected", format!("returned {}", hex::encode(output)))
+        })
+        .collect()
+}
+
+/// Check that every executor charged `expected` gas for its last call.
+pub fn gas(measurements: &[(&'static str, Measurement)], expected: u64) -> Vec<InvariantViolation> {
+    measurements
+        .iter()
+        .filter(|(_, m)| m.gas != expected)
+        .map(|(command, m)| {
+            InvariantViolation::new(
+                "gas",
+                format!("{} charged {}, expected {}", command, m.gas, expected),
+            )
+        })
+        .collect()
+}
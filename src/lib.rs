@@ -13,5 +13,38 @@ pub mod call;
 /// Error types
 pub mod errors;
 
+/// Registry of fuzzer targets
+pub mod registry;
+
 /// convenience cli
-pub mod cli;
\ No newline at end of file
+pub mod cli;
+
+/// Multi-target fuzzing campaigns
+pub mod campaign;
+
+/// Corpus synchronisation between instances
+pub mod corpus;
+
+/// Fuzzer statistics and status reporting
+pub mod stats;
+
+/// Executor latency benchmarking
+pub mod bench;
+
+/// Gas pricing anomaly detection
+pub mod gas;
+
+/// Version regression testing against baseline executor builds
+pub mod regression;
+
+/// Export of saved entries as test vectors
+pub mod export;
+
+/// go-ethereum precompile test vectors
+pub mod testdata;
+
+/// Mock executors for testing targets without Go
+pub mod mock;
+
+/// Reusable target invariants
+pub mod invariants;
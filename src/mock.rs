@@ -0,0 +1,167 @@
+use lain::{
+    prelude::*,
+    rand::{rngs::StdRng, SeedableRng},
+};
+use std::{collections::BTreeMap, fmt, fs, io, path::Path, sync::Arc};
+
+use crate::{
+    call::Response,
+    fuzzer::Fuzzer,
+    traits::{Target, ThreadContext},
+};
+
+/// Answers calls in place of an executor, given the precompile address and
+/// input.
+pub type Handler = Box<dyn FnMut(u8, &[u8]) -> Response + Send>;
+
+/// Recorded executor responses, by precompile address and input.
+///
+/// Saved transcripts have one call per line:
+/// `<address> <input hex> <gas> ok <output hex>` or
+/// `<address> <input hex> <gas> err <message>`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Transcript {
+    calls: BTreeMap<(u8, Vec<u8>), Response>,
+}
+
+fn invalid_data<E: fmt::Display>(line: usize, e: E) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("line {}: {}", line + 1, e),
+    )
+}
+
+impl Transcript {
+    /// Instantiate an empty transcript. Alias for `Default::default()`
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Record the response to a call, replacing any earlier one.
+    pub fn insert(&mut self, address: u8, input: &[u8], response: Response) {
+        self.calls.insert((address, input.to_vec()), response);
+    }
+
+    /// The recorded response to a call.
+    pub fn get(&self, address: u8, input: &[u8]) -> Option<&Response> {
+        self.calls.get(&(address, input.to_vec()))
+    }
+
+    /// The number of calls recorded.
+    pub fn len(&self) -> usize {
+        self.calls.len()
+    }
+
+    /// True if no calls are recorded.
+    pub fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+
+    /// Load a saved transcript.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut transcript = Self::new();
+        for (i, line) in fs::read_to_string(path)?.lines().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            let fields: Vec<&str> = line.splitn(5, ' ').collect();
+            if fields.len() != 5 {
+                return Err(invalid_data(i, "expected 5 fields"));
+            }
+            let address = u8::from_str_radix(fields[0], 16).map_err(|e| invalid_data(i, e))?;
+            let input = hex::decode(fields[1]).map_err(|e| invalid_data(i, e))?;
+            let gas = fields[2].parse().map_err(|e| invalid_data(i, e))?;
+            let response = match fields[3] {
+                "ok" => Response::ok(hex::decode(fields[4]).map_err(|e| invalid_data(i, e))?),
+                "err" => Response::err(fields[4]),
+                other => return Err(invalid_data(i, format!("unknown status {}", other))),
+            };
+            transcript.insert(address, &input, response.set_gas(gas));
+        }
+        Ok(transcript)
+    }
+
+    /// Save the transcript, creating its directory if needed.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut out = String::new();
+        for ((address, input), response) in self.calls.iter() {
+            let (status, body) = match &response.result {
+                Ok(output) => ("ok", hex::encode(output)),
+                Err(message) => ("err", message.replace('\n', " ")),
+            };
+            out.push_str(&format!(
+                "{:02x} {} {} {} {}\n",
+                address,
+                hex::encode(input),
+                response.gas,
+                status,
+                body
+            ));
+        }
+        fs::write(path, out)
+    }
+
+    /// Answer calls from the transcript. Calls it does not contain panic, as
+    /// the test that made them has left the recorded run.
+    pub fn into_handler(self) -> Handler {
+        Box::new(move |address, input| {
+            self.get(address, input).cloned().unwrap_or_else(|| {
+                panic!(
+                    "no recorded response for address {} and input {}",
+                    address,
+                    hex::encode(input)
+                )
+            })
+        })
+    }
+}
+
+/// Builds the mock context of each fuzzer thread, in place of one running the
+/// executors.
+#[derive(Clone)]
+pub struct MockContexts(Arc<dyn Fn() -> ThreadContext + Send + Sync>);
+
+impl fmt::Debug for MockContexts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockContexts").finish()
+    }
+}
+
+impl MockContexts {
+    /// Instantiate from a function building one context.
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn() -> ThreadContext + Send + Sync + 'static,
+    {
+        Self(Arc::new(f))
+    }
+
+    /// Build a context.
+    pub fn context(&self) -> ThreadContext {
+        (self.0)()
+    }
+}
+
+impl<T> Fuzzer<T>
+where
+    T: Target<Rng = StdRng>,
+{
+    /// Record the celo and geth transcripts of `count` inputs generated from
+    /// the fuzzer seed. Replaying the transcripts with a mock answers the same
+    /// inputs without the executors.
+    pub fn transcribe(&self, count: usize) -> (Transcript, Transcript) {
+        let mut target = T::new(self.config().cloned());
+        let mut ctx = self.context();
+        let mut mutator = Mutator::new(StdRng::seed_from_u64(self.seed()));
+
+        ctx.record();
+        for _ in 0..count {
+            let input = target.generate_serialized(&mut mutator);
+            target.run_raw(&mut ctx, &input);
+        }
+        ctx.take_transcripts()
+    }
+}
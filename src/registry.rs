@@ -0,0 +1,190 @@
+use lain::rand::rngs::StdRng;
+use std::{io, path::Path};
+
+use crate::{
+    cli::{self, ExportOpts, Mode, Opts},
+    fuzzer::RunReport,
+    testdata::{self, KnownAnswers},
+    traits::{Metamorphic, ProduceInvalid, Target, TargetWithControl},
+};
+
+/// A fuzzing target known to the `smash` binary. Targets register themselves
+/// from their own module with `inventory::submit!`:
+///
+/// ```ignore
+/// inventory::submit! {
+///     Registration::with_control::<IdentityPrecompile>()
+/// }
+/// ```
+pub struct Registration {
+    name: &'static str,
+    modes: Vec<Mode>,
+    run: fn(&Opts) -> Option<RunReport>,
+    metamorphic: Option<fn(&Opts) -> Option<RunReport>>,
+    export: Option<fn(&ExportOpts)>,
+    known_answers: fn(&Path) -> io::Result<KnownAnswers>,
+}
+
+inventory::collect!(Registration);
+
+impl Registration {
+    /// Register a target that supports only valid inputs.
+    pub fn target<T>() -> Self
+    where
+        T: Target<Rng = StdRng>,
+    {
+        Self {
+            name: T::name(),
+            modes: vec![Mode::Valid, Mode::Bench, Mode::Gas, Mode::Regression],
+            run: cli::target::<T>,
+            metamorphic: None,
+            export: None,
+            known_answers: testdata::check::<T>,
+        }
+    }
+
+    /// Register a target with a control.
+    pub fn with_control<T>() -> Self
+    where
+        T: TargetWithControl + Target<Rng = StdRng>,
+    {
+        Self {
+            name: T::name(),
+            modes: vec![
+                Mode::Valid,
+                Mode::Control,
+                Mode::Bench,
+                Mode::Gas,
+                Mode::Regression,
+            ],
+            run: cli::target_with_control::<T>,
+            metamorphic: None,
+            export: Some(cli::export::<T>),
+            known_answers: testdata::check::<T>,
+        }
+    }
+
+    /// Register a target that produces invalid inputs.
+    pub fn produce_invalid<T>() -> Self
+    where
+        T: ProduceInvalid + Target<Rng = StdRng>,
+    {
+        Self {
+            name: T::name(),
+            modes: vec![
+                Mode::Valid,
+                Mode::Mixed,
+                Mode::Invalid,
+                Mode::Bench,
+                Mode::Gas,
+                Mode::Regression,
+            ],
+            run: cli::produce_invalid::<T>,
+            metamorphic: None,
+            export: None,
+            known_answers: testdata::check::<T>,
+        }
+    }
+
+    /// Register a target that produces invalid inputs and has a control.
+    pub fn produce_invalid_with_control<T>() -> Self
+    where
+        T: TargetWithControl + ProduceInvalid + Target<Rng = StdRng>,
+    {
+        Self {
+            name: T::name(),
+            modes: vec![
+                Mode::Valid,
+                Mode::Control,
+                Mode::Mixed,
+                Mode::Invalid,
+                Mode::Bench,
+                Mode::Gas,
+                Mode::Regression,
+            ],
+            run: cli::produce_invalid_with_control::<T>,
+            metamorphic: None,
+            export: Some(cli::export::<T>),
+            known_answers: testdata::check::<T>,
+        }
+    }
+
+    /// Enable `Mode::Metamorphic`, for a target with metamorphic relations.
+    /// Chain onto any constructor:
+    ///
+    /// ```ignore
+    /// Registration::with_control::<Cip20Precompile>().set_metamorphic::<Cip20Precompile>()
+    /// ```
+    pub fn set_metamorphic<T>(mut self) -> Self
+    where
+        T: Metamorphic + Target<Rng = StdRng>,
+    {
+        self.modes.push(Mode::Metamorphic);
+        self.metamorphic = Some(cli::metamorphic::<T>);
+        self
+    }
+
+    /// The target name. This is also its `smash` subcommand.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The modes this target supports.
+    pub fn modes(&self) -> &[Mode] {
+        &self.modes
+    }
+
+    /// True if the target supports the mode.
+    pub fn supports(&self, mode: Mode) -> bool {
+        self.modes.contains(&mode)
+    }
+
+    /// The most thorough mode the target supports.
+    pub fn preferred_mode(&self) -> Mode {
+        [Mode::Control, Mode::Mixed, Mode::Valid]
+            .iter()
+            .copied()
+            .find(|m| self.supports(*m))
+            .unwrap_or(Mode::Valid)
+    }
+
+    /// Run the target with the given options. Returns `None` if the target
+    /// does not support the mode.
+    pub fn run(&self, opts: &Opts) -> Option<RunReport> {
+        match (opts.mode, self.metamorphic) {
+            (Mode::Metamorphic, Some(metamorphic)) => metamorphic(opts),
+            _ => (self.run)(opts),
+        }
+    }
+
+    /// Check the target's executors against its go-ethereum test vectors.
+    pub fn known_answers(&self, dir: &Path) -> io::Result<KnownAnswers> {
+        (self.known_answers)(dir)
+    }
+
+    /// Export the target's saved entries as test vectors. Returns false if the
+    /// target has no control to compute expected outputs.
+    pub fn export(&self, opts: &ExportOpts) -> bool {
+        match self.export {
+            Some(export) => {
+                export(opts);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// All registered targets, sorted by name.
+pub fn targets() -> Vec<&'static Registration> {
+    let mut targets: Vec<_> = inventory::iter::<Registration>.into_iter().collect();
+    targets.sort_by_key(|r| r.name);
+    targets
+}
+
+/// Look up a registered target by name.
+pub fn find(name: &str) -> Option<&'static Registration> {
+    inventory::iter::<Registration>
+        .into_iter()
+        .find(|r| r.name == name)
+}
@@ -0,0 +1,129 @@
+use sha2::Digest;
+use std::{
+    ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    call::{self, Measurement},
+    errors::{CommunicationError, CommunicationResult, ComparisonError, ComparisonResult},
+};
+
+/// Older builds of the executors, to compare the current builds against.
+/// Executors without a baseline are compared against a second instance of the
+/// current build.
+#[derive(Copy, Clone, Debug)]
+pub struct Baseline {
+    celo: &'static str,
+    geth: &'static str,
+}
+
+impl Default for Baseline {
+    fn default() -> Self {
+        Self {
+            celo: call::CELO,
+            geth: call::GETH,
+        }
+    }
+}
+
+impl Baseline {
+    /// Instantiate a baseline of the current builds. Alias for
+    /// `Default::default()`
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Getter for the celo baseline command.
+    pub fn celo(&self) -> &'static str {
+        self.celo
+    }
+
+    /// Set the celo baseline command, as parsed by `command`.
+    pub fn set_celo(mut self, command: &'static str) -> Self {
+        self.celo = command;
+        self
+    }
+
+    /// Getter for the geth baseline command.
+    pub fn geth(&self) -> &'static str {
+        self.geth
+    }
+
+    /// Set the geth baseline command, as parsed by `command`.
+    pub fn set_geth(mut self, command: &'static str) -> Self {
+        self.geth = command;
+        self
+    }
+}
+
+/// Parse the path to a baseline build into an executor command. Commands live
+/// as long as the process, like the built-in ones, so each path is parsed once,
+/// with the options.
+pub fn command(path: &OsStr) -> &'static str {
+    Box::leak(path.to_string_lossy().into_owned().into_boxed_str())
+}
+
+/// Compare the results and gas of the current executors, on the left, to the
+/// baseline, on the right.
+pub fn compare(
+    current: Vec<CommunicationResult<Vec<u8>>>,
+    baseline: Vec<CommunicationResult<Vec<u8>>>,
+    current_gas: &[(&'static str, Measurement)],
+    baseline_gas: &[(&'static str, Measurement)],
+) -> Vec<ComparisonResult> {
+    let outputs = current.into_iter().zip(baseline).map(|pair| match pair {
+        (Ok(left), Ok(right)) if left == right => Ok(()),
+        (Ok(left), Ok(right)) => Err(ComparisonError::OkNotEqual(left, right)),
+        (
+            Err(CommunicationError::RemoteError(left)),
+            Err(CommunicationError::RemoteError(right)),
+        ) if left == right => Ok(()),
+        (
+            Err(CommunicationError::RemoteError(left)),
+            Err(CommunicationError::RemoteError(right)),
+        ) => Err(ComparisonError::ErrNotEqual(left, right)),
+        (Err(CommunicationError::RemoteError(left)), Ok(right)) => {
+            Err(ComparisonError::LeftErr(left, right))
+        }
+        (Ok(left), Err(CommunicationError::RemoteError(right))) => {
+            Err(ComparisonError::RightErr(left, right))
+        }
+        _ => Err(ComparisonError::NoComp),
+    });
+
+    let gas = current_gas
+        .iter()
+        .zip(baseline_gas.iter())
+        .map(|((_, left), (_, right))| {
+            if left.gas == right.gas {
+                Ok(())
+            } else {
+                Err(ComparisonError::GasNotEqual(left.gas, right.gas))
+            }
+        });
+
+    outputs.chain(gas).collect()
+}
+
+/// Save a regression as `<dir>/<target>/<sha256 of input>`, with the
+/// divergent results alongside in a `.txt` file.
+pub fn save(
+    dir: &Path,
+    target: &str,
+    input: &[u8],
+    results: &[ComparisonResult],
+) -> io::Result<PathBuf> {
+    let dir = dir.join(target);
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(hex::encode(sha2::Sha256::digest(input)));
+    fs::write(&path, input)?;
+
+    let mut report = format!("input: {}\n", hex::encode(input));
+    for e in results.iter().filter_map(|r| r.as_ref().err()) {
+        report.push_str(&e.to_string());
+    }
+    fs::write(path.with_extension("txt"), report)?;
+    Ok(path)
+}
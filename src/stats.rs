@@ -0,0 +1,255 @@
+use lazy_static::lazy_static;
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::errors::{
+    CommunicationError, CommunicationResult, ComparisonError, ComparisonResult, InvariantViolation,
+};
+
+/// Iterations slower than this are counted as timeouts. The calls are not
+/// aborted.
+pub const TIMEOUT: Duration = Duration::from_secs(1);
+
+lazy_static! {
+    static ref EXECUTORS: Mutex<BTreeMap<&'static str, Arc<Latency>>> = Default::default();
+}
+
+/// The outcome of a single iteration.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Outcome {
+    /// All executors returned the expected result
+    Ok,
+    /// An executor returned an error message
+    RemoteError,
+    /// Executors disagreed with each other, or with the control
+    Mismatch,
+    /// An executor crashed
+    Crash,
+    /// The iteration took longer than `TIMEOUT`
+    Timeout,
+    /// The results violated one of the target's invariants
+    Invariant,
+}
+
+impl Outcome {
+    const ALL: [Outcome; 6] = [
+        Outcome::Ok,
+        Outcome::RemoteError,
+        Outcome::Mismatch,
+        Outcome::Crash,
+        Outcome::Timeout,
+        Outcome::Invariant,
+    ];
+
+    /// A short name for the outcome.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Outcome::Ok => "ok",
+            Outcome::RemoteError => "remote error",
+            Outcome::Mismatch => "mismatch",
+            Outcome::Crash => "crash",
+            Outcome::Timeout => "timeout",
+            Outcome::Invariant => "invariant",
+        }
+    }
+
+    /// Classify a set of experimental results. A mismatch is counted even if
+    /// the input also timed out.
+    pub fn of(results: &[CommunicationResult<Vec<u8>>], elapsed: Duration) -> Self {
+        let oks: Vec<_> = results.iter().filter_map(|r| r.as_ref().ok()).collect();
+
+        if results
+            .iter()
+            .any(|r| matches!(r, Err(CommunicationError::IoError(_))))
+        {
+            Outcome::Crash
+        } else if oks.windows(2).any(|w| w[0] != w[1]) {
+            Outcome::Mismatch
+        } else if elapsed >= TIMEOUT {
+            Outcome::Timeout
+        } else if oks.len() != results.len() {
+            Outcome::RemoteError
+        } else {
+            Outcome::Ok
+        }
+    }
+
+    /// Classify a set of comparisons against a control.
+    pub fn of_comparisons(results: &[ComparisonResult], elapsed: Duration) -> Self {
+        if results.contains(&Err(ComparisonError::NoComp)) {
+            Outcome::Crash
+        } else if results.iter().any(|r| r.is_err()) {
+            Outcome::Mismatch
+        } else if elapsed >= TIMEOUT {
+            Outcome::Timeout
+        } else {
+            Outcome::Ok
+        }
+    }
+
+    /// Reclassify an outcome as `Invariant` if there were violations. Crashes
+    /// and timeouts take precedence.
+    pub fn with_violations(self, violations: &[InvariantViolation]) -> Self {
+        match self {
+            Outcome::Crash | Outcome::Timeout => self,
+            _ if !violations.is_empty() => Outcome::Invariant,
+            _ => self,
+        }
+    }
+}
+
+/// Cumulative call latency for a single executor.
+#[derive(Debug, Default)]
+pub struct Latency {
+    calls: AtomicU64,
+    total_nanos: AtomicU64,
+    max_nanos: AtomicU64,
+}
+
+impl Latency {
+    /// Record a single call.
+    pub fn record(&self, elapsed: Duration) {
+        let nanos = elapsed.as_nanos() as u64;
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        self.total_nanos.fetch_add(nanos, Ordering::Relaxed);
+        self.max_nanos.fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    /// The number of calls recorded.
+    pub fn calls(&self) -> u64 {
+        self.calls.load(Ordering::Relaxed)
+    }
+
+    /// The mean call latency.
+    pub fn mean(&self) -> Duration {
+        let calls = self.calls();
+        if calls == 0 {
+            return Duration::default();
+        }
+        Duration::from_nanos(self.total_nanos.load(Ordering::Relaxed) / calls)
+    }
+
+    /// The maximum call latency.
+    pub fn max(&self) -> Duration {
+        Duration::from_nanos(self.max_nanos.load(Ordering::Relaxed))
+    }
+}
+
+/// Get the latency record for an executor, registering it if necessary.
+pub fn executor_latency(name: &'static str) -> Arc<Latency> {
+    EXECUTORS.lock().unwrap().entry(name).or_default().clone()
+}
+
+/// A short name for an executor, from the command that runs it.
+pub fn executor_name(command: &str) -> String {
+    Path::new(command)
+        .file_name()
+        .map_or(command.to_owned(), |n| n.to_string_lossy().into_owned())
+}
+
+/// Latency records for all executors started by this process.
+pub fn executors() -> Vec<(&'static str, Arc<Latency>)> {
+    EXECUTORS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(k, v)| (*k, v.clone()))
+        .collect()
+}
+
+/// Statistics for a single fuzzer run, shared by all fuzzer threads.
+#[derive(Debug)]
+pub struct Stats {
+    target: &'static str,
+    started: Instant,
+    outcomes: [AtomicUsize; 6],
+    variants: Mutex<BTreeMap<&'static str, usize>>,
+    last_finding: Mutex<Option<Instant>>,
+}
+
+impl Stats {
+    /// Instantiate empty stats for a target.
+    pub fn new(target: &'static str) -> Self {
+        Self {
+            target,
+            started: Instant::now(),
+            outcomes: Default::default(),
+            variants: Default::default(),
+            last_finding: Default::default(),
+        }
+    }
+
+    /// Record an iteration on an input variant.
+    pub fn record(&self, variant: &'static str, outcome: Outcome) {
+        self.outcomes[outcome as usize].fetch_add(1, Ordering::Relaxed);
+        *self.variants.lock().unwrap().entry(variant).or_default() += 1;
+    }
+
+    /// Record a failed iteration.
+    pub fn record_finding(&self) {
+        *self.last_finding.lock().unwrap() = Some(Instant::now());
+    }
+
+    /// The number of iterations with an outcome.
+    pub fn outcome_count(&self, outcome: Outcome) -> usize {
+        self.outcomes[outcome as usize].load(Ordering::Relaxed)
+    }
+
+    /// The number of iterations on each input variant.
+    pub fn variants(&self) -> BTreeMap<&'static str, usize> {
+        self.variants.lock().unwrap().clone()
+    }
+
+    /// Time since the last failed iteration, if any.
+    pub fn since_last_finding(&self) -> Option<Duration> {
+        self.last_finding.lock().unwrap().map(|t| t.elapsed())
+    }
+
+    /// Render a status panel. `rate` is the current iterations per second.
+    pub fn panel(&self, iterations: usize, failed: usize, rate: f64) -> Vec<String> {
+        let elapsed = self.started.elapsed();
+        let last_finding = self
+            .since_last_finding()
+            .map_or("never".to_owned(), |d| format!("{}s ago", d.as_secs()));
+
+        let outcomes: Vec<_> = Outcome::ALL
+            .iter()
+            .map(|o| format!("{} {}", o.name(), self.outcome_count(*o)))
+            .collect();
+        let variants: Vec<_> = self
+            .variants()
+            .iter()
+            .map(|(v, c)| format!("{} {}", v, c))
+            .collect();
+        let executors: Vec<_> = executors()
+            .iter()
+            .filter(|(_, l)| l.calls() > 0)
+            .map(|(name, l)| {
+                format!(
+                    "{} mean {:?} max {:?}",
+                    executor_name(name),
+                    l.mean(),
+                    l.max()
+                )
+            })
+            .collect();
+
+        vec![
+            format!("{} running for {}s", self.target, elapsed.as_secs()),
+            format!(
+                "iterations  {} ({:.0}/s), {} failed, last finding {}",
+                iterations, rate, failed, last_finding
+            ),
+            format!("outcomes    {}", outcomes.join(", ")),
+            format!("variants    {}", variants.join(", ")),
+            format!("executors   {}", executors.join(", ")),
+        ]
+    }
+}
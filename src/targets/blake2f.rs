@@ -0,0 +1,356 @@
+use lain::{byteorder::ByteOrder, prelude::*};
+use std::io::Write;
+
+use crate::{
+    errors::{CommunicationResult, InvariantViolation},
+    invariants,
+    registry::Registration,
+    traits::{ProduceInvalid, Target, TargetWithControl, ThreadContext},
+};
+
+inventory::submit! {
+    Registration::produce_invalid_with_control::<Blake2fPrecompile>()
+}
+
+/// The input length: rounds, state, message, offset counters and final flag.
+pub const INPUT_LENGTH: usize = 213;
+
+/// The most rounds generated by default. `u32::MAX` rounds take most of a
+/// minute in go-ethereum, and far longer in the control.
+pub const DEFAULT_MAX_ROUNDS: u32 = 1 << 16;
+
+/// go-ethereum's error for an input that is not 213 bytes.
+pub const INVALID_LENGTH: &str = "invalid input length";
+/// go-ethereum's error for a final flag other than 0 or 1.
+pub const INVALID_FINAL_FLAG: &str = "invalid final flag";
+
+const IV: [u64; 8] = [
+    0x6a09e667f3bcc908,
+    0xbb67ae8584caa73b,
+    0x3c6ef372fe94f82b,
+    0xa54ff53a5f1d36f1,
+    0x510e527fade682d1,
+    0x9b05688c2b3e6c1f,
+    0x1f83d9abfb41bd6b,
+    0x5be0cd19137e2179,
+];
+
+const SIGMA: [[usize; 16]; 10] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+];
+
+/// The BLAKE2b mixing function.
+#[allow(clippy::many_single_char_names)]
+fn mix(v: &mut [u64; 16], a: usize, b: usize, c: usize, d: usize, x: u64, y: u64) {
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(x);
+    v[d] = (v[d] ^ v[a]).rotate_right(32);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(24);
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(y);
+    v[d] = (v[d] ^ v[a]).rotate_right(16);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(63);
+}
+
+/// The BLAKE2b compression function `F`, with a chosen number of rounds.
+pub fn compress(rounds: u32, h: &mut [u64; 8], m: &[u64; 16], t: &[u64; 2], last: bool) {
+    let mut v = [0u64; 16];
+    v[..8].copy_from_slice(h);
+    v[8..].copy_from_slice(&IV);
+    v[12] ^= t[0];
+    v[13] ^= t[1];
+    if last {
+        v[14] = !v[14];
+    }
+    for round in 0..rounds as usize {
+        let s = &SIGMA[round % 10];
+        mix(&mut v, 0, 4, 8, 12, m[s[0]], m[s[1]]);
+        mix(&mut v, 1, 5, 9, 13, m[s[2]], m[s[3]]);
+        mix(&mut v, 2, 6, 10, 14, m[s[4]], m[s[5]]);
+        mix(&mut v, 3, 7, 11, 15, m[s[6]], m[s[7]]);
+        mix(&mut v, 0, 5, 10, 15, m[s[8]], m[s[9]]);
+        mix(&mut v, 1, 6, 11, 12, m[s[10]], m[s[11]]);
+        mix(&mut v, 2, 7, 8, 13, m[s[12]], m[s[13]]);
+        mix(&mut v, 3, 4, 9, 14, m[s[14]], m[s[15]]);
+    }
+    for i in 0..8 {
+        h[i] ^= v[i] ^ v[i + 8];
+    }
+}
+
+/// Read little-endian words.
+fn read_words(bytes: &[u8], words: &mut [u64]) {
+    for (word, chunk) in words.iter_mut().zip(bytes.chunks(8)) {
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(chunk);
+        *word = u64::from_le_bytes(buf);
+    }
+}
+
+/// A number of rounds: the edges, and mostly few enough to run quickly.
+fn gen_rounds<R: Rng>(mutator: &mut Mutator<R>, max_rounds: u32) -> u32 {
+    match mutator.gen_range(0u8, 10) {
+        0 => 0,
+        1 => 1,
+        // as in BLAKE2b itself
+        2 => 12.min(max_rounds),
+        3 => max_rounds,
+        _ => mutator.gen_range(0, max_rounds.min(1 << 10) as u64 + 1) as u32,
+    }
+}
+
+/// An F compression call. Serializes to the 213-byte layout: big-endian
+/// rounds, then little-endian state, message and offset counters, then the
+/// final flag.
+#[derive(Debug, Clone)]
+pub struct Blake2fArgs {
+    pub rounds: u32,
+    pub h: [u64; 8],
+    pub m: [u64; 16],
+    pub t: [u64; 2],
+    /// The final block flag. Only 0 and 1 are valid
+    pub f: u8,
+    /// The serialized length. Shorter inputs are truncated, and longer ones
+    /// extended with zeros.
+    pub len: usize,
+}
+
+impl Blake2fArgs {
+    /// Generate a valid call with at most `max_rounds` rounds.
+    pub fn random<R: Rng>(mutator: &mut Mutator<R>, max_rounds: u32) -> Self {
+        let mut args = Self {
+            rounds: gen_rounds(mutator, max_rounds),
+            h: [0; 8],
+            m: [0; 16],
+            t: [0; 2],
+            f: mutator.gen_range(0, 2),
+            len: INPUT_LENGTH,
+        };
+        args.h.iter_mut().for_each(|w| *w = mutator.gen());
+        args.m.iter_mut().for_each(|w| *w = mutator.gen());
+        // offsets are byte counts, mostly small
+        if mutator.gen_chance(0.5) {
+            args.t[0] = mutator.gen_range(0, 1 << 20) * 128;
+        } else {
+            args.t.iter_mut().for_each(|w| *w = mutator.gen());
+        }
+        args
+    }
+
+    /// Parse a serialized input of any length. Missing bytes read as zero.
+    pub fn parse(input: &[u8]) -> Self {
+        let mut buf = [0u8; INPUT_LENGTH];
+        let len = input.len().min(INPUT_LENGTH);
+        buf[..len].copy_from_slice(&input[..len]);
+
+        let mut rounds = [0u8; 4];
+        rounds.copy_from_slice(&buf[..4]);
+        let mut args = Self {
+            rounds: u32::from_be_bytes(rounds),
+            h: [0; 8],
+            m: [0; 16],
+            t: [0; 2],
+            f: buf[212],
+            len: input.len(),
+        };
+        read_words(&buf[4..68], &mut args.h);
+        read_words(&buf[68..196], &mut args.m);
+        read_words(&buf[196..212], &mut args.t);
+        args
+    }
+
+    /// The serialized input.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut out = self.rounds.to_be_bytes().to_vec();
+        for word in self.h.iter().chain(&self.m).chain(&self.t) {
+            out.extend_from_slice(&word.to_le_bytes());
+        }
+        out.push(self.f);
+        out.resize(self.len, 0);
+        out
+    }
+
+    /// Run the compression, with go-ethereum's errors.
+    pub fn run(&self) -> Result<Vec<u8>, String> {
+        if self.len != INPUT_LENGTH {
+            return Err(INVALID_LENGTH.to_owned());
+        }
+        if self.f > 1 {
+            return Err(INVALID_FINAL_FLAG.to_owned());
+        }
+        let mut h = self.h;
+        compress(self.rounds, &mut h, &self.m, &self.t, self.f == 1);
+        Ok(h.iter().flat_map(|w| w.to_le_bytes().to_vec()).collect())
+    }
+}
+
+impl BinarySerialize for Blake2fArgs {
+    fn binary_serialize<W: Write, E: ByteOrder>(&self, buf: &mut W) -> usize {
+        let out = self.serialize();
+        buf.write_all(&out).unwrap();
+        out.len()
+    }
+}
+
+impl NewFuzzed for Blake2fArgs {
+    type RangeType = u8;
+
+    /// Generate a valid call with at most `DEFAULT_MAX_ROUNDS` rounds.
+    fn new_fuzzed<R: Rng>(
+        mutator: &mut Mutator<R>,
+        _constraints: Option<&Constraints<Self::RangeType>>,
+    ) -> Self {
+        Self::random(mutator, DEFAULT_MAX_ROUNDS)
+    }
+}
+
+/// Bounds the rounds of generated inputs.
+#[derive(Debug, Clone)]
+pub struct Blake2fConfig {
+    max_rounds: u32,
+}
+
+impl Default for Blake2fConfig {
+    fn default() -> Self {
+        Self {
+            max_rounds: DEFAULT_MAX_ROUNDS,
+        }
+    }
+}
+
+impl Blake2fConfig {
+    /// Instantiate with `DEFAULT_MAX_ROUNDS`. Alias for `Default::default()`
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Generate up to this many rounds. The maximum itself is generated
+    /// often, so `u32::MAX` makes for slow fuzzing.
+    pub fn set_max_rounds(mut self, max_rounds: u32) -> Self {
+        self.max_rounds = max_rounds;
+        self
+    }
+
+    /// The most rounds generated.
+    pub fn max_rounds(&self) -> u32 {
+        self.max_rounds
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Blake2fPrecompile {
+    config: Blake2fConfig,
+}
+
+impl Target for Blake2fPrecompile {
+    type Intermediate = Blake2fArgs;
+    type Rng = lain::rand::rngs::StdRng;
+    type Config = Blake2fConfig;
+
+    fn name() -> &'static str {
+        "blake2f"
+    }
+
+    fn variant(&self, input: &Self::Intermediate) -> &'static str {
+        // generated with a bad length or final flag, so that the rounds
+        // are charged for but never run
+        if input.rounds == u32::MAX {
+            "max_rounds"
+        } else if input.len != INPUT_LENGTH {
+            "bad_length"
+        } else if input.f > 1 {
+            "bad_final_flag"
+        } else if input.rounds == 0 {
+            "zero_rounds"
+        } else if input.f == 1 {
+            "final"
+        } else {
+            "not_final"
+        }
+    }
+
+    fn testdata() -> &'static [&'static str] {
+        &["blake2F.json"]
+    }
+
+    fn parse(&self, input: &[u8]) -> Option<Self::Intermediate> {
+        Some(Blake2fArgs::parse(input))
+    }
+
+    fn invariants(
+        &self,
+        input: &Self::Intermediate,
+        results: &[CommunicationResult<Vec<u8>>],
+    ) -> Vec<InvariantViolation> {
+        if input.len != INPUT_LENGTH || input.f > 1 {
+            return invariants::rejected(results);
+        }
+        let mut violations = invariants::accepted(results);
+        violations.extend(invariants::output_length(results, 64));
+        violations
+    }
+
+    /// One gas per round. go-ethereum charges nothing for an input of the
+    /// wrong length, and charges a bad final flag's rounds.
+    fn expected_gas(&self, input: &Self::Intermediate) -> Option<u64> {
+        if input.len == INPUT_LENGTH {
+            Some(input.rounds as u64)
+        } else {
+            Some(0)
+        }
+    }
+
+    fn new(config: Option<Self::Config>) -> Self {
+        Self {
+            config: config.unwrap_or_default(),
+        }
+    }
+
+    fn generate(&self, mutator: &mut Mutator<Self::Rng>) -> Self::Intermediate {
+        Blake2fArgs::random(mutator, self.config.max_rounds())
+    }
+
+    fn run_raw(
+        &mut self,
+        ctx: &mut ThreadContext,
+        input: &[u8],
+    ) -> Vec<CommunicationResult<Vec<u8>>> {
+        vec![
+            ctx.geth.run_precompile(9u8, input),
+            ctx.celo.run_precompile(9u8, input),
+        ]
+    }
+}
+
+impl ProduceInvalid for Blake2fPrecompile {
+    /// Set a final flag other than 0 or 1, or truncate or extend the input.
+    /// Sometimes also declare `u32::MAX` rounds, which are rejected before
+    /// they run.
+    fn generate_invalid(&self, mutator: &mut Mutator<Self::Rng>) -> Self::Intermediate {
+        let mut args = self.generate(mutator);
+        match mutator.gen_range(0u8, 3) {
+            0 => args.f = mutator.gen_range(2u16, 0x100) as u8,
+            1 => args.len = mutator.gen_range(0, INPUT_LENGTH),
+            _ => args.len = mutator.gen_range(INPUT_LENGTH + 1, INPUT_LENGTH + 64),
+        }
+        if mutator.gen_chance(0.1) {
+            args.rounds = u32::MAX;
+        }
+        args
+    }
+}
+
+impl TargetWithControl for Blake2fPrecompile {
+    fn run_control(&self, input: &Self::Intermediate) -> Result<Vec<u8>, String> {
+        input.run()
+    }
+}
@@ -0,0 +1,177 @@
+use lain::{byteorder::ByteOrder, prelude::*};
+use std::io::Write;
+
+use super::{display_point, zero_tail, G1Point, Padding, PointKind};
+use crate::{
+    errors::{CommunicationResult, InvariantViolation},
+    invariants,
+    registry::Registration,
+    traits::{ProduceInvalid, Target, TargetWithControl, ThreadContext},
+};
+
+inventory::submit! {
+    Registration::produce_invalid_with_control::<Bn256AddPrecompile>()
+}
+
+/// The input length, two points.
+pub const INPUT_LENGTH: usize = 128;
+
+/// Gas for an addition since Istanbul (EIP-1108).
+pub const GAS: u64 = 150;
+
+/// Two points to add.
+#[derive(Debug, Clone)]
+pub struct Bn256AddArgs {
+    pub a: G1Point,
+    pub b: G1Point,
+    pub padding: Padding,
+}
+
+impl Bn256AddArgs {
+    /// Parse a serialized input. Short inputs are zero-padded.
+    pub fn parse(input: &[u8]) -> Self {
+        let (buf, padding) = Padding::split(input, INPUT_LENGTH);
+        Self {
+            a: G1Point::from_slice(&buf[..64]),
+            b: G1Point::from_slice(&buf[64..]),
+            padding,
+        }
+    }
+
+    /// Truncate or extend the input. Truncated bytes are zeroed.
+    pub fn set_padding(&mut self, padding: Padding) {
+        let mut buf = self.a.to_bytes();
+        buf.extend_from_slice(&self.b.to_bytes());
+        zero_tail(&mut buf, &padding);
+        self.a = G1Point::from_slice(&buf[..64]);
+        self.b = G1Point::from_slice(&buf[64..]);
+        self.padding = padding;
+    }
+
+    /// The kind of the worse of the two points.
+    pub fn kind(&self) -> PointKind {
+        std::cmp::max(self.a.kind(), self.b.kind())
+    }
+}
+
+impl BinarySerialize for Bn256AddArgs {
+    fn binary_serialize<W: Write, E: ByteOrder>(&self, buf: &mut W) -> usize {
+        let mut out = self.a.to_bytes();
+        out.extend_from_slice(&self.b.to_bytes());
+        self.padding.apply(&mut out);
+        buf.write_all(&out).unwrap();
+        out.len()
+    }
+}
+
+impl NewFuzzed for Bn256AddArgs {
+    type RangeType = u8;
+
+    /// Generate two valid points, sometimes equal or opposite.
+    fn new_fuzzed<R: Rng>(
+        mutator: &mut Mutator<R>,
+        _constraints: Option<&Constraints<Self::RangeType>>,
+    ) -> Self {
+        let a: G1Point = mutator.gen();
+        let b = match mutator.gen_range(0u8, 10) {
+            0 => a,
+            1 => G1Point::from_g1(-a.decode().expect("generated points are valid")),
+            _ => mutator.gen(),
+        };
+        Self {
+            a,
+            b,
+            padding: Padding::Exact,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Bn256AddPrecompile;
+
+impl Target for Bn256AddPrecompile {
+    type Intermediate = Bn256AddArgs;
+    type Rng = lain::rand::rngs::StdRng;
+    type Config = ();
+
+    fn name() -> &'static str {
+        "bn256_add"
+    }
+
+    fn variant(&self, input: &Self::Intermediate) -> &'static str {
+        let kind = input.kind();
+        match input.padding.name() {
+            Some(name) if kind.is_accepted() => name,
+            _ => kind.name(),
+        }
+    }
+
+    fn testdata() -> &'static [&'static str] {
+        &["bn256Add.json"]
+    }
+
+    fn parse(&self, input: &[u8]) -> Option<Self::Intermediate> {
+        Some(Bn256AddArgs::parse(input))
+    }
+
+    fn invariants(
+        &self,
+        input: &Self::Intermediate,
+        results: &[CommunicationResult<Vec<u8>>],
+    ) -> Vec<InvariantViolation> {
+        if !input.kind().is_accepted() {
+            return invariants::rejected(results);
+        }
+        let mut violations = invariants::accepted(results);
+        violations.extend(invariants::output_length(results, 64));
+        violations.extend(
+            results
+                .iter()
+                .filter_map(|r| r.as_ref().ok())
+                .filter(|output| output.len() == 64)
+                .filter(|output| !G1Point::from_slice(output).kind().is_accepted())
+                .map(|output| InvariantViolation::new("on_curve", display_point(output))),
+        );
+        violations
+    }
+
+    fn expected_gas(&self, _input: &Self::Intermediate) -> Option<u64> {
+        Some(GAS)
+    }
+
+    fn display_output(&self, output: &[u8]) -> String {
+        display_point(output)
+    }
+
+    fn run_raw(
+        &mut self,
+        ctx: &mut ThreadContext,
+        input: &[u8],
+    ) -> Vec<CommunicationResult<Vec<u8>>> {
+        vec![
+            ctx.geth.run_precompile(6u8, input),
+            ctx.celo.run_precompile(6u8, input),
+        ]
+    }
+}
+
+impl ProduceInvalid for Bn256AddPrecompile {
+    /// Replace a point with an invalid one, or truncate or extend the input.
+    fn generate_invalid(&self, mutator: &mut Mutator<Self::Rng>) -> Self::Intermediate {
+        let mut args = self.generate(mutator);
+        match mutator.gen_range(0u8, 3) {
+            0 => args.a = G1Point::random_invalid(mutator),
+            1 => args.b = G1Point::random_invalid(mutator),
+            _ => args.set_padding(Padding::random(mutator, INPUT_LENGTH)),
+        }
+        args
+    }
+}
+
+impl TargetWithControl for Bn256AddPrecompile {
+    fn run_control(&self, input: &Self::Intermediate) -> Result<Vec<u8>, String> {
+        let a = input.a.decode()?;
+        let b = input.b.decode()?;
+        Ok(G1Point::from_g1(a + b).to_bytes())
+    }
+}
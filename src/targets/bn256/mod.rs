@@ -0,0 +1,451 @@
+use lain::prelude::*;
+use substrate_bn::{AffineG1, AffineG2, Fq, Fq2, Fr, Group, G1, G2};
+
+pub mod add;
+pub mod mul;
+pub mod pairing;
+
+pub use add::Bn256AddPrecompile;
+pub use mul::Bn256MulPrecompile;
+pub use pairing::Bn256PairingPrecompile;
+
+/// The base field modulus, big-endian.
+pub const FIELD_MODULUS: [u8; 32] = [
+    0x30, 0x64, 0x4e, 0x72, 0xe1, 0x31, 0xa0, 0x29, 0xb8, 0x50, 0x45, 0xb6, 0x81, 0x81, 0x58, 0x5d,
+    0x97, 0x81, 0x6a, 0x91, 0x68, 0x71, 0xca, 0x8d, 0x3c, 0x20, 0x8c, 0x16, 0xd8, 0x7c, 0xfd, 0x47,
+];
+
+/// The order of G1, big-endian.
+pub const GROUP_ORDER: [u8; 32] = [
+    0x30, 0x64, 0x4e, 0x72, 0xe1, 0x31, 0xa0, 0x29, 0xb8, 0x50, 0x45, 0xb6, 0x81, 0x81, 0x58, 0x5d,
+    0x28, 0x33, 0xe8, 0x48, 0x79, 0xb9, 0x70, 0x91, 0x43, 0xe1, 0xf5, 0x93, 0xf0, 0x00, 0x00, 0x01,
+];
+
+/// go-ethereum's error for a coordinate at or above the field modulus.
+pub const EXCEEDS_MODULUS: &str = "bn256: coordinate exceeds modulus";
+/// go-ethereum's error for a point that is not on the curve, or for a G2 point
+/// outside the subgroup.
+pub const MALFORMED_POINT: &str = "bn256: malformed point";
+
+/// The kind of an encoded G1 or G2 point.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+pub enum PointKind {
+    /// A point on the curve
+    Valid,
+    /// The point at infinity, encoded as zeros
+    Infinity,
+    /// Coordinates in the field, off the curve
+    OffCurve,
+    /// A G2 point on the twist, outside the order `r` subgroup. G1 has no
+    /// such points
+    OutsideSubgroup,
+    /// A coordinate at or above the field modulus
+    OutOfField,
+}
+
+impl PointKind {
+    /// A short name for statistics.
+    pub fn name(&self) -> &'static str {
+        match self {
+            PointKind::Valid => "valid",
+            PointKind::Infinity => "infinity",
+            PointKind::OffCurve => "off_curve",
+            PointKind::OutsideSubgroup => "outside_subgroup",
+            PointKind::OutOfField => "out_of_field",
+        }
+    }
+
+    /// True if go-ethereum accepts points of this kind.
+    pub fn is_accepted(&self) -> bool {
+        matches!(self, PointKind::Valid | PointKind::Infinity)
+    }
+}
+
+/// A G1 point as two big-endian coordinates.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct G1Point {
+    pub x: [u8; 32],
+    pub y: [u8; 32],
+}
+
+/// Add two 256-bit big-endian numbers, wrapping.
+fn add_be(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    let mut carry = 0u16;
+    for i in (0..32).rev() {
+        let sum = a[i] as u16 + b[i] as u16 + carry;
+        out[i] = sum as u8;
+        carry = sum >> 8;
+    }
+    out
+}
+
+fn fq_bytes(fq: Fq) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    fq.to_big_endian(&mut out)
+        .expect("32 bytes fit a field element");
+    out
+}
+
+/// A uniformly random scalar.
+pub(crate) fn gen_scalar<R: Rng>(mutator: &mut Mutator<R>) -> Fr {
+    let mut wide = [0u8; 64];
+    wide.iter_mut().for_each(|b| *b = mutator.gen());
+    Fr::interpret(&wide)
+}
+
+/// Reduce a 256-bit big-endian scalar modulo the group order, as
+/// multiplication by a `big.Int` does.
+pub(crate) fn reduce_scalar(scalar: &[u8; 32]) -> Fr {
+    let mut wide = [0u8; 64];
+    wide[32..].copy_from_slice(scalar);
+    Fr::interpret(&wide)
+}
+
+impl G1Point {
+    /// The point at infinity.
+    pub fn infinity() -> Self {
+        Self {
+            x: [0; 32],
+            y: [0; 32],
+        }
+    }
+
+    /// Encode a point. Infinity encodes as zeros.
+    pub fn from_g1(point: G1) -> Self {
+        match AffineG1::from_jacobian(point) {
+            Some(affine) => Self {
+                x: fq_bytes(affine.x()),
+                y: fq_bytes(affine.y()),
+            },
+            None => Self::infinity(),
+        }
+    }
+
+    /// Read a point from the first 64 bytes of a slice.
+    pub fn from_slice(bytes: &[u8]) -> Self {
+        let mut point = Self::infinity();
+        point.x.copy_from_slice(&bytes[..32]);
+        point.y.copy_from_slice(&bytes[32..64]);
+        point
+    }
+
+    /// The encoded point.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.x.to_vec();
+        out.extend_from_slice(&self.y);
+        out
+    }
+
+    /// A random multiple of the generator.
+    pub fn random<R: Rng>(mutator: &mut Mutator<R>) -> Self {
+        Self::from_g1(G1::one() * gen_scalar(mutator))
+    }
+
+    /// A random point the precompiles must reject: off the curve, or with a
+    /// coordinate at or above the modulus.
+    pub fn random_invalid<R: Rng>(mutator: &mut Mutator<R>) -> Self {
+        let mut point = Self::random(mutator);
+        match mutator.gen_range(0u8, 4) {
+            // one off a valid y
+            0 => point.y = fq_bytes(Fq::from_slice(&point.y).expect("valid") + Fq::one()),
+            // random coordinates are almost never on the curve
+            1 => point.y = fq_bytes(gen_field(mutator)),
+            // a valid point with a coordinate not reduced
+            2 => {
+                if mutator.gen_chance(0.5) {
+                    point.x = add_be(&point.x, &FIELD_MODULUS);
+                } else {
+                    point.y = add_be(&point.y, &FIELD_MODULUS);
+                }
+            }
+            _ => point.x = [0xff; 32],
+        }
+        point
+    }
+
+    /// Classify the point as go-ethereum reads it.
+    pub fn kind(&self) -> PointKind {
+        match self.decode() {
+            Ok(point) if point.is_zero() => PointKind::Infinity,
+            Ok(_) => PointKind::Valid,
+            Err(e) if e == EXCEEDS_MODULUS => PointKind::OutOfField,
+            Err(_) => PointKind::OffCurve,
+        }
+    }
+
+    /// Decode the point, with go-ethereum's errors.
+    pub fn decode(&self) -> Result<G1, String> {
+        let x = Fq::from_slice(&self.x).map_err(|_| EXCEEDS_MODULUS.to_owned())?;
+        let y = Fq::from_slice(&self.y).map_err(|_| EXCEEDS_MODULUS.to_owned())?;
+        if x.is_zero() && y.is_zero() {
+            return Ok(G1::zero());
+        }
+        AffineG1::new(x, y)
+            .map(G1::from)
+            .map_err(|_| MALFORMED_POINT.to_owned())
+    }
+}
+
+impl NewFuzzed for G1Point {
+    type RangeType = u8;
+
+    /// Generate a valid point, sometimes infinity.
+    fn new_fuzzed<R: Rng>(
+        mutator: &mut Mutator<R>,
+        _constraints: Option<&Constraints<Self::RangeType>>,
+    ) -> Self {
+        if mutator.gen_chance(0.1) {
+            Self::infinity()
+        } else {
+            Self::random(mutator)
+        }
+    }
+}
+
+/// A G2 point as two big-endian coordinates in Fq2, each the imaginary part
+/// followed by the real part.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct G2Point {
+    pub x: [u8; 64],
+    pub y: [u8; 64],
+}
+
+fn fq2_bytes(fq2: Fq2) -> [u8; 64] {
+    let mut out = [0u8; 64];
+    out[..32].copy_from_slice(&fq_bytes(fq2.imaginary()));
+    out[32..].copy_from_slice(&fq_bytes(fq2.real()));
+    out
+}
+
+fn fq2_from_bytes(bytes: &[u8; 64]) -> Result<Fq2, String> {
+    let imaginary = Fq::from_slice(&bytes[..32]).map_err(|_| EXCEEDS_MODULUS.to_owned())?;
+    let real = Fq::from_slice(&bytes[32..]).map_err(|_| EXCEEDS_MODULUS.to_owned())?;
+    Ok(Fq2::new(real, imaginary))
+}
+
+impl G2Point {
+    /// The point at infinity.
+    pub fn infinity() -> Self {
+        Self {
+            x: [0; 64],
+            y: [0; 64],
+        }
+    }
+
+    /// Encode a point. Infinity encodes as zeros.
+    pub fn from_g2(point: G2) -> Self {
+        match AffineG2::from_jacobian(point) {
+            Some(affine) => Self {
+                x: fq2_bytes(affine.x()),
+                y: fq2_bytes(affine.y()),
+            },
+            None => Self::infinity(),
+        }
+    }
+
+    /// Read a point from the first 128 bytes of a slice.
+    pub fn from_slice(bytes: &[u8]) -> Self {
+        let mut point = Self::infinity();
+        point.x.copy_from_slice(&bytes[..64]);
+        point.y.copy_from_slice(&bytes[64..128]);
+        point
+    }
+
+    /// The encoded point.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.x.to_vec();
+        out.extend_from_slice(&self.y);
+        out
+    }
+
+    /// A random multiple of the generator.
+    pub fn random<R: Rng>(mutator: &mut Mutator<R>) -> Self {
+        Self::from_g2(G2::one() * gen_scalar(mutator))
+    }
+
+    /// A random point on the twist. The cofactor is about as large as the
+    /// order, so these are almost never in the subgroup.
+    pub fn random_outside_subgroup<R: Rng>(mutator: &mut Mutator<R>) -> Self {
+        loop {
+            let x = Fq2::new(gen_field(mutator), gen_field(mutator));
+            if let Some(y) = (x * x * x + G2::b()).sqrt() {
+                let point = Self {
+                    x: fq2_bytes(x),
+                    y: fq2_bytes(y),
+                };
+                if point.kind() == PointKind::OutsideSubgroup {
+                    return point;
+                }
+            }
+        }
+    }
+
+    /// A random point the precompiles must reject: off the curve, outside
+    /// the subgroup, or with a coordinate at or above the modulus.
+    pub fn random_invalid<R: Rng>(mutator: &mut Mutator<R>) -> Self {
+        let mut point = Self::random(mutator);
+        match mutator.gen_range(0u8, 5) {
+            // one off a valid y
+            0 => {
+                let y = fq2_from_bytes(&point.y).expect("valid");
+                point.y = fq2_bytes(y + Fq2::one());
+            }
+            1 | 2 => point = Self::random_outside_subgroup(mutator),
+            // a valid point with a coordinate not reduced
+            3 => {
+                let coordinate = match mutator.gen_range(0u8, 4) {
+                    0 => &mut point.x[..32],
+                    1 => &mut point.x[32..],
+                    2 => &mut point.y[..32],
+                    _ => &mut point.y[32..],
+                };
+                let mut reduced = [0u8; 32];
+                reduced.copy_from_slice(coordinate);
+                coordinate.copy_from_slice(&add_be(&reduced, &FIELD_MODULUS));
+            }
+            _ => point.x[..32].copy_from_slice(&[0xff; 32]),
+        }
+        point
+    }
+
+    /// True if the coordinates satisfy the twist equation, in or out of the
+    /// subgroup.
+    fn is_on_twist(&self) -> bool {
+        match (fq2_from_bytes(&self.x), fq2_from_bytes(&self.y)) {
+            (Ok(x), Ok(y)) => y * y == x * x * x + G2::b(),
+            _ => false,
+        }
+    }
+
+    /// Classify the point as go-ethereum reads it.
+    pub fn kind(&self) -> PointKind {
+        match self.decode() {
+            Ok(point) if point.is_zero() => PointKind::Infinity,
+            Ok(_) => PointKind::Valid,
+            Err(e) if e == EXCEEDS_MODULUS => PointKind::OutOfField,
+            Err(_) if self.is_on_twist() => PointKind::OutsideSubgroup,
+            Err(_) => PointKind::OffCurve,
+        }
+    }
+
+    /// Decode the point, with go-ethereum's errors. Points outside the
+    /// subgroup are malformed, as go-ethereum checks the order along with the
+    /// curve equation.
+    pub fn decode(&self) -> Result<G2, String> {
+        let x = fq2_from_bytes(&self.x)?;
+        let y = fq2_from_bytes(&self.y)?;
+        if x.is_zero() && y.is_zero() {
+            return Ok(G2::zero());
+        }
+        AffineG2::new(x, y)
+            .map(G2::from)
+            .map_err(|_| MALFORMED_POINT.to_owned())
+    }
+}
+
+impl NewFuzzed for G2Point {
+    type RangeType = u8;
+
+    /// Generate a valid point, sometimes infinity.
+    fn new_fuzzed<R: Rng>(
+        mutator: &mut Mutator<R>,
+        _constraints: Option<&Constraints<Self::RangeType>>,
+    ) -> Self {
+        if mutator.gen_chance(0.1) {
+            Self::infinity()
+        } else {
+            Self::random(mutator)
+        }
+    }
+}
+
+/// A random field element.
+fn gen_field<R: Rng>(mutator: &mut Mutator<R>) -> Fq {
+    let mut wide = [0u8; 64];
+    wide.iter_mut().for_each(|b| *b = mutator.gen());
+    Fq::interpret(&wide)
+}
+
+/// Render a 64-byte output as a point, for comparison reports.
+pub fn display_point(output: &[u8]) -> String {
+    if output.len() != 64 {
+        return hex::encode(output);
+    }
+    let point = G1Point::from_slice(output);
+    if point == G1Point::infinity() {
+        return "infinity".to_owned();
+    }
+    format!(
+        "(x: 0x{}, y: 0x{}) {}",
+        hex::encode(point.x),
+        hex::encode(point.y),
+        point.kind().name()
+    )
+}
+
+/// Trailing bytes past a fixed-length input, or truncation of it. go-ethereum
+/// right-pads short inputs with zeros, and ignores trailing bytes.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Padding {
+    /// The input is its full length
+    Exact,
+    /// The input is truncated to this many bytes. The truncated bytes are
+    /// zero, as the precompile reads them
+    Short(usize),
+    /// Bytes past the input
+    Long(Vec<u8>),
+}
+
+impl Padding {
+    /// Random padding for an input of `len` bytes.
+    pub(crate) fn random<R: Rng>(mutator: &mut Mutator<R>, len: usize) -> Self {
+        if mutator.gen_chance(0.5) {
+            Padding::Short(mutator.gen_range(0, len))
+        } else {
+            let extra = mutator.gen_range(1, 65);
+            Padding::Long((0..extra).map(|_| mutator.gen()).collect())
+        }
+    }
+
+    /// Truncate or extend an encoded input.
+    pub(crate) fn apply(&self, buf: &mut Vec<u8>) {
+        match self {
+            Padding::Exact => {}
+            Padding::Short(len) => buf.truncate(*len),
+            Padding::Long(extra) => buf.extend_from_slice(extra),
+        }
+    }
+
+    /// Split a serialized input into its `len` bytes, zero-padded, and the
+    /// padding.
+    pub(crate) fn split(input: &[u8], len: usize) -> (Vec<u8>, Self) {
+        let mut buf = input.to_vec();
+        if input.len() < len {
+            buf.resize(len, 0);
+            (buf, Padding::Short(input.len()))
+        } else if input.len() > len {
+            let extra = buf.split_off(len);
+            (buf, Padding::Long(extra))
+        } else {
+            (buf, Padding::Exact)
+        }
+    }
+
+    /// A short name for statistics, if not exact.
+    pub fn name(&self) -> Option<&'static str> {
+        match self {
+            Padding::Exact => None,
+            Padding::Short(_) => Some("short"),
+            Padding::Long(_) => Some("long"),
+        }
+    }
+}
+
+/// Truncate a fixed-length input in place, zeroing the bytes past `len` so
+/// that the structure matches what the precompile reads.
+pub(crate) fn zero_tail(buf: &mut [u8], padding: &Padding) {
+    if let Padding::Short(len) = padding {
+        buf[*len..].iter_mut().for_each(|b| *b = 0);
+    }
+}
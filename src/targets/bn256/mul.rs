@@ -0,0 +1,207 @@
+use lain::{byteorder::ByteOrder, prelude::*};
+use std::io::Write;
+
+use super::{display_point, reduce_scalar, zero_tail, G1Point, Padding, PointKind, GROUP_ORDER};
+use crate::{
+    errors::{CommunicationResult, InvariantViolation},
+    invariants,
+    registry::Registration,
+    traits::{ProduceInvalid, Target, TargetWithControl, ThreadContext},
+};
+
+inventory::submit! {
+    Registration::produce_invalid_with_control::<Bn256MulPrecompile>()
+}
+
+/// The input length, a point and a scalar.
+pub const INPUT_LENGTH: usize = 96;
+
+/// Gas for a scalar multiplication since Istanbul (EIP-1108).
+pub const GAS: u64 = 6000;
+
+/// A point and a scalar to multiply it by. Scalars are any 256-bit number,
+/// not reduced by the group order.
+#[derive(Debug, Clone)]
+pub struct Bn256MulArgs {
+    pub point: G1Point,
+    pub scalar: [u8; 32],
+    pub padding: Padding,
+}
+
+/// A scalar. Mostly random, sometimes at the edges of the group order.
+fn gen_scalar_bytes<R: Rng>(mutator: &mut Mutator<R>) -> [u8; 32] {
+    let mut scalar = [0u8; 32];
+    match mutator.gen_range(0u8, 12) {
+        0 => {}
+        1 => scalar[31] = 1,
+        2 => {
+            scalar = GROUP_ORDER;
+            scalar[31] -= 1;
+        }
+        3 => scalar = GROUP_ORDER,
+        4 => {
+            // the low byte of the order has room for this
+            scalar = GROUP_ORDER;
+            scalar[31] += mutator.gen_range(1u8, 0xfe);
+        }
+        5 => scalar = [0xff; 32],
+        // most 256-bit numbers are above the order
+        6 | 7 => scalar.iter_mut().for_each(|b| *b = mutator.gen()),
+        _ => super::gen_scalar(mutator)
+            .to_big_endian(&mut scalar)
+            .expect("32 bytes fit a scalar"),
+    }
+    scalar
+}
+
+impl Bn256MulArgs {
+    /// Parse a serialized input. Short inputs are zero-padded.
+    pub fn parse(input: &[u8]) -> Self {
+        let (buf, padding) = Padding::split(input, INPUT_LENGTH);
+        let mut scalar = [0u8; 32];
+        scalar.copy_from_slice(&buf[64..]);
+        Self {
+            point: G1Point::from_slice(&buf[..64]),
+            scalar,
+            padding,
+        }
+    }
+
+    /// Truncate or extend the input. Truncated bytes are zeroed.
+    pub fn set_padding(&mut self, padding: Padding) {
+        let mut buf = self.point.to_bytes();
+        buf.extend_from_slice(&self.scalar);
+        zero_tail(&mut buf, &padding);
+        *self = Self::parse(&buf);
+        self.padding = padding;
+    }
+}
+
+impl BinarySerialize for Bn256MulArgs {
+    fn binary_serialize<W: Write, E: ByteOrder>(&self, buf: &mut W) -> usize {
+        let mut out = self.point.to_bytes();
+        out.extend_from_slice(&self.scalar);
+        self.padding.apply(&mut out);
+        buf.write_all(&out).unwrap();
+        out.len()
+    }
+}
+
+impl NewFuzzed for Bn256MulArgs {
+    type RangeType = u8;
+
+    /// Generate a valid point and a scalar.
+    fn new_fuzzed<R: Rng>(
+        mutator: &mut Mutator<R>,
+        _constraints: Option<&Constraints<Self::RangeType>>,
+    ) -> Self {
+        Self {
+            point: mutator.gen(),
+            scalar: gen_scalar_bytes(mutator),
+            padding: Padding::Exact,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Bn256MulPrecompile;
+
+impl Target for Bn256MulPrecompile {
+    type Intermediate = Bn256MulArgs;
+    type Rng = lain::rand::rngs::StdRng;
+    type Config = ();
+
+    fn name() -> &'static str {
+        "bn256_mul"
+    }
+
+    fn variant(&self, input: &Self::Intermediate) -> &'static str {
+        let kind = input.point.kind();
+        if !kind.is_accepted() {
+            kind.name()
+        } else if let Some(name) = input.padding.name() {
+            name
+        } else if input.scalar == [0; 32] {
+            "zero_scalar"
+        } else if input.scalar >= GROUP_ORDER {
+            "large_scalar"
+        } else {
+            kind.name()
+        }
+    }
+
+    fn testdata() -> &'static [&'static str] {
+        &["bn256ScalarMul.json"]
+    }
+
+    fn parse(&self, input: &[u8]) -> Option<Self::Intermediate> {
+        Some(Bn256MulArgs::parse(input))
+    }
+
+    fn invariants(
+        &self,
+        input: &Self::Intermediate,
+        results: &[CommunicationResult<Vec<u8>>],
+    ) -> Vec<InvariantViolation> {
+        let kind = input.point.kind();
+        if !kind.is_accepted() {
+            return invariants::rejected(results);
+        }
+        let mut violations = invariants::accepted(results);
+        violations.extend(invariants::output_length(results, 64));
+        for output in results.iter().filter_map(|r| r.as_ref().ok()) {
+            if output.len() != 64 {
+                continue;
+            }
+            let product = G1Point::from_slice(output);
+            if !product.kind().is_accepted() {
+                violations.push(InvariantViolation::new("on_curve", display_point(output)));
+            }
+            // infinity times anything, and anything times zero, is infinity
+            let zero = kind == PointKind::Infinity || input.scalar == [0; 32];
+            if zero && product != G1Point::infinity() {
+                violations.push(InvariantViolation::new("infinity", display_point(output)));
+            }
+        }
+        violations
+    }
+
+    fn expected_gas(&self, _input: &Self::Intermediate) -> Option<u64> {
+        Some(GAS)
+    }
+
+    fn display_output(&self, output: &[u8]) -> String {
+        display_point(output)
+    }
+
+    fn run_raw(
+        &mut self,
+        ctx: &mut ThreadContext,
+        input: &[u8],
+    ) -> Vec<CommunicationResult<Vec<u8>>> {
+        vec![
+            ctx.geth.run_precompile(7u8, input),
+            ctx.celo.run_precompile(7u8, input),
+        ]
+    }
+}
+
+impl ProduceInvalid for Bn256MulPrecompile {
+    /// Replace the point with an invalid one, or truncate or extend the input.
+    fn generate_invalid(&self, mutator: &mut Mutator<Self::Rng>) -> Self::Intermediate {
+        let mut args = self.generate(mutator);
+        if mutator.gen_chance(0.5) {
+            args.point = G1Point::random_invalid(mutator);
+        } else {
+            args.set_padding(Padding::random(mutator, INPUT_LENGTH));
+        }
+        args
+    }
+}
+
+impl TargetWithControl for Bn256MulPrecompile {
+    fn run_control(&self, input: &Self::Intermediate) -> Result<Vec<u8>, String> {
+        let point = input.point.decode()?;
+        Ok(G1Point::from_g1(point * reduce_scalar(&input.scalar)).to_bytes())
+    }
+}
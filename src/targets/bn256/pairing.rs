@@ -0,0 +1,319 @@
+use lain::{byteorder::ByteOrder, prelude::*};
+use std::io::Write;
+use substrate_bn::{pairing_batch, Fr, Group, Gt, G1, G2};
+
+use super::{gen_scalar, G1Point, G2Point, PointKind};
+use crate::{
+    errors::{CommunicationResult, InvariantViolation},
+    invariants,
+    registry::Registration,
+    traits::{ProduceInvalid, Target, TargetWithControl, ThreadContext},
+};
+
+inventory::submit! {
+    Registration::produce_invalid_with_control::<Bn256PairingPrecompile>()
+}
+
+/// The length of a pair, a G1 point and a G2 point.
+pub const PAIR_LENGTH: usize = 192;
+
+/// Base gas for a pairing check since Istanbul (EIP-1108).
+pub const BASE_GAS: u64 = 45000;
+/// Gas per pair since Istanbul (EIP-1108).
+pub const PAIR_GAS: u64 = 34000;
+
+/// go-ethereum's error for an input that is not a whole number of pairs.
+pub const BAD_SIZE: &str = "bad elliptic curve pairing size";
+
+/// How the pairs were built.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum PairingKind {
+    /// The product of the pairings is one. Empty input is one too
+    One,
+    /// A product of one with a single pair changed, so it is not one
+    NearMiss,
+    /// Random pairs, almost never one
+    Random,
+}
+
+/// Pairs to check, and trailing bytes short of a whole pair.
+#[derive(Debug, Clone)]
+pub struct Bn256PairingArgs {
+    pub kind: PairingKind,
+    pub pairs: Vec<(G1Point, G2Point)>,
+    pub tail: Vec<u8>,
+}
+
+/// Pairs `(a_i P, b_i Q)` followed by `(-sum(a_i b_i) P, Q)`, whose product
+/// is one.
+fn product_of_one<R: Rng>(mutator: &mut Mutator<R>) -> Vec<(G1, G2)> {
+    let q = G2::one() * gen_scalar(mutator);
+    let mut sum = Fr::zero();
+    let mut pairs = Vec::new();
+    for _ in 0..mutator.gen_range(1, 4) {
+        let a = gen_scalar(mutator);
+        let b = gen_scalar(mutator);
+        sum = sum + a * b;
+        pairs.push((G1::one() * a, q * b));
+    }
+    pairs.push((G1::one() * -sum, q));
+    pairs
+}
+
+/// Change the last pair of a product of one, so that the product is not one.
+fn near_miss<R: Rng>(mutator: &mut Mutator<R>, pairs: &mut Vec<(G1, G2)>) {
+    let (p, q) = pairs.last_mut().expect("products have pairs");
+    match mutator.gen_range(0u8, 4) {
+        0 => *p = *p + G1::one(),
+        1 => *q = *q + G2::one(),
+        2 => *p = -*p,
+        _ => {
+            pairs.pop();
+        }
+    }
+}
+
+impl Bn256PairingArgs {
+    /// Parse a serialized input. The kind is found by running the control.
+    pub fn parse(input: &[u8]) -> Self {
+        let whole = input.len() - input.len() % PAIR_LENGTH;
+        let mut args = Self {
+            kind: PairingKind::Random,
+            pairs: input[..whole]
+                .chunks(PAIR_LENGTH)
+                .map(|pair| (G1Point::from_slice(pair), G2Point::from_slice(&pair[64..])))
+                .collect(),
+            tail: input[whole..].to_vec(),
+        };
+        if args.check() == Ok(true) {
+            args.kind = PairingKind::One;
+        }
+        args
+    }
+
+    /// The kind of the worst point. Valid if there are none.
+    pub fn point_kind(&self) -> PointKind {
+        self.pairs
+            .iter()
+            .flat_map(|(a, b)| vec![a.kind(), b.kind()])
+            .max()
+            .unwrap_or(PointKind::Valid)
+    }
+
+    /// Check the pairs, with go-ethereum's errors.
+    pub fn check(&self) -> Result<bool, String> {
+        if !self.tail.is_empty() {
+            return Err(BAD_SIZE.to_owned());
+        }
+        let pairs = self
+            .pairs
+            .iter()
+            .map(|(a, b)| Ok((a.decode()?, b.decode()?)))
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(pairing_batch(&pairs) == Gt::one())
+    }
+
+    /// The serialized length.
+    pub fn len(&self) -> usize {
+        self.pairs.len() * PAIR_LENGTH + self.tail.len()
+    }
+
+    /// True if there are no pairs and no trailing bytes.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A pairing check result, as a 32-byte word.
+fn word(one: bool) -> Vec<u8> {
+    let mut out = vec![0u8; 32];
+    out[31] = one as u8;
+    out
+}
+
+impl BinarySerialize for Bn256PairingArgs {
+    fn binary_serialize<W: Write, E: ByteOrder>(&self, buf: &mut W) -> usize {
+        let mut out = Vec::with_capacity(self.len());
+        for (a, b) in &self.pairs {
+            out.extend_from_slice(&a.to_bytes());
+            out.extend_from_slice(&b.to_bytes());
+        }
+        out.extend_from_slice(&self.tail);
+        buf.write_all(&out).unwrap();
+        out.len()
+    }
+}
+
+impl NewFuzzed for Bn256PairingArgs {
+    type RangeType = u8;
+
+    /// Generate a product of one, a near miss, or random pairs. Products
+    /// sometimes include pairs with a point at infinity, which pair to one.
+    fn new_fuzzed<R: Rng>(
+        mutator: &mut Mutator<R>,
+        _constraints: Option<&Constraints<Self::RangeType>>,
+    ) -> Self {
+        let (kind, pairs) = match mutator.gen_range(0u8, 20) {
+            0 => (PairingKind::One, vec![]),
+            1..=9 => (PairingKind::One, product_of_one(mutator)),
+            10..=14 => {
+                let mut pairs = product_of_one(mutator);
+                near_miss(mutator, &mut pairs);
+                (PairingKind::NearMiss, pairs)
+            }
+            _ => {
+                let pairs = (0..mutator.gen_range(1, 5))
+                    .map(|_| (mutator.gen(), mutator.gen()))
+                    .collect();
+                let mut args = Self {
+                    kind: PairingKind::Random,
+                    pairs,
+                    tail: vec![],
+                };
+                // random points include points at infinity, which pair to one
+                if args.check() == Ok(true) {
+                    args.kind = PairingKind::One;
+                }
+                return args;
+            }
+        };
+        let mut pairs: Vec<_> = pairs
+            .into_iter()
+            .map(|(a, b)| (G1Point::from_g1(a), G2Point::from_g2(b)))
+            .collect();
+        if mutator.gen_chance(0.2) {
+            let pair = if mutator.gen_chance(0.5) {
+                (G1Point::infinity(), G2Point::random(mutator))
+            } else {
+                (G1Point::random(mutator), G2Point::infinity())
+            };
+            let at = mutator.gen_range(0, pairs.len() + 1);
+            pairs.insert(at, pair);
+        }
+        Self {
+            kind,
+            pairs,
+            tail: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Bn256PairingPrecompile;
+
+impl Target for Bn256PairingPrecompile {
+    type Intermediate = Bn256PairingArgs;
+    type Rng = lain::rand::rngs::StdRng;
+    type Config = ();
+
+    fn name() -> &'static str {
+        "bn256_pairing"
+    }
+
+    fn variant(&self, input: &Self::Intermediate) -> &'static str {
+        let kind = input.point_kind();
+        if !input.tail.is_empty() {
+            "bad_length"
+        } else if !kind.is_accepted() {
+            kind.name()
+        } else if input.pairs.is_empty() {
+            "empty"
+        } else {
+            match input.kind {
+                PairingKind::One => "one",
+                PairingKind::NearMiss => "near_miss",
+                PairingKind::Random => "random",
+            }
+        }
+    }
+
+    fn testdata() -> &'static [&'static str] {
+        &["bn256Pairing.json"]
+    }
+
+    fn parse(&self, input: &[u8]) -> Option<Self::Intermediate> {
+        Some(Bn256PairingArgs::parse(input))
+    }
+
+    fn invariants(
+        &self,
+        input: &Self::Intermediate,
+        results: &[CommunicationResult<Vec<u8>>],
+    ) -> Vec<InvariantViolation> {
+        if !input.tail.is_empty() || !input.point_kind().is_accepted() {
+            return invariants::rejected(results);
+        }
+        let mut violations = invariants::accepted(results);
+        violations.extend(invariants::output_length(results, 32));
+        for output in results.iter().filter_map(|r| r.as_ref().ok()) {
+            let shown = self.display_output(output);
+            if output.len() == 32 && *output != word(true) && *output != word(false) {
+                violations.push(InvariantViolation::new("boolean", shown));
+            } else if input.kind == PairingKind::One && *output != word(true) {
+                violations.push(InvariantViolation::new("one", shown));
+            } else if input.kind == PairingKind::NearMiss && *output != word(false) {
+                violations.push(InvariantViolation::new("near_miss", shown));
+            }
+        }
+        violations
+    }
+
+    fn expected_gas(&self, input: &Self::Intermediate) -> Option<u64> {
+        // go-ethereum charges for whole pairs even if the size is bad
+        Some(BASE_GAS + PAIR_GAS * (input.len() / PAIR_LENGTH) as u64)
+    }
+
+    fn display_output(&self, output: &[u8]) -> String {
+        if *output == word(true)[..] {
+            "true".to_owned()
+        } else if *output == word(false)[..] {
+            "false".to_owned()
+        } else {
+            hex::encode(output)
+        }
+    }
+
+    fn run_raw(
+        &mut self,
+        ctx: &mut ThreadContext,
+        input: &[u8],
+    ) -> Vec<CommunicationResult<Vec<u8>>> {
+        vec![
+            ctx.geth.run_precompile(8u8, input),
+            ctx.celo.run_precompile(8u8, input),
+        ]
+    }
+}
+
+impl ProduceInvalid for Bn256PairingPrecompile {
+    /// Replace a point with an invalid one, or cut or extend the input to a
+    /// length that is not a whole number of pairs.
+    fn generate_invalid(&self, mutator: &mut Mutator<Self::Rng>) -> Self::Intermediate {
+        let mut args = self.generate(mutator);
+        if args.pairs.is_empty() {
+            args.pairs.push((mutator.gen(), mutator.gen()));
+        }
+        let at = mutator.gen_range(0, args.pairs.len());
+        match mutator.gen_range(0u8, 4) {
+            0 => args.pairs[at].0 = G1Point::random_invalid(mutator),
+            1 | 2 => args.pairs[at].1 = G2Point::random_invalid(mutator),
+            _ if mutator.gen_chance(0.5) => {
+                let (a, b) = args.pairs.pop().expect("not empty");
+                let mut pair = a.to_bytes();
+                pair.extend_from_slice(&b.to_bytes());
+                args.tail = pair[..mutator.gen_range(1, PAIR_LENGTH)].to_vec();
+            }
+            _ => {
+                let extra = mutator.gen_range(1, PAIR_LENGTH);
+                args.tail = (0..extra).map(|_| mutator.gen()).collect();
+            }
+        }
+        args
+    }
+}
+
+impl TargetWithControl for Bn256PairingPrecompile {
+    fn run_control(&self, input: &Self::Intermediate) -> Result<Vec<u8>, String> {
+        input.check().map(word)
+    }
+}
@@ -0,0 +1,691 @@
+//! celo's header precompiles, getBlockNumberFromHeader and hashHeader.
+//!
+//! Both decode an RLP header as celo-blockchain's `types.Header`. Its decoder
+//! is strict: non-canonical lengths and integers, trailing bytes and wrong
+//! field counts are all rejected, and the control rejects them too. celo
+//! headers have no difficulty, gas limit, mix digest or nonce.
+
+use lain::{byteorder::ByteOrder, prelude::*};
+use num_bigint::BigUint;
+use rlp::{DecoderError, PayloadInfo, Rlp, RlpStream};
+use sha3::{Digest, Keccak256};
+use std::io::Write;
+
+use crate::{
+    errors::{CommunicationResult, InvariantViolation},
+    invariants,
+    registry::Registration,
+    traits::{ProduceInvalid, Target, TargetWithControl, ThreadContext},
+};
+
+inventory::submit! {
+    Registration::produce_invalid_with_control::<BlockNumberFromHeaderPrecompile>()
+}
+
+inventory::submit! {
+    Registration::produce_invalid_with_control::<HashHeaderPrecompile>()
+}
+
+/// The number of fields in a header.
+pub const HEADER_FIELDS: usize = 10;
+/// The length of the vanity before the Istanbul extra data.
+pub const VANITY_LENGTH: usize = 32;
+/// The length of an address.
+pub const ADDRESS_LENGTH: usize = 20;
+/// The length of a hash.
+pub const HASH_LENGTH: usize = 32;
+/// The length of a header's bloom filter.
+pub const BLOOM_LENGTH: usize = 256;
+/// The length of a validator's serialized BLS public key.
+pub const PUBLIC_KEY_LENGTH: usize = 96;
+/// Gas for either precompile, for any input.
+pub const GAS: u64 = 10;
+
+/// celo's error for an input that does not decode as a header.
+pub const DECODE_ERROR: &str = "input Decode Error";
+
+/// Split the first item from `input`.
+fn split(input: &[u8]) -> Result<(Rlp<'_>, &[u8]), DecoderError> {
+    let total = PayloadInfo::from(input)?.total();
+    if total > input.len() {
+        return Err(DecoderError::RlpIsTooShort);
+    }
+    Ok((Rlp::new(&input[..total]), &input[total..]))
+}
+
+/// The single item `input` holds, rejecting trailing bytes.
+fn single(input: &[u8]) -> Result<Rlp<'_>, DecoderError> {
+    let (rlp, rest) = split(input)?;
+    if !rest.is_empty() {
+        return Err(DecoderError::RlpIsTooBig);
+    }
+    Ok(rlp)
+}
+
+/// A list's items. Each must fill its slice of the list exactly.
+fn list<'a>(rlp: &Rlp<'a>) -> Result<Vec<Rlp<'a>>, DecoderError> {
+    if !rlp.is_list() {
+        return Err(DecoderError::RlpExpectedToBeList);
+    }
+    let mut rest = &rlp.as_raw()[rlp.payload_info()?.header_len..];
+    let mut items = vec![];
+    while !rest.is_empty() {
+        let (item, next) = split(rest)?;
+        items.push(item);
+        rest = next;
+    }
+    Ok(items)
+}
+
+/// A list of exactly `count` items.
+fn fields<'a>(rlp: &Rlp<'a>, count: usize) -> Result<Vec<Rlp<'a>>, DecoderError> {
+    let items = list(rlp)?;
+    if items.len() != count {
+        return Err(DecoderError::RlpIncorrectListLen);
+    }
+    Ok(items)
+}
+
+/// A byte string. Single bytes below 0x80 must be encoded as themselves.
+fn bytes(rlp: &Rlp) -> Result<Vec<u8>, DecoderError> {
+    rlp.as_val()
+}
+
+/// A byte string of exactly `len` bytes.
+fn fixed(rlp: &Rlp, len: usize) -> Result<Vec<u8>, DecoderError> {
+    let bytes = bytes(rlp)?;
+    if bytes.len() != len {
+        return Err(DecoderError::Custom("wrong length"));
+    }
+    Ok(bytes)
+}
+
+/// A big integer, without leading zeros. Any length is accepted.
+fn int(rlp: &Rlp) -> Result<Vec<u8>, DecoderError> {
+    let bytes = bytes(rlp)?;
+    if bytes.first() == Some(&0) {
+        return Err(DecoderError::RlpInvalidIndirection);
+    }
+    Ok(bytes)
+}
+
+/// A random big-endian integer of up to `max` bytes, without leading zeros.
+fn gen_int<R: Rng>(mutator: &mut Mutator<R>, max: usize) -> Vec<u8> {
+    let len = mutator.gen_range(0, max + 1);
+    let mut int = gen_bytes(mutator, len);
+    if let Some(first) = int.first_mut() {
+        *first |= 1;
+    }
+    int
+}
+
+fn gen_bytes<R: Rng>(mutator: &mut Mutator<R>, len: usize) -> Vec<u8> {
+    (0..len).map(|_| mutator.gen()).collect()
+}
+
+/// An aggregated BLS seal over a block.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct AggregatedSeal {
+    pub bitmap: Vec<u8>,
+    pub signature: Vec<u8>,
+    pub round: Vec<u8>,
+}
+
+impl AggregatedSeal {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        let items = fields(rlp, 3)?;
+        Ok(Self {
+            bitmap: int(&items[0])?,
+            signature: bytes(&items[1])?,
+            round: int(&items[2])?,
+        })
+    }
+
+    fn append(&self, stream: &mut RlpStream) {
+        stream
+            .begin_list(3)
+            .append(&self.bitmap)
+            .append(&self.signature)
+            .append(&self.round);
+    }
+
+    fn new_fuzzed<R: Rng>(mutator: &mut Mutator<R>) -> Self {
+        let signature = if mutator.gen_chance(0.8) {
+            gen_bytes(mutator, 48)
+        } else {
+            vec![]
+        };
+        Self {
+            bitmap: gen_int(mutator, 16),
+            signature,
+            round: gen_int(mutator, 2),
+        }
+    }
+}
+
+/// The Istanbul consensus data following the vanity in a header's extra
+/// data.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct IstanbulExtra {
+    pub added_validators: Vec<Vec<u8>>,
+    pub added_public_keys: Vec<Vec<u8>>,
+    /// A bitmap of the removed validators
+    pub removed_validators: Vec<u8>,
+    /// The proposer's ECDSA signature
+    pub seal: Vec<u8>,
+    pub aggregated_seal: AggregatedSeal,
+    pub parent_aggregated_seal: AggregatedSeal,
+}
+
+impl IstanbulExtra {
+    /// Decode the Istanbul data from a header's extra data, if it has any.
+    pub fn extract(extra: &[u8]) -> Option<Self> {
+        if extra.len() < VANITY_LENGTH {
+            return None;
+        }
+        Self::decode(&extra[VANITY_LENGTH..]).ok()
+    }
+
+    fn decode(input: &[u8]) -> Result<Self, DecoderError> {
+        let items = fields(&single(input)?, 6)?;
+        let each = |rlp: &Rlp, len| -> Result<Vec<Vec<u8>>, DecoderError> {
+            list(rlp)?.iter().map(|item| fixed(item, len)).collect()
+        };
+        Ok(Self {
+            added_validators: each(&items[0], ADDRESS_LENGTH)?,
+            added_public_keys: each(&items[1], PUBLIC_KEY_LENGTH)?,
+            removed_validators: int(&items[2])?,
+            seal: bytes(&items[3])?,
+            aggregated_seal: AggregatedSeal::decode(&items[4])?,
+            parent_aggregated_seal: AggregatedSeal::decode(&items[5])?,
+        })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut stream = RlpStream::new_list(6);
+        stream
+            .append_list::<Vec<u8>, _>(&self.added_validators)
+            .append_list::<Vec<u8>, _>(&self.added_public_keys)
+            .append(&self.removed_validators)
+            .append(&self.seal);
+        self.aggregated_seal.append(&mut stream);
+        self.parent_aggregated_seal.append(&mut stream);
+        stream.out().to_vec()
+    }
+
+    fn new_fuzzed<R: Rng>(mutator: &mut Mutator<R>) -> Self {
+        let validators = mutator.gen_range(0, 4);
+        let keys = if mutator.gen_chance(0.8) {
+            validators
+        } else {
+            mutator.gen_range(0, 4)
+        };
+        let seal = if mutator.gen_chance(0.9) {
+            gen_bytes(mutator, 65)
+        } else {
+            vec![]
+        };
+        Self {
+            added_validators: (0..validators)
+                .map(|_| gen_bytes(mutator, ADDRESS_LENGTH))
+                .collect(),
+            added_public_keys: (0..keys)
+                .map(|_| gen_bytes(mutator, PUBLIC_KEY_LENGTH))
+                .collect(),
+            removed_validators: gen_int(mutator, 8),
+            seal,
+            aggregated_seal: AggregatedSeal::new_fuzzed(mutator),
+            parent_aggregated_seal: AggregatedSeal::new_fuzzed(mutator),
+        }
+    }
+}
+
+/// A celo block header. The hashes, coinbase and bloom are kept at their
+/// fixed lengths, and the number is big-endian without leading zeros.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Header {
+    pub parent_hash: Vec<u8>,
+    pub coinbase: Vec<u8>,
+    pub root: Vec<u8>,
+    pub tx_hash: Vec<u8>,
+    pub receipt_hash: Vec<u8>,
+    pub bloom: Vec<u8>,
+    pub number: Vec<u8>,
+    pub gas_used: u64,
+    pub time: u64,
+    pub extra: Vec<u8>,
+}
+
+impl Header {
+    /// Decode a header as celo does.
+    pub fn decode(input: &[u8]) -> Result<Self, DecoderError> {
+        let items = fields(&single(input)?, HEADER_FIELDS)?;
+        Ok(Self {
+            parent_hash: fixed(&items[0], HASH_LENGTH)?,
+            coinbase: fixed(&items[1], ADDRESS_LENGTH)?,
+            root: fixed(&items[2], HASH_LENGTH)?,
+            tx_hash: fixed(&items[3], HASH_LENGTH)?,
+            receipt_hash: fixed(&items[4], HASH_LENGTH)?,
+            bloom: fixed(&items[5], BLOOM_LENGTH)?,
+            number: int(&items[6])?,
+            gas_used: items[7].as_val()?,
+            time: items[8].as_val()?,
+            extra: bytes(&items[9])?,
+        })
+    }
+
+    /// Each field's encoding.
+    pub fn fields(&self) -> Vec<Vec<u8>> {
+        vec![
+            rlp::encode(&self.parent_hash),
+            rlp::encode(&self.coinbase),
+            rlp::encode(&self.root),
+            rlp::encode(&self.tx_hash),
+            rlp::encode(&self.receipt_hash),
+            rlp::encode(&self.bloom),
+            rlp::encode(&self.number),
+            rlp::encode(&self.gas_used),
+            rlp::encode(&self.time),
+            rlp::encode(&self.extra),
+        ]
+        .into_iter()
+        .map(|field| field.to_vec())
+        .collect()
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        wrap(&self.fields())
+    }
+
+    /// The number, left-padded to 32 bytes. Longer numbers are not
+    /// truncated.
+    pub fn number_word(&self) -> Vec<u8> {
+        let mut out = vec![0u8; 32usize.saturating_sub(self.number.len())];
+        out.extend_from_slice(&self.number);
+        out
+    }
+
+    /// The hash as celo computes it. Headers with Istanbul extra data are
+    /// hashed with its aggregated seal cleared, and others as they are.
+    pub fn hash(&self) -> Vec<u8> {
+        let mut header = self.clone();
+        if let Some(mut extra) = IstanbulExtra::extract(&self.extra) {
+            extra.aggregated_seal = AggregatedSeal::default();
+            header.extra.truncate(VANITY_LENGTH);
+            header.extra.extend_from_slice(&extra.encode());
+        }
+        Keccak256::digest(&header.encode()).to_vec()
+    }
+}
+
+/// Wrap encoded items in a list.
+fn wrap(items: &[Vec<u8>]) -> Vec<u8> {
+    let mut stream = RlpStream::new_list(items.len());
+    items.iter().for_each(|item| {
+        stream.append_raw(item, 1);
+    });
+    stream.out().to_vec()
+}
+
+/// Extra data, mostly a vanity and Istanbul data. Sometimes too short for
+/// the vanity, or followed by data that does not decode, which celo hashes
+/// as it is.
+fn gen_extra<R: Rng>(mutator: &mut Mutator<R>) -> Vec<u8> {
+    let mut extra = gen_bytes(mutator, VANITY_LENGTH);
+    match mutator.gen_range(0u8, 10) {
+        0..=5 => extra.extend_from_slice(&IstanbulExtra::new_fuzzed(mutator).encode()),
+        6 => extra.truncate(mutator.gen_range(0, VANITY_LENGTH)),
+        7 => {
+            let len = mutator.gen_range(0, 64);
+            extra.extend_from_slice(&gen_bytes(mutator, len))
+        }
+        8 => {
+            // a trailing byte, or a truncated list
+            let mut istanbul = IstanbulExtra::new_fuzzed(mutator).encode();
+            if mutator.gen_chance(0.5) {
+                istanbul.push(mutator.gen());
+            } else {
+                istanbul.pop();
+            }
+            extra.extend_from_slice(&istanbul);
+        }
+        _ => {}
+    }
+    extra
+}
+
+impl NewFuzzed for Header {
+    type RangeType = u8;
+
+    /// Generate numbers of mostly up to 8 bytes, and sometimes past 32.
+    fn new_fuzzed<R: Rng>(
+        mutator: &mut Mutator<R>,
+        _constraints: Option<&Constraints<Self::RangeType>>,
+    ) -> Self {
+        let number_length = match mutator.gen_range(0u8, 20) {
+            0 => 0,
+            1 => mutator.gen_range(9, 33),
+            2 => mutator.gen_range(33, 41),
+            _ => mutator.gen_range(1, 9),
+        };
+        let mut number = gen_bytes(mutator, number_length);
+        if let Some(first) = number.first_mut() {
+            *first |= 1;
+        }
+        let gen_u64 = |mutator: &mut Mutator<R>| {
+            if mutator.gen_chance(0.1) {
+                0
+            } else {
+                mutator.gen()
+            }
+        };
+        Self {
+            parent_hash: gen_bytes(mutator, HASH_LENGTH),
+            coinbase: gen_bytes(mutator, ADDRESS_LENGTH),
+            root: gen_bytes(mutator, HASH_LENGTH),
+            tx_hash: gen_bytes(mutator, HASH_LENGTH),
+            receipt_hash: gen_bytes(mutator, HASH_LENGTH),
+            bloom: gen_bytes(mutator, BLOOM_LENGTH),
+            number,
+            gas_used: gen_u64(mutator),
+            time: gen_u64(mutator),
+            extra: gen_extra(mutator),
+        }
+    }
+}
+
+/// Re-encode a byte string with a longer length prefix than needed.
+fn non_canonical(field: &[u8]) -> Vec<u8> {
+    let payload = Rlp::new(field).data().expect("fields are strings");
+    let mut out = match payload.len() {
+        1 if payload[0] < 0x80 => vec![0x81],
+        0..=55 => vec![0xb8, payload.len() as u8],
+        len => {
+            // a zero before the length
+            let len = (len as u32).to_be_bytes();
+            let skip = len.iter().take_while(|b| **b == 0).count();
+            let mut out = vec![0xb7 + (5 - skip) as u8, 0];
+            out.extend_from_slice(&len[skip..]);
+            out
+        }
+    };
+    out.extend_from_slice(payload);
+    out
+}
+
+/// The kind of an input.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum HeaderKind {
+    /// A header with Istanbul extra data
+    Istanbul,
+    /// A header whose extra data celo hashes as it is
+    Plain,
+    /// A header numbered past 32 bytes
+    LongNumber,
+    /// An input that does not decode as a header
+    Malformed,
+}
+
+/// A serialized header, valid or not.
+#[derive(Debug, Clone)]
+pub struct HeaderArgs {
+    pub input: Vec<u8>,
+}
+
+impl HeaderArgs {
+    pub fn header(&self) -> Result<Header, DecoderError> {
+        Header::decode(&self.input)
+    }
+
+    pub fn kind(&self) -> HeaderKind {
+        match self.header() {
+            Err(_) => HeaderKind::Malformed,
+            Ok(header) if header.number.len() > 32 => HeaderKind::LongNumber,
+            Ok(header) if IstanbulExtra::extract(&header.extra).is_some() => HeaderKind::Istanbul,
+            Ok(_) => HeaderKind::Plain,
+        }
+    }
+
+    /// Break a valid header's encoding: truncate it, add trailing bytes,
+    /// lengthen a length prefix, give an integer a leading zero or too many
+    /// bytes, drop or add a field, change a fixed length, or replace a
+    /// field with a list.
+    pub fn new_invalid<R: Rng>(mutator: &mut Mutator<R>) -> Self {
+        let header = Header::new_fuzzed(mutator, None);
+        let mut fields = header.fields();
+        let index = mutator.gen_range(0, HEADER_FIELDS);
+        let input = match mutator.gen_range(0u8, 8) {
+            0 => {
+                let mut input = header.encode();
+                input.truncate(mutator.gen_range(0, input.len()));
+                input
+            }
+            1 => {
+                let mut input = header.encode();
+                let len = mutator.gen_range(1, 8);
+                input.extend_from_slice(&gen_bytes(mutator, len));
+                input
+            }
+            2 => {
+                fields[index] = non_canonical(&fields[index]);
+                wrap(&fields)
+            }
+            3 => {
+                // the number, gas used or time
+                let index = mutator.gen_range(6, 9);
+                let mut payload = vec![0];
+                payload.extend_from_slice(Rlp::new(&fields[index]).data().expect("a string"));
+                fields[index] = rlp::encode(&payload).to_vec();
+                wrap(&fields)
+            }
+            4 => {
+                let len = mutator.gen_range(9, 17);
+                let mut int = gen_bytes(mutator, len);
+                int[0] |= 1;
+                fields[mutator.gen_range(7, 9)] = rlp::encode(&int).to_vec();
+                wrap(&fields)
+            }
+            5 => {
+                if mutator.gen_chance(0.5) {
+                    fields.remove(index);
+                } else {
+                    fields.insert(index, rlp::encode(&gen_int(mutator, 8)).to_vec());
+                }
+                wrap(&fields)
+            }
+            6 => {
+                // a hash, the coinbase or the bloom
+                let index = mutator.gen_range(0, 6);
+                let mut bytes = Rlp::new(&fields[index]).data().expect("a string").to_vec();
+                if mutator.gen_chance(0.5) {
+                    bytes.pop();
+                } else {
+                    bytes.push(mutator.gen());
+                }
+                fields[index] = rlp::encode(&bytes).to_vec();
+                wrap(&fields)
+            }
+            _ => {
+                fields[index] = vec![0xc0];
+                wrap(&fields)
+            }
+        };
+        Self { input }
+    }
+}
+
+impl BinarySerialize for HeaderArgs {
+    fn binary_serialize<W: Write, E: ByteOrder>(&self, buf: &mut W) -> usize {
+        buf.write_all(&self.input).unwrap();
+        self.input.len()
+    }
+}
+
+impl NewFuzzed for HeaderArgs {
+    type RangeType = u8;
+
+    fn new_fuzzed<R: Rng>(
+        mutator: &mut Mutator<R>,
+        _constraints: Option<&Constraints<Self::RangeType>>,
+    ) -> Self {
+        Self {
+            input: Header::new_fuzzed(mutator, None).encode(),
+        }
+    }
+}
+
+fn variant(input: &HeaderArgs) -> &'static str {
+    match input.kind() {
+        HeaderKind::Istanbul => "istanbul",
+        HeaderKind::Plain => "plain",
+        HeaderKind::LongNumber => "long_number",
+        HeaderKind::Malformed => "malformed",
+    }
+}
+
+/// celo's getBlockNumberFromHeader.
+#[derive(Debug, Default)]
+pub struct BlockNumberFromHeaderPrecompile;
+
+impl Target for BlockNumberFromHeaderPrecompile {
+    type Intermediate = HeaderArgs;
+    type Rng = lain::rand::rngs::StdRng;
+    type Config = ();
+
+    fn name() -> &'static str {
+        "block_number_from_header"
+    }
+
+    fn variant(&self, input: &Self::Intermediate) -> &'static str {
+        variant(input)
+    }
+
+    fn testdata() -> &'static [&'static str] {
+        &[]
+    }
+
+    fn parse(&self, input: &[u8]) -> Option<Self::Intermediate> {
+        Some(HeaderArgs {
+            input: input.to_vec(),
+        })
+    }
+
+    fn invariants(
+        &self,
+        input: &Self::Intermediate,
+        results: &[CommunicationResult<Vec<u8>>],
+    ) -> Vec<InvariantViolation> {
+        match input.header() {
+            Ok(header) => {
+                let mut violations = invariants::accepted(results);
+                violations.extend(invariants::output_length(
+                    results,
+                    header.number_word().len(),
+                ));
+                violations
+            }
+            Err(_) => invariants::rejected(results),
+        }
+    }
+
+    fn expected_gas(&self, _input: &Self::Intermediate) -> Option<u64> {
+        Some(GAS)
+    }
+
+    fn display_output(&self, output: &[u8]) -> String {
+        BigUint::from_bytes_be(output).to_string()
+    }
+
+    fn run_raw(
+        &mut self,
+        context: &mut ThreadContext,
+        input: &[u8],
+    ) -> Vec<CommunicationResult<Vec<u8>>> {
+        vec![context.celo.run_precompile(0xf7, input)]
+    }
+}
+
+impl ProduceInvalid for BlockNumberFromHeaderPrecompile {
+    fn generate_invalid(&self, mutator: &mut Mutator<Self::Rng>) -> Self::Intermediate {
+        HeaderArgs::new_invalid(mutator)
+    }
+}
+
+impl TargetWithControl for BlockNumberFromHeaderPrecompile {
+    fn run_control(&self, input: &Self::Intermediate) -> Result<Vec<u8>, String> {
+        input
+            .header()
+            .map(|header| header.number_word())
+            .map_err(|_| DECODE_ERROR.to_owned())
+    }
+}
+
+/// celo's hashHeader.
+#[derive(Debug, Default)]
+pub struct HashHeaderPrecompile;
+
+impl Target for HashHeaderPrecompile {
+    type Intermediate = HeaderArgs;
+    type Rng = lain::rand::rngs::StdRng;
+    type Config = ();
+
+    fn name() -> &'static str {
+        "hash_header"
+    }
+
+    fn variant(&self, input: &Self::Intermediate) -> &'static str {
+        variant(input)
+    }
+
+    fn testdata() -> &'static [&'static str] {
+        &[]
+    }
+
+    fn parse(&self, input: &[u8]) -> Option<Self::Intermediate> {
+        Some(HeaderArgs {
+            input: input.to_vec(),
+        })
+    }
+
+    fn invariants(
+        &self,
+        input: &Self::Intermediate,
+        results: &[CommunicationResult<Vec<u8>>],
+    ) -> Vec<InvariantViolation> {
+        match input.kind() {
+            HeaderKind::Malformed => invariants::rejected(results),
+            _ => {
+                let mut violations = invariants::accepted(results);
+                violations.extend(invariants::output_length(results, HASH_LENGTH));
+                violations
+            }
+        }
+    }
+
+    fn expected_gas(&self, _input: &Self::Intermediate) -> Option<u64> {
+        Some(GAS)
+    }
+
+    fn run_raw(
+        &mut self,
+        context: &mut ThreadContext,
+        input: &[u8],
+    ) -> Vec<CommunicationResult<Vec<u8>>> {
+        vec![context.celo.run_precompile(0xf6, input)]
+    }
+}
+
+impl ProduceInvalid for HashHeaderPrecompile {
+    fn generate_invalid(&self, mutator: &mut Mutator<Self::Rng>) -> Self::Intermediate {
+        HeaderArgs::new_invalid(mutator)
+    }
+}
+
+impl TargetWithControl for HashHeaderPrecompile {
+    fn run_control(&self, input: &Self::Intermediate) -> Result<Vec<u8>, String> {
+        input
+            .header()
+            .map(|header| header.hash())
+            .map_err(|_| DECODE_ERROR.to_owned())
+    }
+}
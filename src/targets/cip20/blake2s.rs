@@ -19,6 +19,44 @@ pub struct Blake2sArgs {
 }
 
 impl Blake2sArgs {
+    /// Parse serialized args. Returns `None` if the input is not a valid
+    /// blake2s call.
+    pub fn parse(input: &[u8]) -> Option<Self> {
+        if input.len() < 33 || input[0] != SELECTOR {
+            return None;
+        }
+        let key_length = input[2] as usize;
+        let mut leaf_length = [0u8; 4];
+        leaf_length.copy_from_slice(&input[5..9]);
+        let mut node_offset = [0u8; 8];
+        node_offset[..6].copy_from_slice(&input[9..15]);
+        let mut salt = [0u8; 8];
+        salt.copy_from_slice(&input[17..25]);
+        let mut personalization = [0u8; 8];
+        personalization.copy_from_slice(&input[25..33]);
+
+        let args = Self {
+            hash_length: input[1],
+            fanout: input[3],
+            depth: input[4],
+            leaf_length: u32::from_le_bytes(leaf_length),
+            node_offset: u64::from_le_bytes(node_offset),
+            node_depth: input[15],
+            inner_length: input[16],
+            salt,
+            personalization,
+            key: input.get(33..33 + key_length)?.to_vec(),
+            preimage: input[33 + key_length..].to_vec(),
+        };
+
+        let valid = |len: u8| (1..=32).contains(&len);
+        if valid(args.hash_length) && valid(args.inner_length) && key_length <= 32 {
+            Some(args)
+        } else {
+            None
+        }
+    }
+
     pub fn run(&self) -> Vec<u8> {
         blake2s_simd::Params::new()
             .hash_length(self.hash_length as usize)
@@ -4,13 +4,20 @@ use std::io::Write;
 
 pub mod blake2s;
 
-use blake2s::Blake2sGenOpts;
+use blake2s::{Blake2sArgs, Blake2sGenOpts};
 
 use crate::{
-    errors::CommunicationResult,
-    traits::{ProduceInvalid, Target, TargetWithControl, ThreadContext},
+    errors::{CommunicationResult, InvariantViolation},
+    invariants,
+    registry::Registration,
+    traits::{Metamorphic, ProduceInvalid, Target, TargetWithControl, ThreadContext},
 };
 
+inventory::submit! {
+    Registration::produce_invalid_with_control::<Cip20Precompile>()
+        .set_metamorphic::<Cip20Precompile>()
+}
+
 const SHA_3_256_SELECTOR: u8 = 0x00;
 const SHA_3_512_SELECTOR: u8 = 0x01;
 const KECCAK_512_SELECTOR: u8 = 0x02;
@@ -104,6 +111,50 @@ impl Target for Cip20Precompile {
         "cip20"
     }
 
+    fn variant(&self, input: &Self::Intermediate) -> &'static str {
+        match input {
+            CIP20Modes::Invalid(_) => "invalid",
+            CIP20Modes::Sha3_256(_) => "sha3_256",
+            CIP20Modes::Sha3_512(_) => "sha3_512",
+            CIP20Modes::Keccak512(_) => "keccak512",
+            CIP20Modes::SHA2_512(_) => "sha2_512",
+            CIP20Modes::Blake2s(Blake2sGenOpts::Valid(_)) => "blake2s",
+            CIP20Modes::Blake2s(Blake2sGenOpts::Invalid(_)) => "blake2s_invalid",
+        }
+    }
+
+    fn parse(&self, input: &[u8]) -> Option<Self::Intermediate> {
+        let preimage = input.get(1..)?.to_vec();
+        match *input.first()? {
+            SHA_3_256_SELECTOR => Some(CIP20Modes::Sha3_256(preimage)),
+            SHA_3_512_SELECTOR => Some(CIP20Modes::Sha3_512(preimage)),
+            KECCAK_512_SELECTOR => Some(CIP20Modes::Keccak512(preimage)),
+            SHA_2_512_SELECTOR => Some(CIP20Modes::SHA2_512(preimage)),
+            _ => Blake2sArgs::parse(input)
+                .map(|args| CIP20Modes::Blake2s(Blake2sGenOpts::Valid(args))),
+        }
+    }
+
+    fn invariants(
+        &self,
+        input: &Self::Intermediate,
+        results: &[CommunicationResult<Vec<u8>>],
+    ) -> Vec<InvariantViolation> {
+        let len = match input {
+            // random bytes may happen to be a valid call
+            CIP20Modes::Invalid(_) => return vec![],
+            CIP20Modes::Blake2s(Blake2sGenOpts::Invalid(_)) => {
+                return invariants::rejected(results)
+            }
+            CIP20Modes::Sha3_256(_) => 32,
+            CIP20Modes::Sha3_512(_) | CIP20Modes::Keccak512(_) | CIP20Modes::SHA2_512(_) => 64,
+            CIP20Modes::Blake2s(Blake2sGenOpts::Valid(args)) => args.hash_length as usize,
+        };
+        let mut violations = invariants::accepted(results);
+        violations.extend(invariants::output_length(results, len));
+        violations
+    }
+
     fn run_raw(
         &mut self,
         context: &mut ThreadContext,
@@ -140,3 +191,134 @@ impl TargetWithControl for Cip20Precompile {
         }
     }
 }
+
+impl Metamorphic for Cip20Precompile {
+    /// Fixed-parameter hashes follow up with the preimage under every hash
+    /// mode, in selector order. Blake2s follows up with the hash length moved
+    /// by one, the key toggled between empty and 32 zero bytes, and one bit of
+    /// the personalization flipped.
+    ///
+    /// There is no tree-mode relation. A BLAKE2 tree finalizes its last leaf
+    /// and its root with the last-node flag, which CIP-20 does not take as a
+    /// parameter. And the root's preimage is the leaves' outputs, while
+    /// follow-ups are derived from the base input alone, before any call.
+    fn follow_ups(&self, base: &Self::Intermediate) -> Vec<Self::Intermediate> {
+        match base {
+            CIP20Modes::Sha3_256(preimage)
+            | CIP20Modes::Sha3_512(preimage)
+            | CIP20Modes::Keccak512(preimage)
+            | CIP20Modes::SHA2_512(preimage) => vec![
+                CIP20Modes::Sha3_256(preimage.clone()),
+                CIP20Modes::Sha3_512(preimage.clone()),
+                CIP20Modes::Keccak512(preimage.clone()),
+                CIP20Modes::SHA2_512(preimage.clone()),
+            ],
+            CIP20Modes::Blake2s(Blake2sGenOpts::Valid(args)) => {
+                let mut length = args.clone();
+                length.hash_length = if args.hash_length < 32 {
+                    args.hash_length + 1
+                } else {
+                    args.hash_length - 1
+                };
+                let mut keyed = args.clone();
+                keyed.key = if args.key.is_empty() {
+                    vec![0; 32]
+                } else {
+                    vec![]
+                };
+                let mut personal = args.clone();
+                personal.personalization[0] ^= 1;
+                vec![
+                    CIP20Modes::Blake2s(Blake2sGenOpts::Valid(length)),
+                    CIP20Modes::Blake2s(Blake2sGenOpts::Valid(keyed)),
+                    CIP20Modes::Blake2s(Blake2sGenOpts::Valid(personal)),
+                ]
+            }
+            _ => vec![],
+        }
+    }
+
+    fn relations(
+        &self,
+        base: &Self::Intermediate,
+        results: &[Vec<CommunicationResult<Vec<u8>>>],
+    ) -> Vec<InvariantViolation> {
+        let mut violations = vec![];
+        // invalid inputs have no follow-ups
+        if results.len() < 2 {
+            return violations;
+        }
+        for executor in 0..results[0].len() {
+            // relations only hold between successful calls
+            let outputs: Option<Vec<&Vec<u8>>> = results
+                .iter()
+                .map(|r| r.get(executor)?.as_ref().ok())
+                .collect();
+            let outputs = match outputs {
+                Some(outputs) => outputs,
+                None => continue,
+            };
+
+            match base {
+                CIP20Modes::Blake2s(Blake2sGenOpts::Valid(_)) => {
+                    let (shorter, longer) = if outputs[0].len() < outputs[1].len() {
+                        (outputs[0], outputs[1])
+                    } else {
+                        (outputs[1], outputs[0])
+                    };
+                    if longer.starts_with(shorter) {
+                        violations.push(InvariantViolation::new(
+                            "hash_length_prefix",
+                            format!(
+                                "{} is a prefix of {}",
+                                hex::encode(shorter),
+                                hex::encode(longer)
+                            ),
+                        ));
+                    }
+                    if outputs[2] == outputs[0] {
+                        violations.push(InvariantViolation::new(
+                            "key_separation",
+                            "keyed and unkeyed hashes are equal",
+                        ));
+                    }
+                    if outputs[3] == outputs[0] {
+                        violations.push(InvariantViolation::new(
+                            "personalization_separation",
+                            "hashes with different personalization are equal",
+                        ));
+                    }
+                }
+                _ => {
+                    let selector = match base {
+                        CIP20Modes::Sha3_256(_) => SHA_3_256_SELECTOR,
+                        CIP20Modes::Sha3_512(_) => SHA_3_512_SELECTOR,
+                        CIP20Modes::Keccak512(_) => KECCAK_512_SELECTOR,
+                        _ => SHA_2_512_SELECTOR,
+                    };
+                    if outputs[0] != outputs[1 + selector as usize] {
+                        violations.push(InvariantViolation::new(
+                            "deterministic",
+                            "repeated call returned a different hash",
+                        ));
+                    }
+                    for a in 1..outputs.len() {
+                        for b in a + 1..outputs.len() {
+                            if outputs[a] == outputs[b] {
+                                violations.push(InvariantViolation::new(
+                                    "mode_separation",
+                                    format!(
+                                        "selectors {} and {} returned equal hashes",
+                                        a - 1,
+                                        b - 1
+                                    ),
+                                ));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        violations
+    }
+}
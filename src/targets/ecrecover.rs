@@ -0,0 +1,350 @@
+use lain::{byteorder::ByteOrder, prelude::*};
+use libsecp256k1::{Message, PublicKey, RecoveryId, SecretKey, Signature};
+use sha3::Digest;
+use std::io::Write;
+
+use crate::{
+    errors::{CommunicationResult, InvariantViolation},
+    invariants,
+    registry::Registration,
+    traits::{ProduceInvalid, Target, TargetWithControl, ThreadContext},
+};
+
+inventory::submit! {
+    Registration::produce_invalid_with_control::<EcrecoverPrecompile>()
+}
+
+/// The input length. Shorter inputs are right-padded with zeros, and longer
+/// inputs truncated.
+pub const INPUT_LENGTH: usize = 128;
+
+/// The order of the secp256k1 group, big-endian.
+const ORDER: [u8; 32] = [
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe,
+    0xba, 0xae, 0xdc, 0xe6, 0xaf, 0x48, 0xa0, 0x3b, 0xbf, 0xd2, 0x5e, 0x8c, 0xd0, 0x36, 0x41, 0x41,
+];
+
+/// Half the group order, rounded down. Signatures with a larger s are high-s.
+const HALF_ORDER: [u8; 32] = [
+    0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0x5d, 0x57, 0x6e, 0x73, 0x57, 0xa4, 0x50, 0x1d, 0xdf, 0xe9, 0x2f, 0x46, 0x68, 0x1b, 0x20, 0xa0,
+];
+
+// Update whenever a new invalid kind is added
+const INVALID_KIND_COUNT: u8 = 4;
+
+/// The kind of an ecrecover input.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum EcrecoverKind {
+    /// A signature by a random key
+    Valid,
+    /// A valid signature with s replaced by `n - s`, and v flipped to match
+    HighS,
+    /// A v outside 27 and 28
+    BadV,
+    /// An r or s of zero, or at least the group order
+    BadScalar,
+    /// Non-zero bytes in the 31 bytes of padding before v
+    BadPadding,
+    /// An input shorter than 128 bytes
+    Short,
+}
+
+impl EcrecoverKind {
+    /// True if the precompile must return an empty output for the kind.
+    pub fn is_rejected(&self) -> bool {
+        matches!(
+            self,
+            EcrecoverKind::BadV | EcrecoverKind::BadScalar | EcrecoverKind::BadPadding
+        )
+    }
+}
+
+/// An ecrecover input: the message hash, the v word, and the signature.
+#[derive(Debug, Clone)]
+pub struct EcrecoverArgs {
+    pub kind: EcrecoverKind,
+    pub hash: [u8; 32],
+    pub v: [u8; 32],
+    pub r: [u8; 32],
+    pub s: [u8; 32],
+    /// The serialized length, at most 128. Shorter inputs are truncated.
+    pub len: usize,
+}
+
+impl EcrecoverArgs {
+    /// Sign a random hash with a random key.
+    pub fn sign<R: Rng>(mutator: &mut Mutator<R>) -> Self {
+        let key = loop {
+            let bytes: [u8; 32] = mutator.gen();
+            if let Ok(key) = SecretKey::parse(&bytes) {
+                break key;
+            }
+        };
+        let hash: [u8; 32] = mutator.gen();
+        let (signature, recovery_id) = libsecp256k1::sign(&Message::parse(&hash), &key);
+
+        let mut v = [0u8; 32];
+        v[31] = 27 + recovery_id.serialize();
+        Self {
+            kind: EcrecoverKind::Valid,
+            hash,
+            v,
+            r: signature.r.b32(),
+            s: signature.s.b32(),
+            len: INPUT_LENGTH,
+        }
+    }
+
+    /// Classify a serialized input of at most 128 bytes. Returns `None` for
+    /// longer inputs, as the precompile ignores their trailing bytes.
+    pub fn parse(input: &[u8]) -> Option<Self> {
+        if input.len() > INPUT_LENGTH {
+            return None;
+        }
+        let mut buf = [0u8; INPUT_LENGTH];
+        buf[..input.len()].copy_from_slice(input);
+
+        let mut args = Self {
+            kind: EcrecoverKind::Valid,
+            hash: [0; 32],
+            v: [0; 32],
+            r: [0; 32],
+            s: [0; 32],
+            len: input.len(),
+        };
+        args.hash.copy_from_slice(&buf[..32]);
+        args.v.copy_from_slice(&buf[32..64]);
+        args.r.copy_from_slice(&buf[64..96]);
+        args.s.copy_from_slice(&buf[96..]);
+        args.kind = args.classify();
+        Some(args)
+    }
+
+    fn classify(&self) -> EcrecoverKind {
+        let out_of_range = |x: &[u8; 32]| *x == [0; 32] || *x >= ORDER;
+        if self.len < INPUT_LENGTH {
+            EcrecoverKind::Short
+        } else if self.v[..31].iter().any(|b| *b != 0) {
+            EcrecoverKind::BadPadding
+        } else if self.v[31] != 27 && self.v[31] != 28 {
+            EcrecoverKind::BadV
+        } else if out_of_range(&self.r) || out_of_range(&self.s) {
+            EcrecoverKind::BadScalar
+        } else if self.s > HALF_ORDER {
+            EcrecoverKind::HighS
+        } else {
+            EcrecoverKind::Valid
+        }
+    }
+
+    fn signature(&self) -> [u8; 64] {
+        let mut signature = [0u8; 64];
+        signature[..32].copy_from_slice(&self.r);
+        signature[32..].copy_from_slice(&self.s);
+        signature
+    }
+
+    /// Run ecrecover on the input. Returns the signer's address left-padded
+    /// to 32 bytes, or an empty output if the input is rejected or no key
+    /// recovers.
+    pub fn run(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        self.binary_serialize::<_, lain::byteorder::BigEndian>(&mut buf);
+        ecrecover(&buf)
+    }
+}
+
+impl BinarySerialize for EcrecoverArgs {
+    fn binary_serialize<W: Write, E: ByteOrder>(&self, buf: &mut W) -> usize {
+        let mut out = Vec::with_capacity(INPUT_LENGTH);
+        out.extend_from_slice(&self.hash);
+        out.extend_from_slice(&self.v);
+        out.extend_from_slice(&self.r);
+        out.extend_from_slice(&self.s);
+        out.truncate(self.len);
+        buf.write_all(&out).unwrap();
+        out.len()
+    }
+}
+
+impl NewFuzzed for EcrecoverArgs {
+    type RangeType = u8;
+
+    /// Generate a valid signature, half the time with a high s.
+    fn new_fuzzed<R: Rng>(
+        mutator: &mut Mutator<R>,
+        _constraints: Option<&Constraints<Self::RangeType>>,
+    ) -> Self {
+        let mut args = Self::sign(mutator);
+        if mutator.gen_chance(0.5) {
+            let mut signature = Signature::parse_overflowing(&args.signature());
+            signature.s = -signature.s;
+            args.s = signature.s.b32();
+            args.v[31] = if args.v[31] == 27 { 28 } else { 27 };
+            args.kind = EcrecoverKind::HighS;
+        }
+        args
+    }
+}
+
+/// The go-ethereum ecrecover precompile. Inputs are right-padded to 128
+/// bytes. Any invalid v, r or s, or a signature that recovers no key, gives an
+/// empty output rather than an error.
+pub fn ecrecover(input: &[u8]) -> Vec<u8> {
+    let mut buf = [0u8; INPUT_LENGTH];
+    let len = input.len().min(INPUT_LENGTH);
+    buf[..len].copy_from_slice(&input[..len]);
+
+    if buf[32..63].iter().any(|b| *b != 0) {
+        return vec![];
+    }
+    let v = buf[63].wrapping_sub(27);
+    if v > 1 {
+        return vec![];
+    }
+    let recovery_id = RecoveryId::parse(v).expect("v is 0 or 1");
+    let mut signature = [0u8; 64];
+    signature.copy_from_slice(&buf[64..]);
+    let signature = match Signature::parse_standard(&signature) {
+        Ok(signature) => signature,
+        Err(_) => return vec![],
+    };
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(&buf[..32]);
+
+    match libsecp256k1::recover(&Message::parse(&hash), &signature, &recovery_id) {
+        Ok(key) => address(&key),
+        Err(_) => vec![],
+    }
+}
+
+/// The address of a key, left-padded to 32 bytes.
+fn address(key: &PublicKey) -> Vec<u8> {
+    let hash = sha3::Keccak256::digest(&key.serialize()[1..]);
+    let mut out = vec![0u8; 12];
+    out.extend_from_slice(&hash[12..]);
+    out
+}
+
+#[derive(Debug, Default)]
+pub struct EcrecoverPrecompile;
+
+impl Target for EcrecoverPrecompile {
+    type Intermediate = EcrecoverArgs;
+    type Rng = lain::rand::rngs::StdRng;
+    type Config = ();
+
+    fn name() -> &'static str {
+        "ecrecover"
+    }
+
+    fn variant(&self, input: &Self::Intermediate) -> &'static str {
+        match input.kind {
+            EcrecoverKind::Valid => "valid",
+            EcrecoverKind::HighS => "high_s",
+            EcrecoverKind::BadV => "bad_v",
+            EcrecoverKind::BadScalar => "bad_scalar",
+            EcrecoverKind::BadPadding => "bad_padding",
+            EcrecoverKind::Short => "short",
+        }
+    }
+
+    fn testdata() -> &'static [&'static str] {
+        &["ecRecover.json"]
+    }
+
+    fn parse(&self, input: &[u8]) -> Option<Self::Intermediate> {
+        EcrecoverArgs::parse(input)
+    }
+
+    fn invariants(
+        &self,
+        input: &Self::Intermediate,
+        results: &[CommunicationResult<Vec<u8>>],
+    ) -> Vec<InvariantViolation> {
+        // ecrecover signals failure with an empty output, never an error
+        let mut violations = invariants::accepted(results);
+        for output in results.iter().filter_map(|r| r.as_ref().ok()) {
+            if input.kind.is_rejected() && !output.is_empty() {
+                violations.push(InvariantViolation::new(
+                    "rejected",
+                    format!("returned {}", hex::encode(output)),
+                ));
+            } else if !output.is_empty() && (output.len() != 32 || output[..12] != [0; 12]) {
+                violations.push(InvariantViolation::new(
+                    "address",
+                    format!("returned {}", hex::encode(output)),
+                ));
+            }
+        }
+        violations
+    }
+
+    fn run_raw(
+        &mut self,
+        ctx: &mut ThreadContext,
+        input: &[u8],
+    ) -> Vec<CommunicationResult<Vec<u8>>> {
+        vec![
+            ctx.geth.run_precompile(1u8, input),
+            ctx.celo.run_precompile(1u8, input),
+        ]
+    }
+}
+
+impl ProduceInvalid for EcrecoverPrecompile {
+    /// Corrupt a valid signature.
+    fn generate_invalid(&self, mutator: &mut Mutator<Self::Rng>) -> Self::Intermediate {
+        let mut args = EcrecoverArgs::sign(mutator);
+        let choice: u8 = mutator.gen_range(0, INVALID_KIND_COUNT);
+
+        match choice {
+            0 => {
+                args.kind = EcrecoverKind::BadV;
+                args.v[31] = loop {
+                    let v: u8 = mutator.gen();
+                    if v != 27 && v != 28 {
+                        break v;
+                    }
+                };
+            }
+            1 => {
+                args.kind = EcrecoverKind::BadScalar;
+                let scalar = match mutator.gen_range(0u8, 4) {
+                    0 => [0; 32],
+                    1 => ORDER,
+                    2 => {
+                        // the low byte of the order has room for this
+                        let mut scalar = ORDER;
+                        scalar[31] += mutator.gen_range(1u8, 0x80);
+                        scalar
+                    }
+                    _ => [0xff; 32],
+                };
+                if mutator.gen_chance(0.5) {
+                    args.r = scalar;
+                } else {
+                    args.s = scalar;
+                }
+            }
+            2 => {
+                args.kind = EcrecoverKind::BadPadding;
+                let i = mutator.gen_range(0, 31);
+                args.v[i] = mutator.gen_range(1u16, 0x100) as u8;
+            }
+            3 => {
+                args.kind = EcrecoverKind::Short;
+                args.len = mutator.gen_range(0, INPUT_LENGTH);
+            }
+            _ => panic!("unreachable"),
+        }
+        args
+    }
+}
+
+impl TargetWithControl for EcrecoverPrecompile {
+    fn run_control(&self, input: &Self::Intermediate) -> Result<Vec<u8>, String> {
+        Ok(input.run())
+    }
+}
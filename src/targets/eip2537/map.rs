@@ -0,0 +1,245 @@
+use ark_bls12_381::{g1, g2};
+use ark_ec::{
+    hashing::{
+        curve_maps::wb::{WBConfig, WBMap},
+        map_to_curve_hasher::MapToCurve,
+    },
+    AffineRepr,
+};
+use ark_ff::{BigInt, PrimeField};
+use lain::{byteorder::ByteOrder, prelude::*};
+use std::{io::Write, marker::PhantomData};
+
+use super::{
+    add_modulus, check_padding, display_point, encode_fp, encode_point, gen_prime, point_kind,
+    Group, PointKind, Precompile, FIELD_LENGTH, FIELD_PADDING, INVALID_LENGTH, INVALID_TOP_BYTES,
+};
+use crate::{
+    errors::{CommunicationResult, InvariantViolation},
+    invariants,
+    registry::Registration,
+    traits::{ProduceInvalid, Target, TargetWithControl, ThreadContext},
+};
+
+pub type Bls12381MapFpToG1Precompile = MapPrecompile<g1::Config>;
+pub type Bls12381MapFp2ToG2Precompile = MapPrecompile<g2::Config>;
+
+inventory::submit! {
+    Registration::produce_invalid_with_control::<Bls12381MapFpToG1Precompile>()
+}
+
+inventory::submit! {
+    Registration::produce_invalid_with_control::<Bls12381MapFp2ToG2Precompile>()
+}
+
+/// A group with a precompile mapping field elements to it.
+pub trait Map: Group + WBConfig {
+    const MAP: Precompile;
+}
+
+impl Map for g1::Config {
+    const MAP: Precompile = Precompile {
+        name: "eip2537_map_fp_to_g1",
+        geth: Some(0x11),
+        celo: None,
+        gas: 5500,
+        testdata: &["blsMapG1.json"],
+    };
+}
+
+impl Map for g2::Config {
+    const MAP: Precompile = Precompile {
+        name: "eip2537_map_fp2_to_g2",
+        geth: Some(0x12),
+        celo: None,
+        gas: 110000,
+        testdata: &["blsMapG2.json"],
+    };
+}
+
+/// An encoded field element: mostly random, sometimes 0, 1 or -1.
+fn gen_element<F: PrimeField<BigInt = BigInt<6>>, R: Rng>(mutator: &mut Mutator<R>) -> Vec<u8> {
+    match mutator.gen_range(0u8, 10) {
+        0 => encode_fp(&F::zero()),
+        1 => encode_fp(&F::one()),
+        2 => encode_fp(&-F::one()),
+        _ => encode_fp(&gen_prime::<F, R>(mutator)),
+    }
+}
+
+/// A coordinate to map to the group, as field elements.
+#[derive(Clone)]
+pub struct MapArgs<C> {
+    pub coordinate: Vec<u8>,
+    /// The serialized length. Shorter inputs are truncated, and longer ones
+    /// extended with zeros.
+    pub len: usize,
+    group: PhantomData<fn() -> C>,
+}
+
+impl<C: Group> MapArgs<C> {
+    /// The input length, a coordinate.
+    pub const INPUT_LENGTH: usize = C::DEGREE * FIELD_LENGTH;
+
+    /// Parse a serialized input of any length. Missing bytes read as zero.
+    pub fn parse(input: &[u8]) -> Self {
+        let mut coordinate = input.to_vec();
+        coordinate.resize(Self::INPUT_LENGTH, 0);
+        Self {
+            coordinate,
+            len: input.len(),
+            group: PhantomData,
+        }
+    }
+
+    /// Valid if the coordinate decodes, or why it does not.
+    pub fn kind(&self) -> PointKind {
+        match check_padding(&self.coordinate).and_then(|_| C::decode_coordinate(&self.coordinate)) {
+            Ok(_) => PointKind::Valid,
+            Err(e) if e == INVALID_TOP_BYTES => PointKind::BadPadding,
+            Err(_) => PointKind::OutOfField,
+        }
+    }
+}
+
+impl<C: Map> MapArgs<C> {
+    /// Map the coordinate to the curve and clear the cofactor, with
+    /// go-ethereum's errors.
+    pub fn run(&self) -> Result<Vec<u8>, String> {
+        if self.len != Self::INPUT_LENGTH {
+            return Err(INVALID_LENGTH.to_owned());
+        }
+        check_padding(&self.coordinate)?;
+        let coordinate = C::decode_coordinate(&self.coordinate)?;
+        let point = WBMap::<C>::new()
+            .expect("the map's constants are valid")
+            .map_to_curve(coordinate)
+            .expect("every coordinate maps");
+        Ok(encode_point(&point.clear_cofactor()))
+    }
+}
+
+impl<C: Group> BinarySerialize for MapArgs<C> {
+    fn binary_serialize<W: Write, E: ByteOrder>(&self, buf: &mut W) -> usize {
+        let mut out = self.coordinate.clone();
+        out.resize(self.len, 0);
+        buf.write_all(&out).unwrap();
+        out.len()
+    }
+}
+
+impl<C: Group> NewFuzzed for MapArgs<C> {
+    type RangeType = u8;
+
+    /// Generate a coordinate of field elements in the field.
+    fn new_fuzzed<R: Rng>(
+        mutator: &mut Mutator<R>,
+        _constraints: Option<&Constraints<Self::RangeType>>,
+    ) -> Self {
+        Self {
+            coordinate: (0..C::DEGREE)
+                .flat_map(|_| gen_element::<C::Fp, R>(mutator))
+                .collect(),
+            len: Self::INPUT_LENGTH,
+            group: PhantomData,
+        }
+    }
+}
+
+/// EIP-2537 MAP_FP_TO_G1 or MAP_FP2_TO_G2.
+pub struct MapPrecompile<C>(PhantomData<fn() -> C>);
+
+impl<C> Default for MapPrecompile<C> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<C: Map> Target for MapPrecompile<C> {
+    type Intermediate = MapArgs<C>;
+    type Rng = lain::rand::rngs::StdRng;
+    type Config = ();
+
+    fn name() -> &'static str {
+        C::MAP.name
+    }
+
+    fn variant(&self, input: &Self::Intermediate) -> &'static str {
+        if input.len != MapArgs::<C>::INPUT_LENGTH {
+            "bad_length"
+        } else {
+            input.kind().name()
+        }
+    }
+
+    fn testdata() -> &'static [&'static str] {
+        C::MAP.testdata
+    }
+
+    fn parse(&self, input: &[u8]) -> Option<Self::Intermediate> {
+        Some(MapArgs::parse(input))
+    }
+
+    fn invariants(
+        &self,
+        input: &Self::Intermediate,
+        results: &[CommunicationResult<Vec<u8>>],
+    ) -> Vec<InvariantViolation> {
+        if input.len != MapArgs::<C>::INPUT_LENGTH || input.kind() != PointKind::Valid {
+            return invariants::rejected(results);
+        }
+        let mut violations = invariants::accepted(results);
+        violations.extend(invariants::output_length(results, C::POINT_LENGTH));
+        violations.extend(
+            results
+                .iter()
+                .filter_map(|r| r.as_ref().ok())
+                .filter(|output| output.len() == C::POINT_LENGTH)
+                .filter(|output| !point_kind::<C>(output).is_in_subgroup())
+                .map(|output| InvariantViolation::new("subgroup", display_point::<C>(output))),
+        );
+        violations
+    }
+
+    fn expected_gas(&self, _input: &Self::Intermediate) -> Option<u64> {
+        Some(C::MAP.gas)
+    }
+
+    fn display_output(&self, output: &[u8]) -> String {
+        display_point::<C>(output)
+    }
+
+    fn run_raw(
+        &mut self,
+        ctx: &mut ThreadContext,
+        input: &[u8],
+    ) -> Vec<CommunicationResult<Vec<u8>>> {
+        C::MAP.run(ctx, input)
+    }
+}
+
+impl<C: Map> ProduceInvalid for MapPrecompile<C> {
+    /// Set non-zero padding, push an element past the modulus, or truncate or
+    /// extend the input.
+    fn generate_invalid(&self, mutator: &mut Mutator<Self::Rng>) -> Self::Intermediate {
+        let mut args = self.generate(mutator);
+        let len = MapArgs::<C>::INPUT_LENGTH;
+        let element = mutator.gen_range(0, C::DEGREE) * FIELD_LENGTH;
+        match mutator.gen_range(0u8, 4) {
+            0 => {
+                let at = element + mutator.gen_range(0, FIELD_PADDING);
+                args.coordinate[at] = mutator.gen_range(1u16, 0x100) as u8;
+            }
+            1 => add_modulus::<C::Fp>(&mut args.coordinate[element..element + FIELD_LENGTH]),
+            2 => args.len = mutator.gen_range(0, len),
+            _ => args.len = mutator.gen_range(len + 1, len + 64),
+        }
+        args
+    }
+}
+
+impl<C: Map> TargetWithControl for MapPrecompile<C> {
+    fn run_control(&self, input: &Self::Intermediate) -> Result<Vec<u8>, String> {
+        input.run()
+    }
+}
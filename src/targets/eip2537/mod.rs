@@ -0,0 +1,454 @@
+use ark_bls12_381::{g1, g2, Fq, Fq2};
+use ark_ec::{
+    short_weierstrass::{Affine, SWCurveConfig},
+    AffineRepr, CurveGroup,
+};
+use ark_ff::{BigInt, BigInteger, One, PrimeField, Zero};
+use lain::prelude::*;
+
+use crate::{errors::CommunicationResult, traits::ThreadContext};
+
+pub mod map;
+pub mod ops;
+pub mod pairing;
+
+pub use map::{Bls12381MapFp2ToG2Precompile, Bls12381MapFpToG1Precompile, Map};
+pub use ops::{
+    Bls12381G1AddPrecompile, Bls12381G1MulPrecompile, Bls12381G1MultiExpPrecompile,
+    Bls12381G2AddPrecompile, Bls12381G2MulPrecompile, Bls12381G2MultiExpPrecompile,
+};
+pub use pairing::{Bls12381PairingPrecompile, Engine, PairingPrecompile};
+
+/// The length of an encoded field element: 16 zero bytes, then 48 bytes
+/// big-endian.
+pub const FIELD_LENGTH: usize = 64;
+/// The zero bytes padding a field element.
+pub const FIELD_PADDING: usize = 16;
+/// The length of a scalar, big-endian and not reduced by the group order.
+pub const SCALAR_LENGTH: usize = 32;
+
+/// go-ethereum's error for an input of the wrong length.
+pub const INVALID_LENGTH: &str = "invalid input length";
+/// go-ethereum's error for non-zero padding before a field element.
+pub const INVALID_TOP_BYTES: &str = "invalid field element top bytes";
+/// go-ethereum's error for a field element at or above the modulus.
+pub const EXCEEDS_MODULUS: &str = "must be less than modulus";
+/// go-ethereum's error for a point off the curve.
+pub const NOT_ON_CURVE: &str = "point is not on curve";
+
+/// Multiexp discounts, per mille, by number of pairs. Inputs with more pairs
+/// than the table get the last discount.
+pub const MULTIEXP_DISCOUNT: [u64; 128] = [
+    1200, 888, 764, 641, 594, 547, 500, 453, 438, 423, 408, 394, 379, 364, 349, 334, 330, 326, 322,
+    318, 314, 310, 306, 302, 298, 294, 289, 285, 281, 277, 273, 269, 268, 266, 265, 263, 262, 260,
+    259, 257, 256, 254, 253, 251, 250, 248, 247, 245, 244, 242, 241, 239, 238, 236, 235, 233, 232,
+    231, 229, 228, 226, 225, 223, 222, 221, 220, 219, 219, 218, 217, 216, 216, 215, 214, 213, 213,
+    212, 211, 211, 210, 209, 208, 208, 207, 206, 205, 205, 204, 203, 202, 202, 201, 200, 199, 199,
+    198, 197, 196, 196, 195, 194, 193, 193, 192, 191, 191, 190, 189, 188, 188, 187, 186, 185, 185,
+    184, 183, 182, 182, 181, 180, 179, 179, 178, 177, 176, 176, 175, 174,
+];
+
+/// The gas for a multiexp of `pairs` pairs, from the single multiplication
+/// gas.
+pub fn multiexp_gas(pairs: usize, mul_gas: u64) -> u64 {
+    if pairs == 0 {
+        return 0;
+    }
+    let discount = MULTIEXP_DISCOUNT[(pairs - 1).min(MULTIEXP_DISCOUNT.len() - 1)];
+    pairs as u64 * mul_gas * discount / 1000
+}
+
+/// A precompile's addresses in each executor, and its gas.
+#[derive(Debug, Clone, Copy)]
+pub struct Precompile {
+    pub name: &'static str,
+    /// The address in go-ethereum's YoloV2 set, if go-ethereum has it
+    pub geth: Option<u8>,
+    /// The address in celo-blockchain, if the pinned celo build has it
+    // TODO: the pinned build, prestwich/celo-blockchain cip20-alpha4, has no
+    // BLS12-381 precompiles, so every target here is `None` and runs in geth
+    // alone. celo-blockchain adds them with CIP-30, expected in its Donut
+    // release. Once `call_celo` is bumped to it, set G1 add, mul and
+    // multiexp to 0xf2, 0xf1, 0xf0, G2 to 0xef, 0xee, 0xed, pairing to 0xec
+    // and the maps to 0xeb and 0xea, after checking them against that build.
+    pub celo: Option<u8>,
+    pub gas: u64,
+    /// Files of vectors in go-ethereum's testdata
+    pub testdata: &'static [&'static str],
+}
+
+impl Precompile {
+    /// Run an input in each executor that has the precompile.
+    pub(crate) fn run(
+        &self,
+        ctx: &mut ThreadContext,
+        input: &[u8],
+    ) -> Vec<CommunicationResult<Vec<u8>>> {
+        let mut results = vec![];
+        if let Some(address) = self.geth {
+            results.push(ctx.geth.run_precompile(address, input));
+        }
+        if let Some(address) = self.celo {
+            results.push(ctx.celo.run_precompile(address, input));
+        }
+        results
+    }
+}
+
+/// G1 or G2, with the encoding of its coordinates and its precompiles.
+pub trait Group: SWCurveConfig {
+    /// The prime field the coordinates are made of.
+    type Fp: PrimeField<BigInt = BigInt<6>>;
+
+    /// A short name for statistics.
+    const NAME: &'static str;
+    /// Field elements per coordinate, 1 for G1 and 2 for G2.
+    const DEGREE: usize;
+    /// The encoded length of a point.
+    const POINT_LENGTH: usize = 2 * Self::DEGREE * FIELD_LENGTH;
+    /// go-ethereum's error for a point outside the subgroup, in a pairing.
+    const SUBGROUP_ERROR: &'static str;
+
+    const ADD: Precompile;
+    const MUL: Precompile;
+    const MULTIEXP: Precompile;
+
+    /// Decode a coordinate whose padding has been checked.
+    fn decode_coordinate(bytes: &[u8]) -> Result<Self::BaseField, String>;
+
+    /// Encode a coordinate, with padding.
+    fn encode_coordinate(coordinate: &Self::BaseField) -> Vec<u8>;
+
+    /// A uniformly random coordinate.
+    fn random_coordinate<R: Rng>(mutator: &mut Mutator<R>) -> Self::BaseField;
+}
+
+impl Group for g1::Config {
+    type Fp = Fq;
+
+    const NAME: &'static str = "g1";
+    const DEGREE: usize = 1;
+    const SUBGROUP_ERROR: &'static str = "g1 point is not on correct subgroup";
+
+    const ADD: Precompile = Precompile {
+        name: "eip2537_g1_add",
+        geth: Some(0x0a),
+        celo: None,
+        gas: 600,
+        testdata: &["blsG1Add.json"],
+    };
+    const MUL: Precompile = Precompile {
+        name: "eip2537_g1_mul",
+        geth: Some(0x0b),
+        celo: None,
+        gas: 12000,
+        testdata: &["blsG1Mul.json"],
+    };
+    const MULTIEXP: Precompile = Precompile {
+        name: "eip2537_g1_multiexp",
+        geth: Some(0x0c),
+        celo: None,
+        gas: 12000,
+        testdata: &["blsG1MultiExp.json"],
+    };
+
+    fn decode_coordinate(bytes: &[u8]) -> Result<Fq, String> {
+        decode_fp(bytes)
+    }
+
+    fn encode_coordinate(coordinate: &Fq) -> Vec<u8> {
+        encode_fp(coordinate)
+    }
+
+    fn random_coordinate<R: Rng>(mutator: &mut Mutator<R>) -> Fq {
+        gen_prime(mutator)
+    }
+}
+
+impl Group for g2::Config {
+    type Fp = Fq;
+
+    const NAME: &'static str = "g2";
+    const DEGREE: usize = 2;
+    const SUBGROUP_ERROR: &'static str = "g2 point is not on correct subgroup";
+
+    const ADD: Precompile = Precompile {
+        name: "eip2537_g2_add",
+        geth: Some(0x0d),
+        celo: None,
+        gas: 4500,
+        testdata: &["blsG2Add.json"],
+    };
+    const MUL: Precompile = Precompile {
+        name: "eip2537_g2_mul",
+        geth: Some(0x0e),
+        celo: None,
+        gas: 55000,
+        testdata: &["blsG2Mul.json"],
+    };
+    const MULTIEXP: Precompile = Precompile {
+        name: "eip2537_g2_multiexp",
+        geth: Some(0x0f),
+        celo: None,
+        gas: 55000,
+        testdata: &["blsG2MultiExp.json"],
+    };
+
+    /// The real part comes first.
+    fn decode_coordinate(bytes: &[u8]) -> Result<Fq2, String> {
+        let c0 = decode_fp(&bytes[..FIELD_LENGTH])?;
+        let c1 = decode_fp(&bytes[FIELD_LENGTH..])?;
+        Ok(Fq2::new(c0, c1))
+    }
+
+    fn encode_coordinate(coordinate: &Fq2) -> Vec<u8> {
+        let mut out = encode_fp(&coordinate.c0);
+        out.extend_from_slice(&encode_fp(&coordinate.c1));
+        out
+    }
+
+    fn random_coordinate<R: Rng>(mutator: &mut Mutator<R>) -> Fq2 {
+        Fq2::new(gen_prime(mutator), gen_prime(mutator))
+    }
+}
+
+/// Decode a field element. The padding is not checked.
+pub(crate) fn decode_fp<F: PrimeField<BigInt = BigInt<6>>>(bytes: &[u8]) -> Result<F, String> {
+    let mut limbs = [0u64; 6];
+    for (limb, chunk) in limbs.iter_mut().zip(bytes[FIELD_PADDING..].rchunks(8)) {
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(chunk);
+        *limb = u64::from_be_bytes(buf);
+    }
+    F::from_bigint(BigInt(limbs)).ok_or_else(|| EXCEEDS_MODULUS.to_owned())
+}
+
+/// Encode a field element, with padding.
+pub(crate) fn encode_fp<F: PrimeField<BigInt = BigInt<6>>>(element: &F) -> Vec<u8> {
+    let mut out = vec![0u8; FIELD_PADDING];
+    out.extend_from_slice(&element.into_bigint().to_bytes_be());
+    out
+}
+
+/// Check the padding of every field element in an encoding.
+pub(crate) fn check_padding(bytes: &[u8]) -> Result<(), String> {
+    if bytes
+        .chunks(FIELD_LENGTH)
+        .all(|element| element[..FIELD_PADDING].iter().all(|b| *b == 0))
+    {
+        Ok(())
+    } else {
+        Err(INVALID_TOP_BYTES.to_owned())
+    }
+}
+
+/// A uniformly random element of a prime field.
+pub(crate) fn gen_prime<F: PrimeField, R: Rng>(mutator: &mut Mutator<R>) -> F {
+    let mut wide = [0u8; 64];
+    wide.iter_mut().for_each(|b| *b = mutator.gen());
+    F::from_be_bytes_mod_order(&wide)
+}
+
+/// Add the field modulus to an encoded field element. The sum of a reduced
+/// element and the modulus still fits in 48 bytes.
+pub(crate) fn add_modulus<F: PrimeField<BigInt = BigInt<6>>>(element: &mut [u8]) {
+    let modulus = F::MODULUS.to_bytes_be();
+    let mut carry = 0u16;
+    for (byte, m) in element[FIELD_PADDING..].iter_mut().zip(&modulus).rev() {
+        let sum = *byte as u16 + *m as u16 + carry;
+        *byte = sum as u8;
+        carry = sum >> 8;
+    }
+}
+
+/// The kind of an encoded point.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+pub enum PointKind {
+    /// A point in the subgroup
+    Valid,
+    /// The point at infinity, encoded as zeros
+    Infinity,
+    /// A point on the curve, outside the subgroup. Only pairings reject
+    /// these
+    OutsideSubgroup,
+    /// Coordinates in the field, off the curve
+    OffCurve,
+    /// A field element at or above the modulus
+    OutOfField,
+    /// Non-zero padding before a field element
+    BadPadding,
+}
+
+impl PointKind {
+    /// A short name for statistics.
+    pub fn name(&self) -> &'static str {
+        match self {
+            PointKind::Valid => "valid",
+            PointKind::Infinity => "infinity",
+            PointKind::OutsideSubgroup => "outside_subgroup",
+            PointKind::OffCurve => "off_curve",
+            PointKind::OutOfField => "out_of_field",
+            PointKind::BadPadding => "bad_padding",
+        }
+    }
+
+    /// True if the point decodes, i.e. is on the curve.
+    pub fn is_on_curve(&self) -> bool {
+        *self <= PointKind::OutsideSubgroup
+    }
+
+    /// True if the point is in the subgroup.
+    pub fn is_in_subgroup(&self) -> bool {
+        *self <= PointKind::Infinity
+    }
+}
+
+/// Decode a point, with go-ethereum's errors. Points outside the subgroup
+/// decode.
+pub fn decode_point<C: Group>(bytes: &[u8]) -> Result<Affine<C>, String> {
+    check_padding(bytes)?;
+    let half = C::POINT_LENGTH / 2;
+    let x = C::decode_coordinate(&bytes[..half])?;
+    let y = C::decode_coordinate(&bytes[half..])?;
+    if x.is_zero() && y.is_zero() {
+        return Ok(Affine::identity());
+    }
+    let point = Affine::new_unchecked(x, y);
+    if point.is_on_curve() {
+        Ok(point)
+    } else {
+        Err(NOT_ON_CURVE.to_owned())
+    }
+}
+
+/// Encode a point. Infinity encodes as zeros.
+pub fn encode_point<C: Group>(point: &Affine<C>) -> Vec<u8> {
+    match point.xy() {
+        Some((x, y)) => {
+            let mut out = C::encode_coordinate(x);
+            out.extend_from_slice(&C::encode_coordinate(y));
+            out
+        }
+        None => vec![0; C::POINT_LENGTH],
+    }
+}
+
+/// Classify an encoded point as go-ethereum reads it.
+pub fn point_kind<C: Group>(bytes: &[u8]) -> PointKind {
+    match decode_point::<C>(bytes) {
+        Ok(point) if point.is_zero() => PointKind::Infinity,
+        Ok(point) if point.is_in_correct_subgroup_assuming_on_curve() => PointKind::Valid,
+        Ok(_) => PointKind::OutsideSubgroup,
+        Err(e) if e == INVALID_TOP_BYTES => PointKind::BadPadding,
+        Err(e) if e == EXCEEDS_MODULUS => PointKind::OutOfField,
+        Err(_) => PointKind::OffCurve,
+    }
+}
+
+/// A random multiple of the generator.
+pub fn random_point<C: Group, R: Rng>(mutator: &mut Mutator<R>) -> Affine<C> {
+    (Affine::<C>::generator() * gen_prime::<C::ScalarField, R>(mutator)).into_affine()
+}
+
+/// A random encoded point in the subgroup, sometimes infinity.
+pub fn gen_point<C: Group, R: Rng>(mutator: &mut Mutator<R>) -> Vec<u8> {
+    if mutator.gen_chance(0.1) {
+        vec![0; C::POINT_LENGTH]
+    } else {
+        encode_point(&random_point::<C, R>(mutator))
+    }
+}
+
+/// A random point on the curve. The cofactors are large, so these are almost
+/// never in the subgroup.
+pub fn random_outside_subgroup<C: Group, R: Rng>(mutator: &mut Mutator<R>) -> Vec<u8> {
+    loop {
+        let x = C::random_coordinate(mutator);
+        if let Some(point) = Affine::<C>::get_point_from_x_unchecked(x, mutator.gen()) {
+            if !point.is_in_correct_subgroup_assuming_on_curve() {
+                return encode_point(&point);
+            }
+        }
+    }
+}
+
+/// A random encoded point that does not decode: off the curve, with a field
+/// element at or above the modulus, or with non-zero padding.
+pub fn random_invalid_point<C: Group, R: Rng>(mutator: &mut Mutator<R>) -> Vec<u8> {
+    let point = random_point::<C, R>(mutator);
+    let mut bytes = encode_point(&point);
+    let element = mutator.gen_range(0, 2 * C::DEGREE) * FIELD_LENGTH;
+    match mutator.gen_range(0u8, 3) {
+        // one off a valid y
+        0 => {
+            let (x, y) = point.xy().expect("random points are finite");
+            bytes = encode_point(&Affine::<C>::new_unchecked(*x, *y + C::BaseField::one()));
+        }
+        1 => add_modulus::<C::Fp>(&mut bytes[element..element + FIELD_LENGTH]),
+        _ => {
+            bytes[element + mutator.gen_range(0, FIELD_PADDING)] =
+                mutator.gen_range(1u16, 0x100) as u8
+        }
+    }
+    bytes
+}
+
+/// Render an encoded point, for comparison reports.
+pub fn display_point<C: Group>(output: &[u8]) -> String {
+    if output.len() != C::POINT_LENGTH {
+        return hex::encode(output);
+    }
+    if output.iter().all(|b| *b == 0) {
+        return "infinity".to_owned();
+    }
+    let half = C::POINT_LENGTH / 2;
+    format!(
+        "(x: 0x{}, y: 0x{}) {}",
+        hex::encode(&output[..half]),
+        hex::encode(&output[half..]),
+        point_kind::<C>(output).name()
+    )
+}
+
+/// A scalar, mostly random, sometimes at the edges of the group order.
+pub(crate) fn gen_scalar<C: Group, R: Rng>(mutator: &mut Mutator<R>) -> [u8; SCALAR_LENGTH] {
+    let order = {
+        let mut order = [0u8; SCALAR_LENGTH];
+        order.copy_from_slice(&C::ScalarField::MODULUS.to_bytes_be());
+        order
+    };
+    let mut scalar = [0u8; SCALAR_LENGTH];
+    match mutator.gen_range(0u8, 12) {
+        0 => {}
+        1 => scalar[31] = 1,
+        2 => {
+            scalar = order;
+            scalar[31] -= 1;
+        }
+        3 => scalar = order,
+        4 => {
+            // the low byte of the order has room for this
+            scalar = order;
+            scalar[31] += mutator.gen_range(1u8, 0xfe);
+        }
+        5 => scalar = [0xff; SCALAR_LENGTH],
+        6 | 7 => scalar.iter_mut().for_each(|b| *b = mutator.gen()),
+        _ => scalar.copy_from_slice(
+            &gen_prime::<C::ScalarField, R>(mutator)
+                .into_bigint()
+                .to_bytes_be(),
+        ),
+    }
+    scalar
+}
+
+/// The little-endian limbs of a big-endian scalar.
+pub(crate) fn scalar_limbs(scalar: &[u8]) -> [u64; 4] {
+    let mut limbs = [0u64; 4];
+    for (limb, chunk) in limbs.iter_mut().zip(scalar.rchunks(8)) {
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(chunk);
+        *limb = u64::from_be_bytes(buf);
+    }
+    limbs
+}
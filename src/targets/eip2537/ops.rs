@@ -0,0 +1,576 @@
+use ark_bls12_381::{g1, g2};
+use ark_ec::{short_weierstrass::Projective, AffineRepr, CurveGroup};
+use lain::{byteorder::ByteOrder, prelude::*};
+use std::{io::Write, marker::PhantomData};
+
+use super::{
+    decode_point, display_point, encode_point, gen_point, gen_scalar, multiexp_gas, point_kind,
+    random_invalid_point, random_outside_subgroup, scalar_limbs, Group, PointKind, INVALID_LENGTH,
+    SCALAR_LENGTH,
+};
+use crate::{
+    errors::{CommunicationResult, InvariantViolation},
+    invariants,
+    registry::Registration,
+    traits::{ProduceInvalid, Target, TargetWithControl, ThreadContext},
+};
+
+pub type Bls12381G1AddPrecompile = AddPrecompile<g1::Config>;
+pub type Bls12381G1MulPrecompile = MulPrecompile<g1::Config>;
+pub type Bls12381G1MultiExpPrecompile = MultiExpPrecompile<g1::Config>;
+pub type Bls12381G2AddPrecompile = AddPrecompile<g2::Config>;
+pub type Bls12381G2MulPrecompile = MulPrecompile<g2::Config>;
+pub type Bls12381G2MultiExpPrecompile = MultiExpPrecompile<g2::Config>;
+
+inventory::submit! {
+    Registration::produce_invalid_with_control::<Bls12381G1AddPrecompile>()
+}
+
+inventory::submit! {
+    Registration::produce_invalid_with_control::<Bls12381G1MulPrecompile>()
+}
+
+inventory::submit! {
+    Registration::produce_invalid_with_control::<Bls12381G1MultiExpPrecompile>()
+}
+
+inventory::submit! {
+    Registration::produce_invalid_with_control::<Bls12381G2AddPrecompile>()
+}
+
+inventory::submit! {
+    Registration::produce_invalid_with_control::<Bls12381G2MulPrecompile>()
+}
+
+inventory::submit! {
+    Registration::produce_invalid_with_control::<Bls12381G2MultiExpPrecompile>()
+}
+
+/// A point for addition or multiplication. These accept points outside the
+/// subgroup, so some are.
+fn gen_any_point<C: Group, R: Rng>(mutator: &mut Mutator<R>) -> Vec<u8> {
+    if mutator.gen_chance(0.05) {
+        random_outside_subgroup::<C, R>(mutator)
+    } else {
+        gen_point::<C, R>(mutator)
+    }
+}
+
+/// Check that every output of the right length is a point, and in the
+/// subgroup if the inputs were.
+fn check_outputs<C: Group>(
+    results: &[CommunicationResult<Vec<u8>>],
+    in_subgroup: bool,
+) -> Vec<InvariantViolation> {
+    let mut violations = invariants::output_length(results, C::POINT_LENGTH);
+    for output in results.iter().filter_map(|r| r.as_ref().ok()) {
+        if output.len() != C::POINT_LENGTH {
+            continue;
+        }
+        let kind = point_kind::<C>(output);
+        if !kind.is_on_curve() {
+            violations.push(InvariantViolation::new(
+                "on_curve",
+                display_point::<C>(output),
+            ));
+        } else if in_subgroup && !kind.is_in_subgroup() {
+            violations.push(InvariantViolation::new(
+                "subgroup",
+                display_point::<C>(output),
+            ));
+        }
+    }
+    violations
+}
+
+/// Two points to add.
+#[derive(Clone)]
+pub struct AddArgs<C> {
+    pub a: Vec<u8>,
+    pub b: Vec<u8>,
+    /// The serialized length. Shorter inputs are truncated, and longer ones
+    /// extended with zeros.
+    pub len: usize,
+    group: PhantomData<fn() -> C>,
+}
+
+impl<C: Group> AddArgs<C> {
+    /// The input length, two points.
+    pub const INPUT_LENGTH: usize = 2 * C::POINT_LENGTH;
+
+    /// Add two encoded points.
+    pub fn new(a: Vec<u8>, b: Vec<u8>) -> Self {
+        Self {
+            a,
+            b,
+            len: Self::INPUT_LENGTH,
+            group: PhantomData,
+        }
+    }
+
+    /// Parse a serialized input of any length. Missing bytes read as zero.
+    pub fn parse(input: &[u8]) -> Self {
+        let mut buf = input.to_vec();
+        buf.resize(Self::INPUT_LENGTH, 0);
+        let b = buf.split_off(C::POINT_LENGTH);
+        Self {
+            len: input.len(),
+            ..Self::new(buf, b)
+        }
+    }
+
+    /// The kind of the worse of the two points.
+    pub fn kind(&self) -> PointKind {
+        std::cmp::max(point_kind::<C>(&self.a), point_kind::<C>(&self.b))
+    }
+
+    /// Add the points, with go-ethereum's errors.
+    pub fn run(&self) -> Result<Vec<u8>, String> {
+        if self.len != Self::INPUT_LENGTH {
+            return Err(INVALID_LENGTH.to_owned());
+        }
+        let a = decode_point::<C>(&self.a)?;
+        let b = decode_point::<C>(&self.b)?;
+        Ok(encode_point(&(a + b).into_affine()))
+    }
+}
+
+impl<C: Group> BinarySerialize for AddArgs<C> {
+    fn binary_serialize<W: Write, E: ByteOrder>(&self, buf: &mut W) -> usize {
+        let mut out = self.a.clone();
+        out.extend_from_slice(&self.b);
+        out.resize(self.len, 0);
+        buf.write_all(&out).unwrap();
+        out.len()
+    }
+}
+
+impl<C: Group> NewFuzzed for AddArgs<C> {
+    type RangeType = u8;
+
+    /// Generate two points on the curve, sometimes equal or opposite.
+    fn new_fuzzed<R: Rng>(
+        mutator: &mut Mutator<R>,
+        _constraints: Option<&Constraints<Self::RangeType>>,
+    ) -> Self {
+        let a = gen_any_point::<C, R>(mutator);
+        let b = match mutator.gen_range(0u8, 10) {
+            0 => a.clone(),
+            1 => encode_point(&-decode_point::<C>(&a).expect("generated points decode")),
+            _ => gen_any_point::<C, R>(mutator),
+        };
+        Self::new(a, b)
+    }
+}
+
+/// EIP-2537 G1ADD or G2ADD.
+pub struct AddPrecompile<C>(PhantomData<fn() -> C>);
+
+impl<C> Default for AddPrecompile<C> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<C: Group> Target for AddPrecompile<C> {
+    type Intermediate = AddArgs<C>;
+    type Rng = lain::rand::rngs::StdRng;
+    type Config = ();
+
+    fn name() -> &'static str {
+        C::ADD.name
+    }
+
+    fn variant(&self, input: &Self::Intermediate) -> &'static str {
+        if input.len != AddArgs::<C>::INPUT_LENGTH {
+            "bad_length"
+        } else {
+            input.kind().name()
+        }
+    }
+
+    fn testdata() -> &'static [&'static str] {
+        C::ADD.testdata
+    }
+
+    fn parse(&self, input: &[u8]) -> Option<Self::Intermediate> {
+        Some(AddArgs::parse(input))
+    }
+
+    fn invariants(
+        &self,
+        input: &Self::Intermediate,
+        results: &[CommunicationResult<Vec<u8>>],
+    ) -> Vec<InvariantViolation> {
+        let kind = input.kind();
+        if input.len != AddArgs::<C>::INPUT_LENGTH || !kind.is_on_curve() {
+            return invariants::rejected(results);
+        }
+        let mut violations = invariants::accepted(results);
+        violations.extend(check_outputs::<C>(results, kind.is_in_subgroup()));
+        violations
+    }
+
+    fn expected_gas(&self, _input: &Self::Intermediate) -> Option<u64> {
+        Some(C::ADD.gas)
+    }
+
+    fn display_output(&self, output: &[u8]) -> String {
+        display_point::<C>(output)
+    }
+
+    fn run_raw(
+        &mut self,
+        ctx: &mut ThreadContext,
+        input: &[u8],
+    ) -> Vec<CommunicationResult<Vec<u8>>> {
+        C::ADD.run(ctx, input)
+    }
+}
+
+impl<C: Group> ProduceInvalid for AddPrecompile<C> {
+    /// Replace a point with one that does not decode, or truncate or extend
+    /// the input.
+    fn generate_invalid(&self, mutator: &mut Mutator<Self::Rng>) -> Self::Intermediate {
+        let mut args = self.generate(mutator);
+        let len = AddArgs::<C>::INPUT_LENGTH;
+        match mutator.gen_range(0u8, 4) {
+            0 => args.a = random_invalid_point::<C, _>(mutator),
+            1 => args.b = random_invalid_point::<C, _>(mutator),
+            2 => args.len = mutator.gen_range(0, len),
+            _ => args.len = mutator.gen_range(len + 1, len + 64),
+        }
+        args
+    }
+}
+
+impl<C: Group> TargetWithControl for AddPrecompile<C> {
+    fn run_control(&self, input: &Self::Intermediate) -> Result<Vec<u8>, String> {
+        input.run()
+    }
+}
+
+/// Points and scalars to multiply them by, and trailing bytes short of a
+/// whole pair. A multiplication is a multiexp of one pair.
+#[derive(Clone)]
+pub struct MultiExpArgs<C> {
+    pub pairs: Vec<(Vec<u8>, [u8; SCALAR_LENGTH])>,
+    pub tail: Vec<u8>,
+    group: PhantomData<fn() -> C>,
+}
+
+impl<C: Group> MultiExpArgs<C> {
+    /// The length of a pair, a point and a scalar.
+    pub const PAIR_LENGTH: usize = C::POINT_LENGTH + SCALAR_LENGTH;
+
+    /// Generate `count` pairs of points on the curve and scalars.
+    pub fn random<R: Rng>(mutator: &mut Mutator<R>, count: usize) -> Self {
+        Self {
+            pairs: (0..count)
+                .map(|_| (gen_any_point::<C, R>(mutator), gen_scalar::<C, R>(mutator)))
+                .collect(),
+            tail: vec![],
+            group: PhantomData,
+        }
+    }
+
+    /// Parse a serialized input.
+    pub fn parse(input: &[u8]) -> Self {
+        let whole = input.len() - input.len() % Self::PAIR_LENGTH;
+        Self {
+            pairs: input[..whole]
+                .chunks(Self::PAIR_LENGTH)
+                .map(|pair| {
+                    let mut scalar = [0u8; SCALAR_LENGTH];
+                    scalar.copy_from_slice(&pair[C::POINT_LENGTH..]);
+                    (pair[..C::POINT_LENGTH].to_vec(), scalar)
+                })
+                .collect(),
+            tail: input[whole..].to_vec(),
+            group: PhantomData,
+        }
+    }
+
+    /// The serialized length.
+    pub fn len(&self) -> usize {
+        self.pairs.len() * Self::PAIR_LENGTH + self.tail.len()
+    }
+
+    /// True if there are no pairs and no trailing bytes.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The kind of the worst point. Valid if there are none.
+    pub fn kind(&self) -> PointKind {
+        self.pairs
+            .iter()
+            .map(|(point, _)| point_kind::<C>(point))
+            .max()
+            .unwrap_or(PointKind::Valid)
+    }
+
+    /// Cut the last pair short, or add bytes short of a whole pair.
+    pub fn set_bad_length<R: Rng>(&mut self, mutator: &mut Mutator<R>) {
+        match self.pairs.pop() {
+            Some((point, scalar)) if mutator.gen_chance(0.5) => {
+                let mut pair = point;
+                pair.extend_from_slice(&scalar);
+                self.tail = pair[..mutator.gen_range(1, Self::PAIR_LENGTH)].to_vec();
+            }
+            popped => {
+                self.pairs.extend(popped);
+                let extra = mutator.gen_range(1, Self::PAIR_LENGTH);
+                self.tail = (0..extra).map(|_| mutator.gen()).collect();
+            }
+        }
+    }
+
+    /// The sum of the products, with go-ethereum's errors. The caller checks
+    /// the length.
+    pub fn run(&self) -> Result<Vec<u8>, String> {
+        let mut sum = Projective::<C>::default();
+        for (point, scalar) in &self.pairs {
+            sum += decode_point::<C>(point)?.mul_bigint(scalar_limbs(scalar));
+        }
+        Ok(encode_point(&sum.into_affine()))
+    }
+}
+
+impl<C: Group> BinarySerialize for MultiExpArgs<C> {
+    fn binary_serialize<W: Write, E: ByteOrder>(&self, buf: &mut W) -> usize {
+        let mut out = Vec::with_capacity(self.len());
+        for (point, scalar) in &self.pairs {
+            out.extend_from_slice(point);
+            out.extend_from_slice(scalar);
+        }
+        out.extend_from_slice(&self.tail);
+        buf.write_all(&out).unwrap();
+        out.len()
+    }
+}
+
+impl<C: Group> NewFuzzed for MultiExpArgs<C> {
+    type RangeType = u8;
+
+    /// Generate a few pairs, sometimes more than the discount table covers.
+    fn new_fuzzed<R: Rng>(
+        mutator: &mut Mutator<R>,
+        _constraints: Option<&Constraints<Self::RangeType>>,
+    ) -> Self {
+        let count = if mutator.gen_chance(0.05) {
+            mutator.gen_range(9, 140)
+        } else {
+            mutator.gen_range(1, 9)
+        };
+        Self::random(mutator, count)
+    }
+}
+
+/// Check the results of a multiplication or multiexp with a whole, nonzero
+/// number of pairs.
+fn multiexp_invariants<C: Group>(
+    input: &MultiExpArgs<C>,
+    results: &[CommunicationResult<Vec<u8>>],
+) -> Vec<InvariantViolation> {
+    let kind = input.kind();
+    if !kind.is_on_curve() {
+        return invariants::rejected(results);
+    }
+    let mut violations = invariants::accepted(results);
+    violations.extend(check_outputs::<C>(results, kind.is_in_subgroup()));
+    violations
+}
+
+/// EIP-2537 G1MUL or G2MUL.
+pub struct MulPrecompile<C>(PhantomData<fn() -> C>);
+
+impl<C> Default for MulPrecompile<C> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<C: Group> Target for MulPrecompile<C> {
+    type Intermediate = MultiExpArgs<C>;
+    type Rng = lain::rand::rngs::StdRng;
+    type Config = ();
+
+    fn name() -> &'static str {
+        C::MUL.name
+    }
+
+    fn variant(&self, input: &Self::Intermediate) -> &'static str {
+        let kind = input.kind();
+        if input.len() != MultiExpArgs::<C>::PAIR_LENGTH {
+            "bad_length"
+        } else if kind != PointKind::Valid {
+            kind.name()
+        } else if input.pairs[0].1 == [0; SCALAR_LENGTH] {
+            "zero_scalar"
+        } else {
+            "valid"
+        }
+    }
+
+    fn testdata() -> &'static [&'static str] {
+        C::MUL.testdata
+    }
+
+    fn parse(&self, input: &[u8]) -> Option<Self::Intermediate> {
+        Some(MultiExpArgs::parse(input))
+    }
+
+    fn invariants(
+        &self,
+        input: &Self::Intermediate,
+        results: &[CommunicationResult<Vec<u8>>],
+    ) -> Vec<InvariantViolation> {
+        if input.len() != MultiExpArgs::<C>::PAIR_LENGTH {
+            return invariants::rejected(results);
+        }
+        multiexp_invariants(input, results)
+    }
+
+    fn expected_gas(&self, _input: &Self::Intermediate) -> Option<u64> {
+        Some(C::MUL.gas)
+    }
+
+    fn display_output(&self, output: &[u8]) -> String {
+        display_point::<C>(output)
+    }
+
+    fn generate(&self, mutator: &mut Mutator<Self::Rng>) -> Self::Intermediate {
+        MultiExpArgs::random(mutator, 1)
+    }
+
+    fn run_raw(
+        &mut self,
+        ctx: &mut ThreadContext,
+        input: &[u8],
+    ) -> Vec<CommunicationResult<Vec<u8>>> {
+        C::MUL.run(ctx, input)
+    }
+}
+
+impl<C: Group> ProduceInvalid for MulPrecompile<C> {
+    /// Replace the point with one that does not decode, add a second pair,
+    /// or cut or extend the input.
+    fn generate_invalid(&self, mutator: &mut Mutator<Self::Rng>) -> Self::Intermediate {
+        let mut args = self.generate(mutator);
+        match mutator.gen_range(0u8, 3) {
+            0 => args.pairs[0].0 = random_invalid_point::<C, _>(mutator),
+            1 => args
+                .pairs
+                .extend(MultiExpArgs::<C>::random(mutator, 1).pairs),
+            _ => args.set_bad_length(mutator),
+        }
+        args
+    }
+}
+
+impl<C: Group> TargetWithControl for MulPrecompile<C> {
+    fn run_control(&self, input: &Self::Intermediate) -> Result<Vec<u8>, String> {
+        if input.len() != MultiExpArgs::<C>::PAIR_LENGTH {
+            return Err(INVALID_LENGTH.to_owned());
+        }
+        input.run()
+    }
+}
+
+/// EIP-2537 G1MULTIEXP or G2MULTIEXP.
+pub struct MultiExpPrecompile<C>(PhantomData<fn() -> C>);
+
+impl<C> Default for MultiExpPrecompile<C> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<C: Group> Target for MultiExpPrecompile<C> {
+    type Intermediate = MultiExpArgs<C>;
+    type Rng = lain::rand::rngs::StdRng;
+    type Config = ();
+
+    fn name() -> &'static str {
+        C::MULTIEXP.name
+    }
+
+    fn variant(&self, input: &Self::Intermediate) -> &'static str {
+        let kind = input.kind();
+        if input.is_empty() {
+            "zero_pairs"
+        } else if !input.tail.is_empty() {
+            "bad_length"
+        } else if kind != PointKind::Valid {
+            kind.name()
+        } else if input.pairs.len() > 1 {
+            "valid"
+        } else {
+            "single"
+        }
+    }
+
+    fn testdata() -> &'static [&'static str] {
+        C::MULTIEXP.testdata
+    }
+
+    fn parse(&self, input: &[u8]) -> Option<Self::Intermediate> {
+        Some(MultiExpArgs::parse(input))
+    }
+
+    fn invariants(
+        &self,
+        input: &Self::Intermediate,
+        results: &[CommunicationResult<Vec<u8>>],
+    ) -> Vec<InvariantViolation> {
+        if input.pairs.is_empty() || !input.tail.is_empty() {
+            return invariants::rejected(results);
+        }
+        multiexp_invariants(input, results)
+    }
+
+    /// Discounted by the number of whole pairs, even if the length is bad.
+    fn expected_gas(&self, input: &Self::Intermediate) -> Option<u64> {
+        let pairs = input.len() / MultiExpArgs::<C>::PAIR_LENGTH;
+        Some(multiexp_gas(pairs, C::MULTIEXP.gas))
+    }
+
+    fn display_output(&self, output: &[u8]) -> String {
+        display_point::<C>(output)
+    }
+
+    fn run_raw(
+        &mut self,
+        ctx: &mut ThreadContext,
+        input: &[u8],
+    ) -> Vec<CommunicationResult<Vec<u8>>> {
+        C::MULTIEXP.run(ctx, input)
+    }
+}
+
+impl<C: Group> ProduceInvalid for MultiExpPrecompile<C> {
+    /// Replace a point with one that does not decode, cut or extend the input,
+    /// or remove every pair.
+    fn generate_invalid(&self, mutator: &mut Mutator<Self::Rng>) -> Self::Intermediate {
+        let mut args = self.generate(mutator);
+        match mutator.gen_range(0u8, 3) {
+            0 => {
+                let at = mutator.gen_range(0, args.pairs.len());
+                args.pairs[at].0 = random_invalid_point::<C, _>(mutator);
+            }
+            1 => args.set_bad_length(mutator),
+            _ => args.pairs.clear(),
+        }
+        args
+    }
+}
+
+impl<C: Group> TargetWithControl for MultiExpPrecompile<C> {
+    fn run_control(&self, input: &Self::Intermediate) -> Result<Vec<u8>, String> {
+        if input.pairs.is_empty() || !input.tail.is_empty() {
+            return Err(INVALID_LENGTH.to_owned());
+        }
+        input.run()
+    }
+}
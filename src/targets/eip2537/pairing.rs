@@ -0,0 +1,382 @@
+use ark_bls12_381::{g1, g2, Bls12_381, G1Affine, G2Affine};
+use ark_ec::{pairing::Pairing, short_weierstrass::Affine, AffineRepr, CurveConfig, CurveGroup};
+use ark_ff::Zero;
+use lain::{byteorder::ByteOrder, prelude::*};
+use std::{io::Write, marker::PhantomData};
+
+use super::{
+    decode_point, encode_point, gen_point, gen_prime, point_kind, random_invalid_point,
+    random_outside_subgroup, Group, PointKind, Precompile, INVALID_LENGTH,
+};
+use crate::{
+    errors::{CommunicationResult, InvariantViolation},
+    invariants,
+    registry::Registration,
+    traits::{ProduceInvalid, Target, TargetWithControl, ThreadContext},
+};
+
+pub type Bls12381PairingPrecompile = PairingPrecompile<Bls12_381>;
+
+inventory::submit! {
+    Registration::produce_invalid_with_control::<Bls12381PairingPrecompile>()
+}
+
+/// The length of a pair, a G1 point and a G2 point.
+pub const PAIR_LENGTH: usize = 384;
+
+/// A curve with a pairing check precompile, whose groups encode as in
+/// EIP-2537.
+pub trait Engine: 'static {
+    type G1: Group;
+    type G2: Group<ScalarField = <Self::G1 as CurveConfig>::ScalarField>;
+
+    /// The pairing check. Its gas is per pair.
+    const PAIRING: Precompile;
+    /// Base gas for a pairing check.
+    const BASE_GAS: u64;
+
+    /// True if the product of the pairings of the pairs is one.
+    fn is_one(g1s: Vec<Affine<Self::G1>>, g2s: Vec<Affine<Self::G2>>) -> bool;
+}
+
+impl Engine for Bls12_381 {
+    type G1 = g1::Config;
+    type G2 = g2::Config;
+
+    const PAIRING: Precompile = Precompile {
+        name: "eip2537_pairing",
+        geth: Some(0x10),
+        celo: None,
+        gas: 23000,
+        testdata: &["blsPairing.json"],
+    };
+    const BASE_GAS: u64 = 115000;
+
+    fn is_one(g1s: Vec<G1Affine>, g2s: Vec<G2Affine>) -> bool {
+        Bls12_381::multi_pairing(g1s, g2s).is_zero()
+    }
+}
+
+/// How the pairs were built.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum PairingKind {
+    /// The product of the pairings is one
+    One,
+    /// A product of one with a single pair changed, so it is not one
+    NearMiss,
+    /// Random pairs, almost never one
+    Random,
+}
+
+/// Pairs to check, and trailing bytes short of a whole pair.
+#[derive(Debug, Clone)]
+pub struct PairingArgs<P> {
+    pub kind: PairingKind,
+    pub pairs: Vec<(Vec<u8>, Vec<u8>)>,
+    pub tail: Vec<u8>,
+    engine: PhantomData<fn() -> P>,
+}
+
+/// The scalar field of both groups.
+type Scalar<P> = <<P as Engine>::G1 as CurveConfig>::ScalarField;
+
+/// A G1 point and a G2 point.
+type Pair<P> = (Affine<<P as Engine>::G1>, Affine<<P as Engine>::G2>);
+
+/// Pairs `(a_i P, b_i Q)` followed by `(-sum(a_i b_i) P, Q)`, whose product
+/// is one.
+fn product_of_one<P: Engine, R: Rng>(mutator: &mut Mutator<R>) -> Vec<Pair<P>> {
+    let q = Affine::<P::G2>::generator() * gen_prime::<Scalar<P>, R>(mutator);
+    let mut sum = Scalar::<P>::zero();
+    let mut pairs = Vec::new();
+    for _ in 0..mutator.gen_range(1, 4) {
+        let a: Scalar<P> = gen_prime(mutator);
+        let b: Scalar<P> = gen_prime(mutator);
+        sum += a * b;
+        pairs.push((Affine::<P::G1>::generator() * a, q * b));
+    }
+    pairs.push((Affine::<P::G1>::generator() * -sum, q));
+    pairs
+        .into_iter()
+        .map(|(p, q)| (p.into_affine(), q.into_affine()))
+        .collect()
+}
+
+/// Change the last pair of a product of one, so that the product is not one.
+fn near_miss<P: Engine, R: Rng>(mutator: &mut Mutator<R>, pairs: &mut Vec<Pair<P>>) {
+    let (p, q) = pairs.last_mut().expect("products have pairs");
+    match mutator.gen_range(0u8, 4) {
+        0 => *p = (*p + Affine::<P::G1>::generator()).into_affine(),
+        1 => *q = (*q + Affine::<P::G2>::generator()).into_affine(),
+        2 => *p = -*p,
+        _ => {
+            pairs.pop();
+        }
+    }
+}
+
+impl<P: Engine> PairingArgs<P> {
+    /// Parse a serialized input. The kind is found by running the control.
+    pub fn parse(input: &[u8]) -> Self {
+        let whole = input.len() - input.len() % PAIR_LENGTH;
+        let mut args = Self {
+            kind: PairingKind::Random,
+            pairs: input[..whole]
+                .chunks(PAIR_LENGTH)
+                .map(|pair| {
+                    let (a, b) = pair.split_at(P::G1::POINT_LENGTH);
+                    (a.to_vec(), b.to_vec())
+                })
+                .collect(),
+            tail: input[whole..].to_vec(),
+            engine: PhantomData,
+        };
+        if args.check() == Ok(true) {
+            args.kind = PairingKind::One;
+        }
+        args
+    }
+
+    /// The kind of the worst point. Valid if there are none.
+    pub fn point_kind(&self) -> PointKind {
+        self.pairs
+            .iter()
+            .flat_map(|(a, b)| vec![point_kind::<P::G1>(a), point_kind::<P::G2>(b)])
+            .max()
+            .unwrap_or(PointKind::Valid)
+    }
+
+    /// Check the pairs, with go-ethereum's errors.
+    pub fn check(&self) -> Result<bool, String> {
+        if self.pairs.is_empty() || !self.tail.is_empty() {
+            return Err(INVALID_LENGTH.to_owned());
+        }
+        let mut g1s = vec![];
+        let mut g2s = vec![];
+        for (a, b) in &self.pairs {
+            let a = decode_point::<P::G1>(a)?;
+            let b = decode_point::<P::G2>(b)?;
+            if !a.is_in_correct_subgroup_assuming_on_curve() {
+                return Err(P::G1::SUBGROUP_ERROR.to_owned());
+            }
+            if !b.is_in_correct_subgroup_assuming_on_curve() {
+                return Err(P::G2::SUBGROUP_ERROR.to_owned());
+            }
+            g1s.push(a);
+            g2s.push(b);
+        }
+        Ok(P::is_one(g1s, g2s))
+    }
+
+    /// The serialized length.
+    pub fn len(&self) -> usize {
+        self.pairs.len() * PAIR_LENGTH + self.tail.len()
+    }
+
+    /// True if there are no pairs and no trailing bytes.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A pairing check result, as a 32-byte word.
+fn word(one: bool) -> Vec<u8> {
+    let mut out = vec![0u8; 32];
+    out[31] = one as u8;
+    out
+}
+
+impl<P: Engine> BinarySerialize for PairingArgs<P> {
+    fn binary_serialize<W: Write, E: ByteOrder>(&self, buf: &mut W) -> usize {
+        let mut out = Vec::with_capacity(self.len());
+        for (a, b) in &self.pairs {
+            out.extend_from_slice(a);
+            out.extend_from_slice(b);
+        }
+        out.extend_from_slice(&self.tail);
+        buf.write_all(&out).unwrap();
+        out.len()
+    }
+}
+
+impl<P: Engine> NewFuzzed for PairingArgs<P> {
+    type RangeType = u8;
+
+    /// Generate a product of one, a near miss, or random pairs. Products
+    /// sometimes include pairs with a point at infinity, which pair to one.
+    fn new_fuzzed<R: Rng>(
+        mutator: &mut Mutator<R>,
+        _constraints: Option<&Constraints<Self::RangeType>>,
+    ) -> Self {
+        let (kind, pairs) = match mutator.gen_range(0u8, 20) {
+            0..=9 => (PairingKind::One, product_of_one::<P, R>(mutator)),
+            10..=14 => {
+                let mut pairs = product_of_one::<P, R>(mutator);
+                near_miss::<P, R>(mutator, &mut pairs);
+                (PairingKind::NearMiss, pairs)
+            }
+            _ => {
+                let pairs = (0..mutator.gen_range(1, 5))
+                    .map(|_| {
+                        (
+                            gen_point::<P::G1, R>(mutator),
+                            gen_point::<P::G2, R>(mutator),
+                        )
+                    })
+                    .collect();
+                let mut args = Self {
+                    kind: PairingKind::Random,
+                    pairs,
+                    tail: vec![],
+                    engine: PhantomData,
+                };
+                // random points include points at infinity, which pair to one
+                if args.check() == Ok(true) {
+                    args.kind = PairingKind::One;
+                }
+                return args;
+            }
+        };
+        let mut pairs: Vec<_> = pairs
+            .iter()
+            .map(|(a, b)| (encode_point(a), encode_point(b)))
+            .collect();
+        if mutator.gen_chance(0.2) {
+            let pair = if mutator.gen_chance(0.5) {
+                (vec![0; P::G1::POINT_LENGTH], gen_point::<P::G2, R>(mutator))
+            } else {
+                (gen_point::<P::G1, R>(mutator), vec![0; P::G2::POINT_LENGTH])
+            };
+            let at = mutator.gen_range(0, pairs.len() + 1);
+            pairs.insert(at, pair);
+        }
+        Self {
+            kind,
+            pairs,
+            tail: vec![],
+            engine: PhantomData,
+        }
+    }
+}
+
+/// EIP-2537 PAIRING.
+pub struct PairingPrecompile<P>(PhantomData<fn() -> P>);
+
+impl<P> Default for PairingPrecompile<P> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<P: Engine> Target for PairingPrecompile<P> {
+    type Intermediate = PairingArgs<P>;
+    type Rng = lain::rand::rngs::StdRng;
+    type Config = ();
+
+    fn name() -> &'static str {
+        P::PAIRING.name
+    }
+
+    fn variant(&self, input: &Self::Intermediate) -> &'static str {
+        let kind = input.point_kind();
+        if input.is_empty() {
+            "empty"
+        } else if !input.tail.is_empty() {
+            "bad_length"
+        } else if !kind.is_in_subgroup() {
+            kind.name()
+        } else {
+            match input.kind {
+                PairingKind::One => "one",
+                PairingKind::NearMiss => "near_miss",
+                PairingKind::Random => "random",
+            }
+        }
+    }
+
+    fn testdata() -> &'static [&'static str] {
+        P::PAIRING.testdata
+    }
+
+    fn parse(&self, input: &[u8]) -> Option<Self::Intermediate> {
+        Some(PairingArgs::parse(input))
+    }
+
+    fn invariants(
+        &self,
+        input: &Self::Intermediate,
+        results: &[CommunicationResult<Vec<u8>>],
+    ) -> Vec<InvariantViolation> {
+        if input.pairs.is_empty() || !input.tail.is_empty() || !input.point_kind().is_in_subgroup()
+        {
+            return invariants::rejected(results);
+        }
+        let mut violations = invariants::accepted(results);
+        violations.extend(invariants::output_length(results, 32));
+        for output in results.iter().filter_map(|r| r.as_ref().ok()) {
+            let shown = self.display_output(output);
+            if output.len() == 32 && *output != word(true) && *output != word(false) {
+                violations.push(InvariantViolation::new("boolean", shown));
+            } else if input.kind == PairingKind::One && *output != word(true) {
+                violations.push(InvariantViolation::new("one", shown));
+            } else if input.kind == PairingKind::NearMiss && *output != word(false) {
+                violations.push(InvariantViolation::new("near_miss", shown));
+            }
+        }
+        violations
+    }
+
+    fn expected_gas(&self, input: &Self::Intermediate) -> Option<u64> {
+        Some(P::BASE_GAS + P::PAIRING.gas * (input.len() / PAIR_LENGTH) as u64)
+    }
+
+    fn display_output(&self, output: &[u8]) -> String {
+        if *output == word(true)[..] {
+            "true".to_owned()
+        } else if *output == word(false)[..] {
+            "false".to_owned()
+        } else {
+            hex::encode(output)
+        }
+    }
+
+    fn run_raw(
+        &mut self,
+        ctx: &mut ThreadContext,
+        input: &[u8],
+    ) -> Vec<CommunicationResult<Vec<u8>>> {
+        P::PAIRING.run(ctx, input)
+    }
+}
+
+impl<P: Engine> ProduceInvalid for PairingPrecompile<P> {
+    /// Replace a point with one outside the subgroup or one that does not
+    /// decode, cut or extend the input, or remove every pair.
+    fn generate_invalid(&self, mutator: &mut Mutator<Self::Rng>) -> Self::Intermediate {
+        let mut args = self.generate(mutator);
+        let at = mutator.gen_range(0, args.pairs.len());
+        match mutator.gen_range(0u8, 6) {
+            0 => args.pairs[at].0 = random_outside_subgroup::<P::G1, _>(mutator),
+            1 => args.pairs[at].1 = random_outside_subgroup::<P::G2, _>(mutator),
+            2 => args.pairs[at].0 = random_invalid_point::<P::G1, _>(mutator),
+            3 => args.pairs[at].1 = random_invalid_point::<P::G2, _>(mutator),
+            4 if mutator.gen_chance(0.5) => {
+                let (a, b) = args.pairs.pop().expect("not empty");
+                let pair = [a, b].concat();
+                args.tail = pair[..mutator.gen_range(1, PAIR_LENGTH)].to_vec();
+            }
+            4 => {
+                let extra = mutator.gen_range(1, PAIR_LENGTH);
+                args.tail = (0..extra).map(|_| mutator.gen()).collect();
+            }
+            _ => args.pairs.clear(),
+        }
+        args
+    }
+}
+
+impl<P: Engine> TargetWithControl for PairingPrecompile<P> {
+    fn run_control(&self, input: &Self::Intermediate) -> Result<Vec<u8>, String> {
+        input.check().map(word)
+    }
+}
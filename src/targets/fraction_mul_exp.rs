@@ -0,0 +1,428 @@
+use lain::{byteorder::ByteOrder, prelude::*};
+use num_bigint::BigUint;
+use num_rational::Ratio;
+use num_traits::{One, Pow, ToPrimitive, Zero};
+use std::{cmp, io::Write};
+
+use crate::{
+    errors::{CommunicationResult, InvariantViolation},
+    invariants,
+    registry::Registration,
+    traits::{ProduceInvalid, Target, TargetWithControl, ThreadContext},
+};
+
+inventory::submit! {
+    Registration::produce_invalid_with_control::<FractionMulExpPrecompile>()
+}
+
+/// The input length, six 32-byte words. Longer inputs are accepted.
+pub const INPUT_LENGTH: usize = 192;
+/// The largest exponent and decimals celo accepts.
+pub const MAX_EXPONENT: u64 = 100_000;
+/// Exponents from here are only generated with small bases, to keep the
+/// control fast.
+pub const LARGE_EXPONENT: u64 = 1000;
+/// Gas for any input, before the charge for large exponents or decimals.
+pub const BASE_GAS: u64 = 50;
+
+/// celo's error for an input shorter than `INPUT_LENGTH`.
+pub const INVALID_LENGTH: &str = "invalid input length";
+/// celo's error for a zero denominator.
+pub const ZERO_DENOMINATOR: &str = "Input Error: Denominator of zero provided!";
+/// celo's error for an exponent or decimals above `MAX_EXPONENT`.
+pub const TOO_LARGE: &str = "Input Error: Decimals or exponent too large";
+
+/// The fixed-point one of celo's FixidityLib, 10^24.
+fn fixidity_one() -> BigUint {
+    BigUint::from(10u32).pow(24u32)
+}
+
+/// A big-endian word, left-padded. Panics if the value is over 256 bits.
+fn word(value: &BigUint) -> [u8; 32] {
+    let bytes = value.to_bytes_be();
+    let mut word = [0u8; 32];
+    word[32 - bytes.len()..].copy_from_slice(&bytes);
+    word
+}
+
+/// A word as a `u64`, if it fits.
+fn word_u64(word: &[u8; 32]) -> Option<u64> {
+    BigUint::from_bytes_be(word).to_u64()
+}
+
+/// A numerator or denominator: mostly fixed-point values near 10^24 or
+/// random words, sometimes 0, 1 or the largest word.
+fn gen_value<R: Rng>(mutator: &mut Mutator<R>) -> [u8; 32] {
+    match mutator.gen_range(0u8, 10) {
+        0 => [0; 32],
+        1 => word(&BigUint::one()),
+        2 => word(&fixidity_one()),
+        3 => word(&BigUint::from(mutator.gen::<u64>())),
+        4 | 5 => {
+            let offset = BigUint::from(mutator.gen::<u64>());
+            if mutator.gen_chance(0.5) {
+                word(&(fixidity_one() + offset))
+            } else {
+                word(&(fixidity_one() - offset))
+            }
+        }
+        6 => [0xff; 32],
+        _ => {
+            let mut word = [0u8; 32];
+            word.iter_mut().for_each(|b| *b = mutator.gen());
+            word
+        }
+    }
+}
+
+/// A value between 1 and 2^16, for bases raised to large exponents.
+fn gen_small<R: Rng>(mutator: &mut Mutator<R>) -> [u8; 32] {
+    word(&BigUint::from(mutator.gen_range(1u32, 1 << 16)))
+}
+
+/// An exponent or decimals up to `max`: mostly small, sometimes the 18 and
+/// 24 decimals of celo's tokens and fixed-point numbers.
+fn gen_small_exponent<R: Rng>(mutator: &mut Mutator<R>, max: u64) -> [u8; 32] {
+    let exponent = match mutator.gen_range(0u8, 10) {
+        0 => 0,
+        1 => 18,
+        2 => 24,
+        3..=6 => mutator.gen_range(0, 100),
+        _ => mutator.gen_range(0, max + 1),
+    };
+    word(&BigUint::from(exponent))
+}
+
+/// The kind of an input.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FractionKind {
+    /// An exponent and decimals below `LARGE_EXPONENT`
+    Valid,
+    /// An exponent or decimals from `LARGE_EXPONENT` to `MAX_EXPONENT`
+    LargeExponent,
+    /// A zero denominator
+    ZeroDenominator,
+    /// An exponent or decimals above `MAX_EXPONENT`
+    TooLarge,
+    /// An input shorter than `INPUT_LENGTH`
+    Short,
+}
+
+/// `aNumerator/aDenominator * (bNumerator/bDenominator)^exponent`, at
+/// `decimals` decimals. Each is a big-endian word.
+#[derive(Debug, Clone)]
+pub struct FractionMulExpArgs {
+    pub a_numerator: [u8; 32],
+    pub a_denominator: [u8; 32],
+    pub b_numerator: [u8; 32],
+    pub b_denominator: [u8; 32],
+    pub exponent: [u8; 32],
+    pub decimals: [u8; 32],
+    /// The serialized length. Shorter inputs are truncated, and longer ones
+    /// extended with zeros.
+    pub len: usize,
+}
+
+impl FractionMulExpArgs {
+    /// Parse a serialized input of any length. Missing bytes read as zero.
+    pub fn parse(input: &[u8]) -> Self {
+        let mut buf = input.to_vec();
+        buf.resize(cmp::max(input.len(), INPUT_LENGTH), 0);
+        let word = |i: usize| {
+            let mut word = [0u8; 32];
+            word.copy_from_slice(&buf[i * 32..(i + 1) * 32]);
+            word
+        };
+        Self {
+            a_numerator: word(0),
+            a_denominator: word(1),
+            b_numerator: word(2),
+            b_denominator: word(3),
+            exponent: word(4),
+            decimals: word(5),
+            len: input.len(),
+        }
+    }
+
+    /// The larger of the exponent and decimals, if it fits in a `u64`.
+    fn max_exponent(&self) -> Option<u64> {
+        Some(cmp::max(
+            word_u64(&self.exponent)?,
+            word_u64(&self.decimals)?,
+        ))
+    }
+
+    /// The kind, in the order celo checks the input.
+    pub fn kind(&self) -> FractionKind {
+        let zero = |word: &[u8; 32]| word.iter().all(|b| *b == 0);
+        if self.len < INPUT_LENGTH {
+            return FractionKind::Short;
+        }
+        if zero(&self.a_denominator) || zero(&self.b_denominator) {
+            return FractionKind::ZeroDenominator;
+        }
+        match self.max_exponent() {
+            Some(max) if max < LARGE_EXPONENT => FractionKind::Valid,
+            Some(max) if max <= MAX_EXPONENT => FractionKind::LargeExponent,
+            _ => FractionKind::TooLarge,
+        }
+    }
+
+    /// Compute the fraction as celo does: the numerator is truncated at
+    /// `decimals` decimals, and returned over `10^decimals`. Each is
+    /// left-padded to 32 bytes, and longer if they do not fit.
+    pub fn run(&self) -> Result<Vec<u8>, String> {
+        match self.kind() {
+            FractionKind::Short => return Err(INVALID_LENGTH.to_owned()),
+            FractionKind::ZeroDenominator => return Err(ZERO_DENOMINATOR.to_owned()),
+            FractionKind::TooLarge => return Err(TOO_LARGE.to_owned()),
+            FractionKind::Valid | FractionKind::LargeExponent => {}
+        }
+        let value = |word: &[u8; 32]| BigUint::from_bytes_be(word);
+        let exponent = word_u64(&self.exponent).expect("checked above") as u32;
+        let decimals = word_u64(&self.decimals).expect("checked above") as u32;
+
+        let a = Ratio::new(value(&self.a_numerator), value(&self.a_denominator));
+        let b = Ratio::new(value(&self.b_numerator), value(&self.b_denominator)).pow(exponent);
+        let scale = BigUint::from(10u32).pow(decimals);
+        // Reducing products this large costs more than computing them, and
+        // does not change the truncated value
+        let scaled = Ratio::new_raw(a.numer() * b.numer() * &scale, a.denom() * b.denom());
+
+        let mut out = padded(&scaled.to_integer());
+        out.extend_from_slice(&padded(&scale));
+        Ok(out)
+    }
+}
+
+/// A value's bytes, left-padded to 32. Longer values are not truncated.
+fn padded(value: &BigUint) -> Vec<u8> {
+    let bytes = if value.is_zero() {
+        vec![]
+    } else {
+        value.to_bytes_be()
+    };
+    let mut out = vec![0u8; 32usize.saturating_sub(bytes.len())];
+    out.extend_from_slice(&bytes);
+    out
+}
+
+impl BinarySerialize for FractionMulExpArgs {
+    fn binary_serialize<W: Write, E: ByteOrder>(&self, buf: &mut W) -> usize {
+        let mut out = Vec::with_capacity(INPUT_LENGTH);
+        for word in [
+            &self.a_numerator,
+            &self.a_denominator,
+            &self.b_numerator,
+            &self.b_denominator,
+            &self.exponent,
+            &self.decimals,
+        ]
+        .iter()
+        {
+            out.extend_from_slice(*word);
+        }
+        out.resize(self.len, 0);
+        buf.write_all(&out).unwrap();
+        out.len()
+    }
+}
+
+impl NewFuzzed for FractionMulExpArgs {
+    type RangeType = u8;
+
+    /// Generate non-zero denominators, and an exponent and decimals celo
+    /// accepts. Large exponents are only generated with small bases.
+    fn new_fuzzed<R: Rng>(
+        mutator: &mut Mutator<R>,
+        _constraints: Option<&Constraints<Self::RangeType>>,
+    ) -> Self {
+        let non_zero = |mut word: [u8; 32]| {
+            if word.iter().all(|b| *b == 0) {
+                word[31] = 1;
+            }
+            word
+        };
+        let large = mutator.gen_chance(0.1);
+        let (b_numerator, b_denominator, exponent) = if large {
+            let exponent = mutator.gen_range(LARGE_EXPONENT, MAX_EXPONENT + 1);
+            (
+                gen_small(mutator),
+                gen_small(mutator),
+                word(&BigUint::from(exponent)),
+            )
+        } else {
+            (
+                gen_value(mutator),
+                non_zero(gen_value(mutator)),
+                gen_small_exponent(mutator, LARGE_EXPONENT - 1),
+            )
+        };
+        let decimals = if mutator.gen_chance(0.05) {
+            word(&BigUint::from(
+                mutator.gen_range(LARGE_EXPONENT, MAX_EXPONENT + 1),
+            ))
+        } else {
+            gen_small_exponent(mutator, LARGE_EXPONENT - 1)
+        };
+        // celo ignores bytes past the input
+        let len = if mutator.gen_chance(0.05) {
+            mutator.gen_range(INPUT_LENGTH + 1, INPUT_LENGTH + 64)
+        } else {
+            INPUT_LENGTH
+        };
+        Self {
+            a_numerator: gen_value(mutator),
+            a_denominator: non_zero(gen_value(mutator)),
+            b_numerator,
+            b_denominator,
+            exponent,
+            decimals,
+            len,
+        }
+    }
+}
+
+/// celo's gas: the base gas, plus a tenth of the larger of the exponent and
+/// decimals from 10 up to `MAX_EXPONENT`.
+pub fn gas(args: &FractionMulExpArgs) -> u64 {
+    if args.len < INPUT_LENGTH {
+        return BASE_GAS;
+    }
+    match args.max_exponent() {
+        Some(max) if (10..=MAX_EXPONENT).contains(&max) => BASE_GAS + max / 10,
+        _ => BASE_GAS,
+    }
+}
+
+/// celo's fractionMulExp.
+#[derive(Debug, Default)]
+pub struct FractionMulExpPrecompile;
+
+impl Target for FractionMulExpPrecompile {
+    type Intermediate = FractionMulExpArgs;
+    type Rng = lain::rand::rngs::StdRng;
+    type Config = ();
+
+    fn name() -> &'static str {
+        "fraction_mul_exp"
+    }
+
+    fn variant(&self, input: &Self::Intermediate) -> &'static str {
+        match input.kind() {
+            FractionKind::Valid => "valid",
+            FractionKind::LargeExponent => "large_exponent",
+            FractionKind::ZeroDenominator => "zero_denominator",
+            FractionKind::TooLarge => "too_large",
+            FractionKind::Short => "short",
+        }
+    }
+
+    fn testdata() -> &'static [&'static str] {
+        &[]
+    }
+
+    fn parse(&self, input: &[u8]) -> Option<Self::Intermediate> {
+        Some(FractionMulExpArgs::parse(input))
+    }
+
+    fn invariants(
+        &self,
+        input: &Self::Intermediate,
+        results: &[CommunicationResult<Vec<u8>>],
+    ) -> Vec<InvariantViolation> {
+        match input.kind() {
+            FractionKind::Valid | FractionKind::LargeExponent => {}
+            _ => return invariants::rejected(results),
+        }
+        let mut violations = invariants::accepted(results);
+        // the output ends with 10^decimals
+        let decimals = word_u64(&input.decimals).expect("accepted inputs fit") as u32;
+        let scale = padded(&BigUint::from(10u32).pow(decimals));
+        violations.extend(
+            results
+                .iter()
+                .filter_map(|r| r.as_ref().ok())
+                .filter(|output| output.len() < 32 + scale.len() || !output.ends_with(&scale))
+                .map(|output| {
+                    InvariantViolation::new(
+                        "denominator",
+                        format!(
+                            "expected 10^{}, got {}",
+                            decimals,
+                            self.display_output(output)
+                        ),
+                    )
+                }),
+        );
+        violations
+    }
+
+    fn expected_gas(&self, input: &Self::Intermediate) -> Option<u64> {
+        Some(gas(input))
+    }
+
+    /// Outputs of two words show as a fraction.
+    fn display_output(&self, output: &[u8]) -> String {
+        if output.len() != 64 {
+            return hex::encode(output);
+        }
+        format!(
+            "{}/{}",
+            BigUint::from_bytes_be(&output[..32]),
+            BigUint::from_bytes_be(&output[32..])
+        )
+    }
+
+    fn run_raw(
+        &mut self,
+        context: &mut ThreadContext,
+        input: &[u8],
+    ) -> Vec<CommunicationResult<Vec<u8>>> {
+        vec![context.celo.run_precompile(0xfc, input)]
+    }
+}
+
+impl ProduceInvalid for FractionMulExpPrecompile {
+    /// Zero a denominator, raise the exponent or decimals past
+    /// `MAX_EXPONENT`, or truncate the input.
+    fn generate_invalid(&self, mutator: &mut Mutator<Self::Rng>) -> Self::Intermediate {
+        let mut args = self.generate(mutator);
+        match mutator.gen_range(0u8, 4) {
+            0 => {
+                if mutator.gen_chance(0.5) {
+                    args.a_denominator = [0; 32];
+                } else {
+                    args.b_denominator = [0; 32];
+                }
+            }
+            1 => {
+                let too_large = match mutator.gen_range(0u8, 3) {
+                    0 => word(&BigUint::from(MAX_EXPONENT + 1)),
+                    1 => word(&BigUint::from(
+                        mutator.gen_range(MAX_EXPONENT + 1, u64::MAX),
+                    )),
+                    // past i64, which celo checks before comparing
+                    _ => {
+                        let mut word = [0u8; 32];
+                        word[mutator.gen_range(0, 24)] = mutator.gen_range(1, 0xff);
+                        word
+                    }
+                };
+                if mutator.gen_chance(0.5) {
+                    args.exponent = too_large;
+                } else {
+                    args.decimals = too_large;
+                }
+            }
+            _ => args.len = mutator.gen_range(0, INPUT_LENGTH),
+        }
+        args
+    }
+}
+
+impl TargetWithControl for FractionMulExpPrecompile {
+    fn run_control(&self, input: &Self::Intermediate) -> Result<Vec<u8>, String> {
+        input.run()
+    }
+}
@@ -1,10 +1,16 @@
 use lain::traits::BinarySerialize;
 
 use crate::{
-    errors::CommunicationResult,
+    errors::{CommunicationResult, InvariantViolation},
+    invariants,
+    registry::Registration,
     traits::{Target, TargetWithControl, ThreadContext},
 };
 
+inventory::submit! {
+    Registration::with_control::<IdentityPrecompile>()
+}
+
 #[derive(Debug, Default)]
 pub struct IdentityPrecompile;
 
@@ -17,6 +23,28 @@ impl Target for IdentityPrecompile {
         "identity"
     }
 
+    fn parse(&self, input: &[u8]) -> Option<Self::Intermediate> {
+        Some(input.to_vec())
+    }
+
+    fn invariants(
+        &self,
+        input: &Self::Intermediate,
+        results: &[CommunicationResult<Vec<u8>>],
+    ) -> Vec<InvariantViolation> {
+        let mut violations = invariants::accepted(results);
+        violations.extend(
+            results
+                .iter()
+                .filter_map(|r| r.as_ref().ok())
+                .filter(|output| *output != input)
+                .map(|output| {
+                    InvariantViolation::new("echo", format!("returned {}", hex::encode(output)))
+                }),
+        );
+        violations
+    }
+
     fn run_raw(
         &mut self,
         context: &mut ThreadContext,
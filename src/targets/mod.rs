@@ -1,7 +1,20 @@
-pub mod cip20;
-pub mod identity;
-pub mod sha256;
+// one module per file or directory here, declared by build.rs
+include!(concat!(env!("OUT_DIR"), "/targets.rs"));
 
+pub use blake2f::Blake2fPrecompile;
+pub use bn256::{Bn256AddPrecompile, Bn256MulPrecompile, Bn256PairingPrecompile};
+pub use celo_header::{BlockNumberFromHeaderPrecompile, HashHeaderPrecompile};
 pub use cip20::Cip20Precompile;
+pub use ecrecover::EcrecoverPrecompile;
+pub use eip2537::{
+    Bls12381G1AddPrecompile, Bls12381G1MulPrecompile, Bls12381G1MultiExpPrecompile,
+    Bls12381G2AddPrecompile, Bls12381G2MulPrecompile, Bls12381G2MultiExpPrecompile,
+    Bls12381MapFp2ToG2Precompile, Bls12381MapFpToG1Precompile, Bls12381PairingPrecompile,
+};
+pub use fraction_mul_exp::FractionMulExpPrecompile;
 pub use identity::IdentityPrecompile;
+pub use modexp::ModExpPrecompile;
+pub use proof_of_possession::ProofOfPossessionPrecompile;
+// the ripemd160 crate shares the name of the declared module
+pub use self::ripemd160::Ripemd160Precompile;
 pub use sha256::Sha256Precompile;
@@ -0,0 +1,464 @@
+use lain::{byteorder::ByteOrder, prelude::*};
+use num_bigint::BigUint;
+use num_traits::{ToPrimitive, Zero};
+use std::{cmp, io::Write};
+
+use crate::{
+    errors::{CommunicationResult, InvariantViolation},
+    invariants,
+    registry::Registration,
+    traits::{ProduceInvalid, Target, TargetWithControl, ThreadContext},
+};
+
+inventory::submit! {
+    Registration::produce_invalid_with_control::<ModExpPrecompile>()
+}
+
+/// The longest declared operand length the control allocates. Longer
+/// declarations are refused, so generated inputs declare at most this much.
+pub const MAX_LENGTH: u64 = 1 << 20;
+
+// Update whenever a new kind is added
+const VALID_KIND_COUNT: u8 = 4;
+const INVALID_KIND_COUNT: u8 = 2;
+
+/// The kind of a modexp input.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ModExpKind {
+    /// Operands of their declared lengths, with a non-zero modulus
+    Valid,
+    /// An exponent with leading zero bytes
+    LeadingZeros,
+    /// A modulus of zero
+    ZeroModulus,
+    /// Zero-length base and modulus
+    Empty,
+    /// Declared lengths longer than the operands that follow
+    ShortBody,
+    /// A length word above 64 bits. Executors read its low 64 bits, and price
+    /// the full value
+    Overflow,
+}
+
+/// A modexp input in the EIP-198 layout: three 32-byte length words, then the
+/// base, exponent and modulus.
+#[derive(Debug, Clone)]
+pub struct ModExpArgs {
+    pub kind: ModExpKind,
+    pub base_len: [u8; 32],
+    pub exp_len: [u8; 32],
+    pub mod_len: [u8; 32],
+    pub base: Vec<u8>,
+    pub exp: Vec<u8>,
+    pub modulus: Vec<u8>,
+}
+
+/// A big-endian length word.
+fn word(len: u64) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[24..].copy_from_slice(&len.to_be_bytes());
+    word
+}
+
+/// The low 64 bits of a length word, as go-ethereum's `big.Int.Uint64` reads
+/// them.
+fn low_u64(word: &[u8]) -> u64 {
+    let mut low = [0u8; 8];
+    low.copy_from_slice(&word[word.len() - 8..]);
+    u64::from_be_bytes(low)
+}
+
+/// go-ethereum's `getData`: `size` bytes from `start`, right-padded with zeros.
+fn get_data(data: &[u8], start: u64, size: u64) -> Vec<u8> {
+    let len = data.len() as u64;
+    let start = cmp::min(start, len);
+    let end = cmp::min(start.saturating_add(size), len);
+    let mut out = data[start as usize..end as usize].to_vec();
+    out.resize(size as usize, 0);
+    out
+}
+
+fn gen_bytes<R: Rng>(mutator: &mut Mutator<R>, len: usize) -> Vec<u8> {
+    (0..len).map(|_| mutator.gen()).collect()
+}
+
+/// An operand length. Mostly small, sometimes past the 64 and 1024 byte
+/// steps of the EIP-198 complexity function.
+fn gen_len<R: Rng>(mutator: &mut Mutator<R>) -> usize {
+    match mutator.gen_range(0u8, 10) {
+        0..=5 => mutator.gen_range(1, 65),
+        6..=8 => mutator.gen_range(65, 257),
+        _ => mutator.gen_range(1025, 1100),
+    }
+}
+
+impl ModExpArgs {
+    /// Instantiate with lengths declared from the operands.
+    pub fn new(base: Vec<u8>, exp: Vec<u8>, modulus: Vec<u8>) -> Self {
+        let mut args = Self {
+            kind: ModExpKind::Valid,
+            base_len: word(base.len() as u64),
+            exp_len: word(exp.len() as u64),
+            mod_len: word(modulus.len() as u64),
+            base,
+            exp,
+            modulus,
+        };
+        args.kind = args.classify();
+        args
+    }
+
+    /// Split a serialized input at its declared lengths. Returns `None` if the
+    /// input is shorter than the length words, or has bytes past the modulus,
+    /// as the precompile ignores them.
+    pub fn parse(input: &[u8]) -> Option<Self> {
+        if input.len() < 96 {
+            return None;
+        }
+        let mut args = Self {
+            kind: ModExpKind::Valid,
+            base_len: [0; 32],
+            exp_len: [0; 32],
+            mod_len: [0; 32],
+            base: vec![],
+            exp: vec![],
+            modulus: vec![],
+        };
+        args.base_len.copy_from_slice(&input[..32]);
+        args.exp_len.copy_from_slice(&input[32..64]);
+        args.mod_len.copy_from_slice(&input[64..96]);
+
+        let mut body = &input[96..];
+        for (len, operand) in [
+            (&args.base_len, &mut args.base),
+            (&args.exp_len, &mut args.exp),
+            (&args.mod_len, &mut args.modulus),
+        ]
+        .iter_mut()
+        {
+            let len = cmp::min(low_u64(*len), body.len() as u64) as usize;
+            operand.extend_from_slice(&body[..len]);
+            body = &body[len..];
+        }
+        if !body.is_empty() {
+            return None;
+        }
+        args.kind = args.classify();
+        Some(args)
+    }
+
+    fn classify(&self) -> ModExpKind {
+        let overflows = |word: &[u8; 32]| word[..24].iter().any(|b| *b != 0);
+        let short = |word: &[u8; 32], operand: &Vec<u8>| low_u64(word) > operand.len() as u64;
+        if overflows(&self.base_len) || overflows(&self.exp_len) || overflows(&self.mod_len) {
+            ModExpKind::Overflow
+        } else if short(&self.base_len, &self.base)
+            || short(&self.exp_len, &self.exp)
+            || short(&self.mod_len, &self.modulus)
+        {
+            ModExpKind::ShortBody
+        } else if self.base.is_empty() && self.modulus.is_empty() {
+            ModExpKind::Empty
+        } else if self.modulus.iter().all(|b| *b == 0) {
+            ModExpKind::ZeroModulus
+        } else if self.exp.first() == Some(&0) {
+            ModExpKind::LeadingZeros
+        } else {
+            ModExpKind::Valid
+        }
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        self.binary_serialize::<_, lain::byteorder::BigEndian>(&mut buf);
+        buf
+    }
+}
+
+impl BinarySerialize for ModExpArgs {
+    fn binary_serialize<W: Write, E: ByteOrder>(&self, buf: &mut W) -> usize {
+        buf.write_all(&self.base_len).unwrap();
+        buf.write_all(&self.exp_len).unwrap();
+        buf.write_all(&self.mod_len).unwrap();
+        buf.write_all(&self.base).unwrap();
+        buf.write_all(&self.exp).unwrap();
+        buf.write_all(&self.modulus).unwrap();
+        96 + self.base.len() + self.exp.len() + self.modulus.len()
+    }
+}
+
+impl NewFuzzed for ModExpArgs {
+    type RangeType = u8;
+
+    fn new_fuzzed<R: Rng>(
+        mutator: &mut Mutator<R>,
+        _constraints: Option<&Constraints<Self::RangeType>>,
+    ) -> Self {
+        let base_len = gen_len(mutator);
+        let base = gen_bytes(mutator, base_len);
+        // exponents over 32 bytes are priced by their length
+        let exp_len = if mutator.gen_chance(0.9) {
+            mutator.gen_range(0, 33)
+        } else {
+            mutator.gen_range(33, 65)
+        };
+        let mut exp = gen_bytes(mutator, exp_len);
+        let mod_len = gen_len(mutator);
+        let mut modulus = gen_bytes(mutator, mod_len);
+        if modulus.iter().all(|b| *b == 0) {
+            modulus[mod_len - 1] = 1;
+        }
+
+        match mutator.gen_range(0, VALID_KIND_COUNT) {
+            0 => {
+                if let Some(first) = exp.first_mut() {
+                    *first |= 1;
+                }
+                ModExpArgs::new(base, exp, modulus)
+            }
+            1 => {
+                exp.insert(0, 0);
+                let zeros = mutator.gen_range(0, exp.len());
+                exp[..zeros].iter_mut().for_each(|b| *b = 0);
+                ModExpArgs::new(base, exp, modulus)
+            }
+            2 => ModExpArgs::new(base, exp, vec![0; mod_len]),
+            3 => ModExpArgs::new(vec![], exp, vec![]),
+            _ => panic!("unreachable"),
+        }
+    }
+}
+
+/// The go-ethereum modexp precompile. Operands are read at their declared
+/// lengths, right-padded with zeros, and the result is left-padded to the
+/// modulus length. A zero modulus gives zero. Declared lengths above
+/// `MAX_LENGTH` are refused, rather than allocated.
+pub fn modexp(input: &[u8]) -> Result<Vec<u8>, String> {
+    let base_len = low_u64(&get_data(input, 0, 32));
+    let exp_len = low_u64(&get_data(input, 32, 32));
+    let mod_len = low_u64(&get_data(input, 64, 32));
+    let body = input.get(96..).unwrap_or(&[]);
+
+    if base_len == 0 && mod_len == 0 {
+        return Ok(vec![]);
+    }
+    if cmp::max(base_len, cmp::max(exp_len, mod_len)) > MAX_LENGTH {
+        return Err("declared length too large for the control".to_owned());
+    }
+
+    let base = BigUint::from_bytes_be(&get_data(body, 0, base_len));
+    let exp = BigUint::from_bytes_be(&get_data(body, base_len, exp_len));
+    let modulus = BigUint::from_bytes_be(&get_data(body, base_len + exp_len, mod_len));
+
+    let mut out = vec![0u8; mod_len as usize];
+    if modulus.is_zero() {
+        return Ok(out);
+    }
+    let result = base.modpow(&exp, &modulus);
+    if !result.is_zero() {
+        let bytes = result.to_bytes_be();
+        let start = out.len() - bytes.len();
+        out[start..].copy_from_slice(&bytes);
+    }
+    Ok(out)
+}
+
+/// EIP-198's multiplication complexity.
+fn mult_complexity(x: &BigUint) -> BigUint {
+    if *x <= BigUint::from(64u32) {
+        x * x
+    } else if *x <= BigUint::from(1024u32) {
+        ((x * x) >> 2) + x * 96u32 - 3072u32
+    } else {
+        ((x * x) >> 4) + x * 480u32 - 199_680u32
+    }
+}
+
+/// The gas go-ethereum charges for the input, under EIP-198 pricing or, if
+/// `eip2565`, under EIP-2565 pricing. Lengths are priced at their full 256
+/// bits, and prices above 64 bits are capped at `u64::MAX`.
+pub fn gas(input: &[u8], eip2565: bool) -> u64 {
+    let base_len = BigUint::from_bytes_be(&get_data(input, 0, 32));
+    let exp_len = BigUint::from_bytes_be(&get_data(input, 32, 32));
+    let mod_len = BigUint::from_bytes_be(&get_data(input, 64, 32));
+    let body = input.get(96..).unwrap_or(&[]);
+    let head_len = BigUint::from(32u32);
+
+    // the first 32 bytes of the exponent
+    let exp_head = if BigUint::from(body.len()) <= base_len {
+        BigUint::zero()
+    } else {
+        let start = base_len.to_u64().expect("below the body length");
+        let size = cmp::min(exp_len.clone(), head_len.clone());
+        BigUint::from_bytes_be(&get_data(body, start, size.to_u64().expect("at most 32")))
+    };
+    let msb = exp_head.bits().saturating_sub(1);
+    let mut adj_exp_len = if exp_len > head_len {
+        (&exp_len - &head_len) * 8u32
+    } else {
+        BigUint::zero()
+    };
+    adj_exp_len += msb;
+    let adj_exp_len = cmp::max(adj_exp_len, BigUint::from(1u32));
+
+    let x = cmp::max(mod_len, base_len);
+    let gas = if eip2565 {
+        let words = (x + 7u32) / 8u32;
+        &words * &words * adj_exp_len / 3u32
+    } else {
+        mult_complexity(&x) * adj_exp_len / 20u32
+    };
+    let gas = gas.to_u64().unwrap_or(u64::MAX);
+    if eip2565 {
+        cmp::max(gas, 200)
+    } else {
+        gas
+    }
+}
+
+/// Chooses the pricing the executors are expected to charge.
+#[derive(Debug, Default, Clone)]
+pub struct ModExpConfig {
+    eip2565: bool,
+}
+
+impl ModExpConfig {
+    /// Instantiate with EIP-198 pricing. Alias for `Default::default()`
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Price inputs with EIP-2565 instead of EIP-198. Both executors run the
+    /// Istanbul precompiles, which use EIP-198.
+    pub fn set_eip2565(mut self, eip2565: bool) -> Self {
+        self.eip2565 = eip2565;
+        self
+    }
+
+    /// True if inputs are priced with EIP-2565.
+    pub fn eip2565(&self) -> bool {
+        self.eip2565
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ModExpPrecompile {
+    config: ModExpConfig,
+}
+
+impl Target for ModExpPrecompile {
+    type Intermediate = ModExpArgs;
+    type Rng = lain::rand::rngs::StdRng;
+    type Config = ModExpConfig;
+
+    fn name() -> &'static str {
+        "modexp"
+    }
+
+    fn variant(&self, input: &Self::Intermediate) -> &'static str {
+        match input.kind {
+            ModExpKind::Valid => "valid",
+            ModExpKind::LeadingZeros => "leading_zeros",
+            ModExpKind::ZeroModulus => "zero_modulus",
+            ModExpKind::Empty => "empty",
+            ModExpKind::ShortBody => "short_body",
+            ModExpKind::Overflow => "overflow",
+        }
+    }
+
+    fn testdata() -> &'static [&'static str] {
+        &["modexp.json", "modexp_eip2565.json"]
+    }
+
+    fn parse(&self, input: &[u8]) -> Option<Self::Intermediate> {
+        ModExpArgs::parse(input)
+    }
+
+    fn invariants(
+        &self,
+        input: &Self::Intermediate,
+        results: &[CommunicationResult<Vec<u8>>],
+    ) -> Vec<InvariantViolation> {
+        // modexp never fails, and pads its output to the modulus length
+        let mut violations = invariants::accepted(results);
+        let mod_len = low_u64(&input.mod_len);
+        if mod_len <= MAX_LENGTH {
+            violations.extend(invariants::output_length(results, mod_len as usize));
+        }
+        if input.kind == ModExpKind::ZeroModulus {
+            violations.extend(
+                results
+                    .iter()
+                    .filter_map(|r| r.as_ref().ok())
+                    .filter(|output| output.iter().any(|b| *b != 0))
+                    .map(|output| {
+                        InvariantViolation::new(
+                            "zero_modulus",
+                            format!("returned {}", hex::encode(output)),
+                        )
+                    }),
+            );
+        }
+        violations
+    }
+
+    fn expected_gas(&self, input: &Self::Intermediate) -> Option<u64> {
+        Some(gas(&input.serialize(), self.config.eip2565()))
+    }
+
+    fn new(config: Option<Self::Config>) -> Self {
+        Self {
+            config: config.unwrap_or_default(),
+        }
+    }
+
+    fn run_raw(
+        &mut self,
+        ctx: &mut ThreadContext,
+        input: &[u8],
+    ) -> Vec<CommunicationResult<Vec<u8>>> {
+        vec![
+            ctx.geth.run_precompile(5u8, input),
+            ctx.celo.run_precompile(5u8, input),
+        ]
+    }
+}
+
+impl ProduceInvalid for ModExpPrecompile {
+    /// Declare lengths the operands don't match.
+    fn generate_invalid(&self, mutator: &mut Mutator<Self::Rng>) -> Self::Intermediate {
+        let mut args = self.generate(mutator);
+        let choice: u8 = mutator.gen_range(0, INVALID_KIND_COUNT);
+        // outputs are as long as the modulus, and exponents cost time in
+        // their length, so only the base is declared far longer than its body
+        let (len_word, extra) = match mutator.gen_range(0u8, 3) {
+            0 => (&mut args.base_len, MAX_LENGTH),
+            1 => (&mut args.exp_len, 1024),
+            _ => (&mut args.mod_len, 1024),
+        };
+
+        match choice {
+            0 => {
+                let len = low_u64(len_word);
+                let extra = cmp::min(extra, MAX_LENGTH - len);
+                *len_word = word(len + mutator.gen_range(1, extra + 1));
+                // drop the tail of the body
+                let cut = mutator.gen_range(0, args.modulus.len() + 1);
+                args.modulus.truncate(cut);
+            }
+            1 => {
+                let i = mutator.gen_range(0, 24);
+                len_word[i] = mutator.gen_range(1u16, 0x100) as u8;
+            }
+            _ => panic!("unreachable"),
+        }
+        args.kind = args.classify();
+        args
+    }
+}
+
+impl TargetWithControl for ModExpPrecompile {
+    fn run_control(&self, input: &Self::Intermediate) -> Result<Vec<u8>, String> {
+        modexp(&input.serialize())
+    }
+}
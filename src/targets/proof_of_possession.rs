@@ -0,0 +1,517 @@
+//! celo's proofOfPossession, which checks a BLS signature by a validator's
+//! key over the validator's address. Keys are BLS12-377 G2 points, and
+//! signatures G1 points, in celo-bls's compressed encoding.
+//!
+//! The control hashes to G1 with a reconstruction of celo-bls's
+//! try-and-increment over Blake2Xs. It has not been checked against celo.
+
+use ark_bls12_377::{g1, g2, Bls12_377, Fq, Fq2, Fr, G1Affine, G1Projective, G2Affine};
+use ark_ec::{
+    pairing::Pairing,
+    short_weierstrass::{Affine, SWCurveConfig},
+    AffineRepr, CurveGroup,
+};
+use ark_ff::{BigInt, BigInteger, PrimeField, Zero};
+use lain::{byteorder::ByteOrder, prelude::*};
+use std::io::Write;
+
+use super::eip2537::gen_prime;
+use crate::{
+    errors::{CommunicationResult, InvariantViolation},
+    invariants,
+    registry::Registration,
+    traits::{ProduceInvalid, Target, TargetWithControl, ThreadContext},
+};
+
+inventory::submit! {
+    Registration::produce_invalid_with_control::<ProofOfPossessionPrecompile>()
+}
+
+pub const ADDRESS_LENGTH: usize = 20;
+/// The input length: an address, a public key and a signature.
+pub const INPUT_LENGTH: usize = ADDRESS_LENGTH + g2::Config::LENGTH + g1::Config::LENGTH;
+/// Gas for any input.
+pub const GAS: u64 = 350_000;
+
+/// The domain of proofs of possession.
+pub const POP_DOMAIN: &[u8] = b"ULforpop";
+/// Bytes hashed for each candidate point.
+const HASH_LENGTH: usize = 64;
+
+/// The flag of a compressed point with the greater of its two y.
+const GREATEST: u8 = 1 << 7;
+/// The flag of a compressed point at infinity.
+const INFINITY: u8 = 1 << 6;
+
+/// celo's error for an input of the wrong length.
+pub const INVALID_LENGTH: &str = "invalid input length";
+/// celo-bls's error for a key or signature that does not decode.
+pub const GENERAL_ERROR: &str = "General error";
+/// celo-bls's error for a signature that does not verify.
+pub const NOT_VERIFIED: &str = "Not verified";
+
+/// A BLS12-377 group in celo-bls's compressed encoding: x, little-endian,
+/// with flags in the top bits of the last byte.
+pub trait Compressed: SWCurveConfig {
+    const LENGTH: usize;
+
+    /// Decode x, with the flags cleared. `None` if an element is at or above
+    /// the modulus.
+    fn x_from_le(bytes: &[u8]) -> Option<Self::BaseField>;
+
+    fn x_to_le(x: &Self::BaseField) -> Vec<u8>;
+}
+
+fn fq_from_le(bytes: &[u8]) -> Option<Fq> {
+    let mut limbs = [0u64; 6];
+    for (limb, chunk) in limbs.iter_mut().zip(bytes.chunks(8)) {
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(chunk);
+        *limb = u64::from_le_bytes(buf);
+    }
+    Fq::from_bigint(BigInt(limbs))
+}
+
+impl Compressed for g1::Config {
+    const LENGTH: usize = 48;
+
+    fn x_from_le(bytes: &[u8]) -> Option<Fq> {
+        fq_from_le(bytes)
+    }
+
+    fn x_to_le(x: &Fq) -> Vec<u8> {
+        x.into_bigint().to_bytes_le()
+    }
+}
+
+impl Compressed for g2::Config {
+    const LENGTH: usize = 96;
+
+    /// The real part comes first.
+    fn x_from_le(bytes: &[u8]) -> Option<Fq2> {
+        Some(Fq2::new(
+            fq_from_le(&bytes[..48])?,
+            fq_from_le(&bytes[48..])?,
+        ))
+    }
+
+    fn x_to_le(x: &Fq2) -> Vec<u8> {
+        let mut out = x.c0.into_bigint().to_bytes_le();
+        out.extend_from_slice(&x.c1.into_bigint().to_bytes_le());
+        out
+    }
+}
+
+/// Compress a point.
+pub fn compress<C: Compressed>(point: &Affine<C>) -> Vec<u8> {
+    match point.xy() {
+        Some((x, y)) => {
+            let mut out = C::x_to_le(x);
+            if *y > -*y {
+                out[C::LENGTH - 1] |= GREATEST;
+            }
+            out
+        }
+        None => {
+            let mut out = vec![0; C::LENGTH];
+            out[C::LENGTH - 1] |= INFINITY;
+            out
+        }
+    }
+}
+
+/// Decompress a point, as celo-bls deserializes keys and signatures. x is
+/// decoded before the infinity flag is read, and points outside the
+/// subgroup are refused.
+pub fn decompress<C: Compressed>(bytes: &[u8]) -> Result<Affine<C>, String> {
+    let mut bytes = bytes.to_vec();
+    let flags = bytes[C::LENGTH - 1] & (GREATEST | INFINITY);
+    bytes[C::LENGTH - 1] &= !(GREATEST | INFINITY);
+    let x = C::x_from_le(&bytes).ok_or_else(|| GENERAL_ERROR.to_owned())?;
+    if flags & INFINITY != 0 {
+        return Ok(Affine::identity());
+    }
+    let point = Affine::<C>::get_point_from_x_unchecked(x, flags & GREATEST != 0)
+        .ok_or_else(|| GENERAL_ERROR.to_owned())?;
+    if !point.is_in_correct_subgroup_assuming_on_curve() {
+        return Err(GENERAL_ERROR.to_owned());
+    }
+    Ok(point)
+}
+
+/// Blake2Xs, as celo-bls runs it: the digest length is packed into the node
+/// offset of each block.
+fn blake2xs(domain: &[u8], message: &[u8], len: usize) -> Vec<u8> {
+    let node_offset = |i: usize| i as u64 | (len as u64) << 32;
+    let root = blake2s_simd::Params::new()
+        .hash_length(32)
+        .node_offset(node_offset(0))
+        .personal(domain)
+        .hash(message);
+    let mut out = vec![];
+    for (i, start) in (0..len).step_by(32).enumerate() {
+        let block = blake2s_simd::Params::new()
+            .hash_length(std::cmp::min(32, len - start))
+            .max_leaf_length(32)
+            .inner_hash_length(32)
+            .fanout(0)
+            .max_depth(0)
+            .node_offset(node_offset(i))
+            .personal(domain)
+            .hash(root.as_bytes());
+        out.extend_from_slice(block.as_bytes());
+    }
+    out
+}
+
+/// A candidate point from hash bytes. celo-bls parses candidates with the
+/// `from_random_bytes` of an older arkworks, which reads flags from the top
+/// two bits of the last byte: the greatest y from the highest, and infinity
+/// from the next. Deployed celo first overwrites the highest bit with the
+/// second lowest. arkworks 0.4 inverts the meaning of the highest bit, so
+/// the parse is written out here.
+fn candidate(bytes: &[u8]) -> Option<G1Affine> {
+    let mut bytes = bytes[..g1::Config::LENGTH].to_vec();
+    let last = bytes.len() - 1;
+    if bytes[last] & 2 != 0 {
+        bytes[last] |= GREATEST;
+    } else {
+        bytes[last] &= !GREATEST;
+    }
+    let greatest = bytes[last] & GREATEST != 0;
+    let infinity = bytes[last] & INFINITY != 0;
+    // x has 377 bits
+    bytes[last] &= 1;
+    let x = fq_from_le(&bytes)?;
+    match (greatest, infinity) {
+        // infinity has a single encoding
+        (true, true) => None,
+        (false, true) if x.is_zero() => Some(G1Affine::identity()),
+        (false, true) => None,
+        _ => G1Affine::get_point_from_x_unchecked(x, greatest),
+    }
+}
+
+/// Hash a message to G1 by try-and-increment: the first candidate, from a
+/// counter byte and the message, that is a point and does not vanish when
+/// the cofactor is cleared.
+pub fn hash_to_g1(domain: &[u8], message: &[u8]) -> G1Projective {
+    for counter in 0..=u8::MAX {
+        let hash = blake2xs(domain, &[&[counter], message].concat(), HASH_LENGTH);
+        if let Some(point) = candidate(&hash) {
+            let scaled = point.mul_by_cofactor_to_group();
+            if !scaled.is_zero() {
+                return scaled;
+            }
+        }
+    }
+    panic!("no candidate in 256 tries");
+}
+
+/// The kind of an input, from the first check it fails.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum PopKind {
+    /// A signature that verifies
+    Valid,
+    /// A key and signature at infinity, which verify
+    Infinity,
+    /// A key and signature that decode, and do not verify
+    NotVerified,
+    /// A key or signature on the curve, outside the subgroup
+    OutsideSubgroup,
+    /// A key or signature that does not decode
+    BadEncoding,
+    /// An input of the wrong length
+    BadLength,
+}
+
+/// An address, a public key and a signature over the address.
+#[derive(Debug, Clone)]
+pub struct PopArgs {
+    pub kind: PopKind,
+    pub address: [u8; ADDRESS_LENGTH],
+    pub public_key: Vec<u8>,
+    pub signature: Vec<u8>,
+    /// The serialized length. Shorter inputs are truncated, and longer ones
+    /// extended with zeros.
+    pub len: usize,
+}
+
+/// True if a compressed point decodes to a point outside the subgroup.
+fn outside_subgroup<C: Compressed>(bytes: &[u8]) -> bool {
+    let mut bytes = bytes.to_vec();
+    let flags = bytes[C::LENGTH - 1] & (GREATEST | INFINITY);
+    bytes[C::LENGTH - 1] &= !(GREATEST | INFINITY);
+    let point = C::x_from_le(&bytes)
+        .filter(|_| flags & INFINITY == 0)
+        .and_then(|x| Affine::<C>::get_point_from_x_unchecked(x, flags & GREATEST != 0));
+    matches!(point, Some(point) if !point.is_in_correct_subgroup_assuming_on_curve())
+}
+
+impl PopArgs {
+    /// A proof of possession by `key` of `address`.
+    pub fn new(key: Fr, address: [u8; ADDRESS_LENGTH]) -> Self {
+        let public_key = G2Affine::generator() * key;
+        let signature = hash_to_g1(POP_DOMAIN, &address) * key;
+        Self::from_parts(
+            address,
+            compress(&public_key.into_affine()),
+            compress(&signature.into_affine()),
+            INPUT_LENGTH,
+        )
+    }
+
+    fn from_parts(
+        address: [u8; ADDRESS_LENGTH],
+        public_key: Vec<u8>,
+        signature: Vec<u8>,
+        len: usize,
+    ) -> Self {
+        let mut args = Self {
+            kind: PopKind::Valid,
+            address,
+            public_key,
+            signature,
+            len,
+        };
+        args.kind = args.classify();
+        args
+    }
+
+    /// Parse a serialized input of any length. Missing bytes read as zero.
+    pub fn parse(input: &[u8]) -> Self {
+        let mut buf = input.to_vec();
+        buf.resize(std::cmp::max(input.len(), INPUT_LENGTH), 0);
+        let mut address = [0u8; ADDRESS_LENGTH];
+        address.copy_from_slice(&buf[..ADDRESS_LENGTH]);
+        let signature = buf[ADDRESS_LENGTH + g2::Config::LENGTH..INPUT_LENGTH].to_vec();
+        Self::from_parts(
+            address,
+            buf[ADDRESS_LENGTH..ADDRESS_LENGTH + g2::Config::LENGTH].to_vec(),
+            signature,
+            input.len(),
+        )
+    }
+
+    fn classify(&self) -> PopKind {
+        match self.verify() {
+            Ok(_) if matches!(decompress::<g2::Config>(&self.public_key), Ok(key) if key.is_zero()) => {
+                PopKind::Infinity
+            }
+            Ok(_) => PopKind::Valid,
+            Err(e) if e == INVALID_LENGTH => PopKind::BadLength,
+            Err(e) if e == NOT_VERIFIED => PopKind::NotVerified,
+            Err(_)
+                if outside_subgroup::<g2::Config>(&self.public_key)
+                    || outside_subgroup::<g1::Config>(&self.signature) =>
+            {
+                PopKind::OutsideSubgroup
+            }
+            Err(_) => PopKind::BadEncoding,
+        }
+    }
+
+    /// Verify the signature, with celo's errors. The key is decoded before
+    /// the signature.
+    pub fn verify(&self) -> Result<(), String> {
+        if self.len != INPUT_LENGTH {
+            return Err(INVALID_LENGTH.to_owned());
+        }
+        let public_key = decompress::<g2::Config>(&self.public_key)?;
+        let signature = decompress::<g1::Config>(&self.signature)?;
+        let hash = hash_to_g1(POP_DOMAIN, &self.address).into_affine();
+        let product =
+            Bls12_377::multi_pairing([signature, hash], [-G2Affine::generator(), public_key]);
+        if product.is_zero() {
+            Ok(())
+        } else {
+            Err(NOT_VERIFIED.to_owned())
+        }
+    }
+}
+
+/// celo's result for a proof that verifies.
+fn true_word() -> Vec<u8> {
+    let mut out = vec![0u8; 32];
+    out[31] = 1;
+    out
+}
+
+impl BinarySerialize for PopArgs {
+    fn binary_serialize<W: Write, E: ByteOrder>(&self, buf: &mut W) -> usize {
+        let mut out = self.address.to_vec();
+        out.extend_from_slice(&self.public_key);
+        out.extend_from_slice(&self.signature);
+        out.resize(self.len, 0);
+        buf.write_all(&out).unwrap();
+        out.len()
+    }
+}
+
+impl NewFuzzed for PopArgs {
+    type RangeType = u8;
+
+    /// Generate a proof of possession by a random key of a random address.
+    fn new_fuzzed<R: Rng>(
+        mutator: &mut Mutator<R>,
+        _constraints: Option<&Constraints<Self::RangeType>>,
+    ) -> Self {
+        let mut address = [0u8; ADDRESS_LENGTH];
+        address.iter_mut().for_each(|b| *b = mutator.gen());
+        PopArgs::new(gen_prime(mutator), address)
+    }
+}
+
+/// A random point on the curve, outside the subgroup.
+fn random_outside_subgroup<C: Compressed, R: Rng>(
+    mutator: &mut Mutator<R>,
+    random_x: impl Fn(&mut Mutator<R>) -> C::BaseField,
+) -> Vec<u8> {
+    loop {
+        if let Some(point) =
+            Affine::<C>::get_point_from_x_unchecked(random_x(mutator), mutator.gen())
+        {
+            if !point.is_in_correct_subgroup_assuming_on_curve() {
+                return compress(&point);
+            }
+        }
+    }
+}
+
+/// Break an encoding: set the bits above the modulus, pick an x with no
+/// point, or set both flags.
+fn break_encoding<C: Compressed, R: Rng>(mutator: &mut Mutator<R>, bytes: &mut [u8]) {
+    let last = C::LENGTH - 1;
+    match mutator.gen_range(0u8, 3) {
+        0 => bytes[last] |= 0x3e,
+        1 => loop {
+            bytes.iter_mut().for_each(|b| *b = mutator.gen());
+            bytes[last] &= 0x01 | GREATEST;
+            let x = C::x_from_le(&bytes[..]);
+            let point = x.and_then(|x| Affine::<C>::get_point_from_x_unchecked(x, false));
+            if x.is_some() && point.is_none() {
+                break;
+            }
+        },
+        _ => bytes[last] |= GREATEST | INFINITY,
+    }
+}
+
+/// celo's proofOfPossession.
+#[derive(Debug, Default)]
+pub struct ProofOfPossessionPrecompile;
+
+impl Target for ProofOfPossessionPrecompile {
+    type Intermediate = PopArgs;
+    type Rng = lain::rand::rngs::StdRng;
+    type Config = ();
+
+    fn name() -> &'static str {
+        "proof_of_possession"
+    }
+
+    fn variant(&self, input: &Self::Intermediate) -> &'static str {
+        match input.kind {
+            PopKind::Valid => "valid",
+            PopKind::Infinity => "infinity",
+            PopKind::NotVerified => "not_verified",
+            PopKind::OutsideSubgroup => "outside_subgroup",
+            PopKind::BadEncoding => "bad_encoding",
+            PopKind::BadLength => "bad_length",
+        }
+    }
+
+    fn testdata() -> &'static [&'static str] {
+        &[]
+    }
+
+    fn parse(&self, input: &[u8]) -> Option<Self::Intermediate> {
+        Some(PopArgs::parse(input))
+    }
+
+    /// Keys at infinity are left to the control, as celo-bls may refuse
+    /// them.
+    fn invariants(
+        &self,
+        input: &Self::Intermediate,
+        results: &[CommunicationResult<Vec<u8>>],
+    ) -> Vec<InvariantViolation> {
+        match input.kind {
+            PopKind::Valid => {
+                let mut violations = invariants::accepted(results);
+                violations.extend(
+                    results
+                        .iter()
+                        .filter_map(|r| r.as_ref().ok())
+                        .filter(|output| **output != true_word())
+                        .map(|output| InvariantViolation::new("true", hex::encode(output))),
+                );
+                violations
+            }
+            PopKind::Infinity => vec![],
+            _ => invariants::rejected(results),
+        }
+    }
+
+    fn expected_gas(&self, _input: &Self::Intermediate) -> Option<u64> {
+        Some(GAS)
+    }
+
+    fn run_raw(
+        &mut self,
+        context: &mut ThreadContext,
+        input: &[u8],
+    ) -> Vec<CommunicationResult<Vec<u8>>> {
+        vec![context.celo.run_precompile(0xfb, input)]
+    }
+}
+
+impl ProduceInvalid for ProofOfPossessionPrecompile {
+    /// Change the address, the key or the signature, put a key or signature
+    /// outside the subgroup, break an encoding, put both at infinity, or
+    /// truncate or extend the input.
+    fn generate_invalid(&self, mutator: &mut Mutator<Self::Rng>) -> Self::Intermediate {
+        let mut args = self.generate(mutator);
+        let other = self.generate(mutator);
+        match mutator.gen_range(0u8, 8) {
+            0 => {
+                let bit = mutator.gen_range(0, 8 * ADDRESS_LENGTH);
+                args.address[bit / 8] ^= 1 << (bit % 8);
+            }
+            1 => args.public_key = other.public_key,
+            2 => args.signature = other.signature,
+            3 => {
+                args.public_key = random_outside_subgroup::<g2::Config, _>(mutator, |m| {
+                    Fq2::new(gen_prime(m), gen_prime(m))
+                })
+            }
+            4 => args.signature = random_outside_subgroup::<g1::Config, _>(mutator, gen_prime),
+            5 => {
+                if mutator.gen_chance(0.5) {
+                    break_encoding::<g2::Config, _>(mutator, &mut args.public_key);
+                } else {
+                    break_encoding::<g1::Config, _>(mutator, &mut args.signature);
+                }
+            }
+            6 => {
+                args.public_key = compress(&G2Affine::identity());
+                args.signature = compress(&G1Affine::identity());
+            }
+            _ => {
+                args.len = if mutator.gen_chance(0.5) {
+                    mutator.gen_range(0, INPUT_LENGTH)
+                } else {
+                    mutator.gen_range(INPUT_LENGTH + 1, INPUT_LENGTH + 64)
+                }
+            }
+        }
+        args.kind = args.classify();
+        args
+    }
+}
+
+impl TargetWithControl for ProofOfPossessionPrecompile {
+    fn run_control(&self, input: &Self::Intermediate) -> Result<Vec<u8>, String> {
+        input.verify().map(|_| true_word())
+    }
+}
@@ -0,0 +1,72 @@
+use ripemd160::Digest;
+
+use lain::traits::BinarySerialize;
+
+use crate::{
+    errors::{CommunicationResult, InvariantViolation},
+    invariants,
+    registry::Registration,
+    traits::{Target, TargetWithControl, ThreadContext},
+};
+
+inventory::submit! {
+    Registration::with_control::<Ripemd160Precompile>()
+}
+
+#[derive(Debug, Default)]
+pub struct Ripemd160Precompile;
+
+impl Target for Ripemd160Precompile {
+    type Intermediate = Vec<u8>;
+    type Rng = lain::rand::rngs::StdRng;
+    type Config = ();
+
+    fn name() -> &'static str {
+        "ripemd160"
+    }
+
+    fn parse(&self, input: &[u8]) -> Option<Self::Intermediate> {
+        Some(input.to_vec())
+    }
+
+    fn invariants(
+        &self,
+        _input: &Self::Intermediate,
+        results: &[CommunicationResult<Vec<u8>>],
+    ) -> Vec<InvariantViolation> {
+        let mut violations = invariants::accepted(results);
+        violations.extend(invariants::output_length(results, 32));
+        // the 20-byte hash is left-padded to a word
+        violations.extend(
+            results
+                .iter()
+                .filter_map(|r| r.as_ref().ok())
+                .filter(|output| output.len() == 32 && output[..12] != [0; 12])
+                .map(|output| {
+                    InvariantViolation::new("padding", format!("returned {}", hex::encode(output)))
+                }),
+        );
+        violations
+    }
+
+    fn run_raw(
+        &mut self,
+        ctx: &mut ThreadContext,
+        input: &[u8],
+    ) -> Vec<CommunicationResult<Vec<u8>>> {
+        vec![
+            ctx.geth.run_precompile(3u8, input),
+            ctx.celo.run_precompile(3u8, input),
+        ]
+    }
+}
+
+impl TargetWithControl for Ripemd160Precompile {
+    fn run_control(&self, input: &Self::Intermediate) -> Result<Vec<u8>, String> {
+        let mut buf = vec![];
+        input.binary_serialize::<_, lain::byteorder::BigEndian>(&mut buf);
+        let mut out = vec![0u8; 12];
+        out.extend_from_slice(&ripemd160::Ripemd160::digest(&buf));
+        Ok(out)
+    }
+}
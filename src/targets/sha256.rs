@@ -3,10 +3,16 @@ use sha2::Digest;
 use lain::traits::BinarySerialize;
 
 use crate::{
-    errors::CommunicationResult,
+    errors::{CommunicationResult, InvariantViolation},
+    invariants,
+    registry::Registration,
     traits::{Target, TargetWithControl, ThreadContext},
 };
 
+inventory::submit! {
+    Registration::with_control::<Sha256Precompile>()
+}
+
 #[derive(Debug, Default)]
 pub struct Sha256Precompile;
 
@@ -19,6 +25,20 @@ impl Target for Sha256Precompile {
         "sha256"
     }
 
+    fn parse(&self, input: &[u8]) -> Option<Self::Intermediate> {
+        Some(input.to_vec())
+    }
+
+    fn invariants(
+        &self,
+        _input: &Self::Intermediate,
+        results: &[CommunicationResult<Vec<u8>>],
+    ) -> Vec<InvariantViolation> {
+        let mut violations = invariants::accepted(results);
+        violations.extend(invariants::output_length(results, 32));
+        violations
+    }
+
     fn run_raw(
         &mut self,
         ctx: &mut ThreadContext,
@@ -0,0 +1,156 @@
+use serde::Deserialize;
+use std::{fmt, fs, io, path::Path};
+
+use crate::{
+    errors::CommunicationError,
+    stats,
+    traits::{Target, ThreadContext},
+};
+
+/// A vector from go-ethereum's `core/vm/testdata/precompiles`. Files named
+/// `fail-*.json` have an `ExpectedError` instead of an output and gas.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct TestVector {
+    /// The precompile input, hex encoded
+    pub input: String,
+    /// The precompile output, hex encoded
+    #[serde(default)]
+    pub expected: Option<String>,
+    /// The error message, if the input is invalid
+    #[serde(default)]
+    pub expected_error: Option<String>,
+    /// The vector name
+    pub name: String,
+    /// The required gas
+    #[serde(default)]
+    pub gas: Option<u64>,
+}
+
+fn invalid_data<E: fmt::Display>(path: &Path, e: E) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{}: {}", path.display(), e),
+    )
+}
+
+/// Load the vectors for a target from a local go-ethereum checkout. `dir` is
+/// the `core/vm/testdata/precompiles` directory.
+pub fn load<T>(dir: &Path) -> io::Result<Vec<TestVector>>
+where
+    T: Target,
+{
+    let mut vectors = vec![];
+    for file in T::testdata().iter() {
+        let path = dir.join(file);
+        let json = fs::read_to_string(&path).map_err(|e| invalid_data(&path, e))?;
+        let file_vectors: Vec<TestVector> =
+            serde_json::from_str(&json).map_err(|e| invalid_data(&path, e))?;
+        vectors.extend(file_vectors);
+    }
+    Ok(vectors)
+}
+
+/// Load the inputs of a target's vectors, to seed its corpus.
+pub fn seeds<T>(dir: &Path) -> io::Result<Vec<Vec<u8>>>
+where
+    T: Target,
+{
+    load::<T>(dir)?
+        .iter()
+        .map(|v| hex::decode(&v.input).map_err(|e| invalid_data(dir, e)))
+        .collect()
+}
+
+/// Results of running a target's vectors through its executors.
+#[derive(Clone, Debug)]
+pub struct KnownAnswers {
+    /// The target name
+    pub target: &'static str,
+    /// The number of vectors checked
+    pub checked: usize,
+    /// A description of each failed check
+    pub failures: Vec<String>,
+}
+
+impl KnownAnswers {
+    /// True if every executor gave every expected answer.
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl fmt::Display for KnownAnswers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{}: {} known answers checked, {} failures",
+            self.target,
+            self.checked,
+            self.failures.len()
+        )?;
+        for failure in self.failures.iter() {
+            writeln!(f, "\t{}", failure)?;
+        }
+        Ok(())
+    }
+}
+
+/// Run a target's vectors through its executors, and check every output
+/// against the expected answer. Outputs and gas must match exactly. Vectors
+/// with an expected error pass on any error message, as clients word errors
+/// differently.
+pub fn check<T>(dir: &Path) -> io::Result<KnownAnswers>
+where
+    T: Target,
+{
+    let vectors = load::<T>(dir)?;
+    let mut target = T::new(None);
+    let mut ctx = ThreadContext::default();
+    let mut failures = vec![];
+
+    for vector in vectors.iter() {
+        let input = hex::decode(&vector.input).map_err(|e| invalid_data(dir, e))?;
+        ctx.take_measurements();
+        let results = target.run_raw(&mut ctx, &input);
+
+        for result in results.iter() {
+            let failure = match (result, &vector.expected) {
+                (Ok(output), Some(expected)) if hex::encode(output) == *expected => None,
+                (Ok(output), Some(expected)) => Some(format!(
+                    "expected {}, got {}",
+                    expected,
+                    hex::encode(output)
+                )),
+                (Ok(output), None) => {
+                    Some(format!("expected an error, got {}", hex::encode(output)))
+                }
+                (Err(CommunicationError::RemoteError(_)), None) => None,
+                (Err(e), _) => Some(e.to_string()),
+            };
+            if let Some(failure) = failure {
+                failures.push(format!("{}: {}", vector.name, failure));
+            }
+        }
+
+        if let Some(gas) = vector.gas {
+            for (command, measurement) in ctx.take_measurements() {
+                if measurement.gas != gas {
+                    failures.push(format!(
+                        "{}: {} expected {} gas, got {}",
+                        vector.name,
+                        stats::executor_name(command),
+                        gas,
+                        measurement.gas
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(KnownAnswers {
+        target: T::name(),
+        checked: vectors.len(),
+        failures,
+    })
+}
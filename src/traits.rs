@@ -5,9 +5,13 @@ use lain::{
 };
 
 use crate::{
-    call::Caller,
-    errors::{CommunicationError, CommunicationResult, ComparisonError, ComparisonResult},
+    call::{self, Caller, Measurement, Response},
+    errors::{
+        CommunicationError, CommunicationResult, ComparisonError, ComparisonResult,
+        InvariantViolation,
+    },
     fuzzer::Fuzzer,
+    mock::Transcript,
 };
 
 pub struct ThreadContext {
@@ -24,19 +28,139 @@ impl Default for ThreadContext {
     }
 }
 
+impl ThreadContext {
+    /// Instantiate a context running the given executor commands.
+    pub(crate) fn with_executors(celo: &'static str, geth: &'static str) -> Self {
+        Self {
+            celo: Caller::new(celo),
+            geth: Caller::new(geth),
+        }
+    }
+
+    /// Instantiate a context whose executors answer from Rust closures, for
+    /// testing without the Go executors. Measurements are reported under the
+    /// executors' commands.
+    pub fn mock<C, G>(celo: C, geth: G) -> Self
+    where
+        C: FnMut(u8, &[u8]) -> Response + Send + 'static,
+        G: FnMut(u8, &[u8]) -> Response + Send + 'static,
+    {
+        Self {
+            celo: Caller::mock(call::CELO, Box::new(celo)),
+            geth: Caller::mock(call::GETH, Box::new(geth)),
+        }
+    }
+
+    /// Instantiate a context whose executors answer from recorded
+    /// transcripts. Calls missing from a transcript panic.
+    pub fn from_transcripts(celo: Transcript, geth: Transcript) -> Self {
+        Self {
+            celo: Caller::mock(call::CELO, celo.into_handler()),
+            geth: Caller::mock(call::GETH, geth.into_handler()),
+        }
+    }
+
+    /// True if all child processes are still running.
+    pub(crate) fn is_running(&mut self) -> bool {
+        self.celo.is_running() && self.geth.is_running()
+    }
+
+    /// True if the executors are mocks.
+    pub(crate) fn is_mock(&self) -> bool {
+        self.celo.is_mock() && self.geth.is_mock()
+    }
+
+    /// Start recording every call and its response.
+    pub fn record(&mut self) {
+        self.celo.record();
+        self.geth.record();
+    }
+
+    /// Take the celo and geth transcripts recorded since `record`, and stop
+    /// recording.
+    pub fn take_transcripts(&mut self) -> (Transcript, Transcript) {
+        (self.celo.take_transcript(), self.geth.take_transcript())
+    }
+
+    /// The measurement of each executor's last call, for executors called
+    /// since the last take. Unlike `take_measurements`, this leaves them in
+    /// place.
+    pub fn measurements(&self) -> Vec<(&'static str, Measurement)> {
+        [&self.celo, &self.geth]
+            .iter()
+            .filter_map(|caller| Some((caller.command(), caller.measurement()?)))
+            .collect()
+    }
+
+    /// The measurement of each executor's last call, for executors called
+    /// since the last take.
+    pub fn take_measurements(&mut self) -> Vec<(&'static str, Measurement)> {
+        let mut measurements = vec![];
+        for caller in [&mut self.celo, &mut self.geth].iter_mut() {
+            if let Some(measurement) = caller.take_measurement() {
+                measurements.push((caller.command(), measurement));
+            }
+        }
+        measurements
+    }
+}
+
 /// A fuzzing Target. It defines 1 or more experimental runs, and provides
 /// generation routine
-pub trait Target: Send + Sync + Default {
+pub trait Target: Send + Sync + Default + 'static {
     type Intermediate: BinarySerialize + NewFuzzed;
     type Rng: Rng;
 
-    // We require these bounds to allow each fuzzer thread to copy the config
+    // We require these bounds to allow each fuzzer thread to clone the config
     // and hold it indefinitely
-    type Config: Default + Copy + Send + Sync + 'static;
+    type Config: Default + Clone + Send + Sync + 'static;
 
     /// A short human-readable name for the targt
     fn name() -> &'static str;
 
+    /// A short name for the kind of input, used to break down statistics.
+    /// Targets that generate several kinds of input should override this.
+    fn variant(&self, _input: &Self::Intermediate) -> &'static str {
+        "default"
+    }
+
+    /// Files in go-ethereum's `core/vm/testdata/precompiles` with vectors for
+    /// this target. Their inputs seed the corpus, and their answers check the
+    /// executors.
+    fn testdata() -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Parse a serialized input, e.g. a saved corpus entry. Targets that can't
+    /// recover their inputs return `None`.
+    fn parse(&self, _input: &[u8]) -> Option<Self::Intermediate> {
+        None
+    }
+
+    /// Check properties the results must have for any input, e.g. output
+    /// length. Unlike a control, invariants need no reference implementation.
+    /// They are checked in every mode, including on invalid inputs.
+    fn invariants(
+        &self,
+        _input: &Self::Intermediate,
+        _results: &[CommunicationResult<Vec<u8>>],
+    ) -> Vec<InvariantViolation> {
+        vec![]
+    }
+
+    /// Render an output in comparison reports. Targets whose outputs have
+    /// structure, e.g. curve points, may decode them. Defaults to hex.
+    fn display_output(&self, output: &[u8]) -> String {
+        hex::encode(output)
+    }
+
+    /// The gas every executor must charge for the input, if the target can
+    /// price it. Checked with the invariants, and reported as a `gas`
+    /// violation.
+    fn expected_gas(&self, _input: &Self::Intermediate) -> Option<u64> {
+        None
+    }
+
     /// Instantiate a new target (alias for Default)
     fn new(_config: Option<Self::Config>) -> Self {
         Default::default()
@@ -123,6 +247,15 @@ pub trait TargetWithControl: Target {
         input: &<Self as Target>::Intermediate,
     ) -> Vec<ComparisonResult> {
         let experimental = self.run_experimental(ctx, input);
+        self.compare_results(input, experimental)
+    }
+
+    /// Compare experimental results of an input to the control result.
+    fn compare_results(
+        &self,
+        input: &<Self as Target>::Intermediate,
+        experimental: Vec<CommunicationResult<Vec<u8>>>,
+    ) -> Vec<ComparisonResult> {
         let control = self.run_control(input);
 
         experimental
@@ -165,3 +298,36 @@ pub trait TargetWithControl: Target {
         self.compare(ctx, &case)
     }
 }
+
+/// Metamorphic relations between the results of related inputs. A target
+/// derives follow-up inputs from a base input, and checks that the results of
+/// all of them relate as they should. Relations need no control, and catch
+/// bugs a control shares.
+pub trait Metamorphic: Target {
+    /// Derive follow-up inputs from a base input. Returns no inputs if no
+    /// relation applies to the base.
+    fn follow_ups(&self, base: &Self::Intermediate) -> Vec<Self::Intermediate>;
+
+    /// Check relations between results. `results[0]` are the results of the
+    /// base input, and `results[i + 1]` those of follow-up `i`, each in the
+    /// order of `run_raw`.
+    fn relations(
+        &self,
+        base: &Self::Intermediate,
+        results: &[Vec<CommunicationResult<Vec<u8>>>],
+    ) -> Vec<InvariantViolation>;
+
+    /// Shortcut function to run a base input and its follow-ups, and check
+    /// their relations.
+    fn check_relations(
+        &mut self,
+        ctx: &mut ThreadContext,
+        base: &Self::Intermediate,
+    ) -> Vec<InvariantViolation> {
+        let mut results = vec![self.run_experimental(ctx, base)];
+        for follow_up in self.follow_ups(base).iter() {
+            results.push(self.run_experimental(ctx, follow_up));
+        }
+        self.relations(base, &results)
+    }
+}
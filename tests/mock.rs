@@ -0,0 +1,686 @@
+//! Targets and fuzzer modes against mock executors. These run without the Go
+//! executors.
+
+use ark_bls12_381::{g1, g2, G1Affine};
+use ark_ec::AffineRepr;
+use lain::{
+    prelude::*,
+    rand::{rngs::StdRng, SeedableRng},
+};
+use sha2::Digest;
+use std::time::Duration;
+
+use smash::{
+    call::Response,
+    errors::ComparisonError,
+    fuzzer::Fuzzer,
+    mock::{MockContexts, Transcript},
+    targets::{
+        blake2f::{self, Blake2fArgs},
+        bn256::{
+            self, add::Bn256AddArgs, mul::Bn256MulArgs, pairing::PairingKind, G1Point, G2Point,
+            PointKind,
+        },
+        celo_header::{HeaderArgs, HeaderKind, IstanbulExtra, VANITY_LENGTH},
+        cip20::{
+            blake2s::{Blake2sArgs, Blake2sGenOpts},
+            CIP20Modes,
+        },
+        ecrecover,
+        eip2537::{self, Bls12381PairingPrecompile, Group},
+        fraction_mul_exp::{self, FractionMulExpArgs},
+        modexp,
+        proof_of_possession::{self, PopArgs, PopKind},
+        Blake2fPrecompile, BlockNumberFromHeaderPrecompile, Bls12381G1AddPrecompile,
+        Bls12381G1MulPrecompile, Bls12381G1MultiExpPrecompile, Bls12381G2AddPrecompile,
+        Bls12381MapFp2ToG2Precompile, Bls12381MapFpToG1Precompile, Bn256AddPrecompile,
+        Bn256MulPrecompile, Bn256PairingPrecompile, Cip20Precompile, EcrecoverPrecompile,
+        FractionMulExpPrecompile, HashHeaderPrecompile, ModExpPrecompile,
+        ProofOfPossessionPrecompile, Ripemd160Precompile, Sha256Precompile,
+    },
+    traits::{Metamorphic, ProduceInvalid, Target, TargetWithControl, ThreadContext},
+};
+
+fn sha256(address: u8, input: &[u8]) -> Response {
+    assert_eq!(address, 2);
+    Response::ok(sha2::Sha256::digest(input).to_vec()).set_gas(60)
+}
+
+fn broken(_address: u8, input: &[u8]) -> Response {
+    if input.len() < 3 {
+        Response::ok(vec![0; 32])
+    } else {
+        Response::err("broken")
+    }
+}
+
+#[test]
+fn run_raw_calls_both_executors() {
+    let mut ctx = ThreadContext::mock(sha256, sha256);
+    let mut target = Sha256Precompile::new(None);
+
+    let results = target.run_raw(&mut ctx, b"abc");
+    assert_eq!(results.len(), 2);
+    for result in results {
+        assert_eq!(
+            hex::encode(result.unwrap()),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    let measurements = ctx.take_measurements();
+    assert_eq!(measurements.len(), 2);
+    assert!(measurements.iter().all(|(_, m)| m.gas == 60));
+}
+
+#[test]
+fn compare_flags_divergent_executor() {
+    let mut ctx = ThreadContext::mock(sha256, broken);
+    let mut target = Sha256Precompile::new(None);
+
+    let short = target.compare(&mut ctx, &b"ab".to_vec());
+    assert!(matches!(short[0], Err(ComparisonError::OkNotEqual(_, _))));
+    assert!(short[1].is_ok());
+
+    let long = target.compare(&mut ctx, &b"abc".to_vec());
+    assert!(matches!(long[0], Err(ComparisonError::LeftErr(_, _))));
+    assert!(long[1].is_ok());
+}
+
+#[test]
+fn transcripts_replay_recorded_calls() {
+    let mut ctx = ThreadContext::mock(sha256, broken);
+    let mut target = Sha256Precompile::new(None);
+    let inputs: Vec<&[u8]> = vec![b"", b"a", b"ab", b"abc"];
+
+    ctx.record();
+    let recorded: Vec<_> = inputs.iter().map(|i| target.run_raw(&mut ctx, i)).collect();
+    let (celo, geth) = ctx.take_transcripts();
+    assert_eq!(celo.len(), inputs.len());
+
+    let path = std::env::temp_dir().join(format!("smash-mock-{}.txt", std::process::id()));
+    geth.save(&path).unwrap();
+    let loaded = Transcript::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded, geth);
+
+    let mut ctx = ThreadContext::from_transcripts(celo, loaded);
+    for (input, recorded) in inputs.iter().zip(recorded) {
+        let replayed = target.run_raw(&mut ctx, input);
+        assert_eq!(format!("{:?}", replayed), format!("{:?}", recorded));
+    }
+}
+
+#[test]
+fn fuzzer_passes_matching_mocks() {
+    let report = Fuzzer::<Sha256Precompile>::new()
+        .set_threads(1)
+        .set_duration(Some(Duration::from_secs(1)))
+        .set_mock(Some(MockContexts::new(|| {
+            ThreadContext::mock(sha256, sha256)
+        })))
+        .run_against_control();
+
+    assert!(report.iterations > 0);
+    assert_eq!(report.failed, 0);
+}
+
+#[test]
+fn fuzzer_reports_divergent_mocks() {
+    let report = Fuzzer::<Sha256Precompile>::new()
+        .set_threads(1)
+        .set_duration(Some(Duration::from_secs(1)))
+        .set_mock(Some(MockContexts::new(|| {
+            ThreadContext::mock(sha256, broken)
+        })))
+        .run_against_control();
+
+    assert!(report.failed > 0);
+}
+
+fn truncated(address: u8, input: &[u8]) -> Response {
+    let mut output = sha256(address, input).result.unwrap();
+    output.pop();
+    Response::ok(output)
+}
+
+#[test]
+fn invariants_flag_wrong_output_length() {
+    let mut ctx = ThreadContext::mock(truncated, sha256);
+    let mut target = Sha256Precompile::new(None);
+    let input = b"abc".to_vec();
+
+    let results = target.run_experimental(&mut ctx, &input);
+    let violations = target.invariants(&input, &results);
+    assert_eq!(violations.len(), 1);
+    assert_eq!(violations[0].name, "output_length");
+}
+
+#[test]
+fn fuzzer_reports_invariant_violations() {
+    let report = Fuzzer::<Sha256Precompile>::new()
+        .set_threads(1)
+        .set_duration(Some(Duration::from_secs(1)))
+        .set_mock(Some(MockContexts::new(|| {
+            ThreadContext::mock(truncated, truncated)
+        })))
+        .run();
+
+    assert!(report.failed > 0);
+}
+
+#[test]
+fn relations_flag_ignored_blake2s_key() {
+    // answers blake2s calls as if the key were always empty
+    fn keyless(_address: u8, input: &[u8]) -> Response {
+        let mut args = Blake2sArgs::parse(input).unwrap();
+        args.key.clear();
+        Response::ok(args.run())
+    }
+
+    let mut ctx = ThreadContext::mock(keyless, keyless);
+    let mut target = Cip20Precompile::new(None);
+    let mut mutator = Mutator::new(StdRng::seed_from_u64(0));
+    let mut args: Blake2sArgs = mutator.gen();
+    args.key = vec![1; 32];
+    let base = CIP20Modes::Blake2s(Blake2sGenOpts::Valid(args));
+
+    let violations = target.check_relations(&mut ctx, &base);
+    assert_eq!(violations.len(), 1);
+    assert_eq!(violations[0].name, "key_separation");
+}
+
+fn ecrecover(address: u8, input: &[u8]) -> Response {
+    assert_eq!(address, 1);
+    Response::ok(ecrecover::ecrecover(input)).set_gas(3000)
+}
+
+#[test]
+fn ecrecover_control_recovers_known_signer() {
+    let input = hex::decode(
+        "38d18acb67d25c8bb9942764b62f18e17054f66a817bd4295423adf9ed98873e\
+         000000000000000000000000000000000000000000000000000000000000001b\
+         38d18acb67d25c8bb9942764b62f18e17054f66a817bd4295423adf9ed98873e\
+         789d1dd423d25f0772d2748d60f7e4b81bb14d086eba8e8e8efb6dcff8a4ae02",
+    )
+    .unwrap();
+    let target = EcrecoverPrecompile::new(None);
+    let args = target.parse(&input).unwrap();
+
+    assert_eq!(
+        hex::encode(target.run_control(&args).unwrap()),
+        "000000000000000000000000ceaccac640adf55b2028469bd36ba501f28b699d"
+    );
+}
+
+#[test]
+fn ecrecover_generated_inputs_parse_to_their_kind() {
+    let target = EcrecoverPrecompile::new(None);
+    let mut mutator = Mutator::new(StdRng::seed_from_u64(0));
+
+    for i in 0..200 {
+        let args = if i % 2 == 0 {
+            target.generate(&mut mutator)
+        } else {
+            target.generate_invalid(&mut mutator)
+        };
+        let mut buf = vec![];
+        args.binary_serialize::<_, lain::byteorder::BigEndian>(&mut buf);
+        assert_eq!(target.parse(&buf).unwrap().kind, args.kind);
+
+        let output = args.run();
+        if args.kind.is_rejected() {
+            assert!(output.is_empty());
+        } else if buf.len() == ecrecover::INPUT_LENGTH {
+            assert_eq!(output.len(), 32);
+        }
+    }
+}
+
+#[test]
+fn fuzzer_passes_ecrecover_mocks() {
+    let report = Fuzzer::<EcrecoverPrecompile>::new()
+        .set_threads(1)
+        .set_duration(Some(Duration::from_secs(1)))
+        .set_mock(Some(MockContexts::new(|| {
+            ThreadContext::mock(ecrecover, ecrecover)
+        })))
+        .run_mixed();
+
+    assert!(report.iterations > 0);
+    assert_eq!(report.failed, 0);
+}
+
+#[test]
+fn ripemd160_control_pads_hash() {
+    let target = Ripemd160Precompile::new(None);
+
+    assert_eq!(
+        hex::encode(target.run_control(&b"abc".to_vec()).unwrap()),
+        "0000000000000000000000008eb208f7e05d987a9b044a8e98c6b087f15a0bfc"
+    );
+}
+
+fn modexp_eip198(address: u8, input: &[u8]) -> Response {
+    assert_eq!(address, 5);
+    Response::ok(modexp::modexp(input).unwrap()).set_gas(modexp::gas(input, false))
+}
+
+fn modexp_eip2565(address: u8, input: &[u8]) -> Response {
+    modexp_eip198(address, input).set_gas(modexp::gas(input, true))
+}
+
+#[test]
+fn modexp_control_prices_eip198_example() {
+    // 3 ** (p - 1) mod p, for the secp256k1 field prime p
+    let input = hex::decode(
+        "0000000000000000000000000000000000000000000000000000000000000001\
+         0000000000000000000000000000000000000000000000000000000000000020\
+         0000000000000000000000000000000000000000000000000000000000000020\
+         03\
+         fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2e\
+         fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2f",
+    )
+    .unwrap();
+    let target = ModExpPrecompile::new(None);
+    let args = target.parse(&input).unwrap();
+
+    let mut expected = vec![0; 32];
+    expected[31] = 1;
+    assert_eq!(target.run_control(&args).unwrap(), expected);
+    assert_eq!(modexp::gas(&input, false), 13056);
+    assert_eq!(modexp::gas(&input, true), 1360);
+}
+
+#[test]
+fn fuzzer_passes_modexp_mocks() {
+    let report = Fuzzer::<ModExpPrecompile>::new()
+        .set_threads(1)
+        .set_duration(Some(Duration::from_secs(1)))
+        .set_mock(Some(MockContexts::new(|| {
+            ThreadContext::mock(modexp_eip198, modexp_eip198)
+        })))
+        .run_mixed();
+
+    assert!(report.iterations > 0);
+    assert_eq!(report.failed, 0);
+}
+
+#[test]
+fn fuzzer_reports_unexpected_gas() {
+    let report = Fuzzer::<ModExpPrecompile>::new()
+        .set_threads(1)
+        .set_duration(Some(Duration::from_secs(1)))
+        .set_mock(Some(MockContexts::new(|| {
+            ThreadContext::mock(modexp_eip198, modexp_eip2565)
+        })))
+        .run_against_control();
+
+    assert!(report.failed > 0);
+}
+
+fn bn256_add(address: u8, input: &[u8]) -> Response {
+    assert_eq!(address, 6);
+    let target = Bn256AddPrecompile::new(None);
+    match target.run_control(&Bn256AddArgs::parse(input)) {
+        Ok(output) => Response::ok(output),
+        Err(e) => Response::err(e),
+    }
+    .set_gas(bn256::add::GAS)
+}
+
+#[test]
+fn bn256_controls_agree_on_doubling() {
+    let mut generator = G1Point::infinity();
+    generator.x[31] = 1;
+    generator.y[31] = 2;
+    let doubled = hex::decode(
+        "030644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd3\
+         15ed738c0e0a7c92e7845f96b2ae9c0a68a6a449e3538fc7ff3ebf7a5a18a2c4",
+    )
+    .unwrap();
+
+    let add = Bn256AddPrecompile::new(None);
+    let mut sum = Bn256AddArgs::parse(&[generator.to_bytes(), generator.to_bytes()].concat());
+    assert_eq!(add.run_control(&sum).unwrap(), doubled);
+
+    let mul = Bn256MulPrecompile::new(None);
+    let mut product = Bn256MulArgs::parse(&generator.to_bytes());
+    product.scalar[31] = 2;
+    assert_eq!(mul.run_control(&product).unwrap(), doubled);
+    product.scalar = bn256::GROUP_ORDER;
+    assert_eq!(mul.run_control(&product).unwrap(), vec![0; 64]);
+
+    sum.b.y[31] = 3;
+    assert_eq!(add.run_control(&sum).unwrap_err(), bn256::MALFORMED_POINT);
+    sum.b.x = bn256::FIELD_MODULUS;
+    assert_eq!(add.run_control(&sum).unwrap_err(), bn256::EXCEEDS_MODULUS);
+}
+
+#[test]
+fn fuzzer_passes_bn256_mocks() {
+    let report = Fuzzer::<Bn256AddPrecompile>::new()
+        .set_threads(1)
+        .set_duration(Some(Duration::from_secs(1)))
+        .set_mock(Some(MockContexts::new(|| {
+            ThreadContext::mock(bn256_add, bn256_add)
+        })))
+        .run_mixed();
+
+    assert!(report.iterations > 0);
+    assert_eq!(report.failed, 0);
+}
+
+#[test]
+fn bn256_pairing_control_agrees_with_construction() {
+    let target = Bn256PairingPrecompile::new(None);
+    let mut mutator = Mutator::new(StdRng::seed_from_u64(0));
+
+    let empty = target.parse(&[]).unwrap();
+    assert_eq!(empty.kind, PairingKind::One);
+    assert_eq!(
+        target.display_output(&target.run_control(&empty).unwrap()),
+        "true"
+    );
+    assert_eq!(target.expected_gas(&empty), Some(bn256::pairing::BASE_GAS));
+
+    for i in 0..20 {
+        let args = if i % 2 == 0 {
+            target.generate(&mut mutator)
+        } else {
+            target.generate_invalid(&mut mutator)
+        };
+        let mut buf = vec![];
+        args.binary_serialize::<_, lain::byteorder::BigEndian>(&mut buf);
+        let parsed = target.parse(&buf).unwrap();
+        match args.check() {
+            Ok(one) => {
+                assert_eq!(one, args.kind == PairingKind::One);
+                assert_eq!(one, parsed.kind == PairingKind::One);
+            }
+            Err(e) if !args.tail.is_empty() => assert_eq!(e, bn256::pairing::BAD_SIZE),
+            Err(e) => assert!(e == bn256::MALFORMED_POINT || e == bn256::EXCEEDS_MODULUS),
+        }
+    }
+
+    let twisted = G2Point::random_outside_subgroup(&mut mutator);
+    assert_eq!(twisted.kind(), PointKind::OutsideSubgroup);
+    assert_eq!(twisted.decode().unwrap_err(), bn256::MALFORMED_POINT);
+}
+
+#[test]
+fn blake2f_control_matches_eip152_vector() {
+    // test vector 5 of EIP-152, BLAKE2b-512 of "abc"
+    let input = hex::decode(
+        "0000000c48c9bdf267e6096a3ba7ca8485ae67bb2bf894fe72f36e3cf1361d5f3af54fa5d182e6ad\
+         7f520e511f6c3e2b8c68059b6bbd41fbabd9831f79217e1319cde05b616263000000000000000000\
+         00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+         00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+         00000000000000000000000000000000000000000000000000000000000000000000000003000000\
+         00000000000000000000000001",
+    )
+    .unwrap();
+    let target = Blake2fPrecompile::new(None);
+    let mut args = target.parse(&input).unwrap();
+    assert_eq!(args.serialize(), input);
+    assert_eq!(target.expected_gas(&args), Some(12));
+    assert_eq!(
+        hex::encode(target.run_control(&args).unwrap()),
+        "ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d1\
+         7d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923"
+    );
+
+    args.f = 2;
+    assert_eq!(args.run().unwrap_err(), blake2f::INVALID_FINAL_FLAG);
+    let short = Blake2fArgs::parse(&input[..blake2f::INPUT_LENGTH - 1]);
+    assert_eq!(short.run().unwrap_err(), blake2f::INVALID_LENGTH);
+    assert_eq!(target.expected_gas(&short), Some(0));
+}
+
+#[test]
+fn eip2537_controls_agree_on_doubling() {
+    let g = eip2537::encode_point(&G1Affine::generator());
+    let mut two = vec![0u8; eip2537::SCALAR_LENGTH];
+    two[31] = 2;
+
+    let add = Bls12381G1AddPrecompile::new(None);
+    let sum = add
+        .run_control(&add.parse(&[g.clone(), g.clone()].concat()).unwrap())
+        .unwrap();
+    let mul = Bls12381G1MulPrecompile::new(None);
+    let product = mul
+        .run_control(&mul.parse(&[g.clone(), two.clone()].concat()).unwrap())
+        .unwrap();
+    assert_eq!(sum, product);
+
+    let multiexp = Bls12381G1MultiExpPrecompile::new(None);
+    let args = multiexp.parse(&[g, two].concat()).unwrap();
+    assert_eq!(multiexp.run_control(&args).unwrap(), sum);
+    assert_eq!(multiexp.expected_gas(&args), Some(14400));
+    let empty = multiexp.parse(&[]).unwrap();
+    assert_eq!(
+        multiexp.run_control(&empty).unwrap_err(),
+        eip2537::INVALID_LENGTH
+    );
+}
+
+#[test]
+fn eip2537_pairing_control_agrees_with_construction() {
+    let target = Bls12381PairingPrecompile::new(None);
+    let mut mutator = Mutator::new(StdRng::seed_from_u64(0));
+
+    for i in 0..10 {
+        let args = if i % 2 == 0 {
+            target.generate(&mut mutator)
+        } else {
+            target.generate_invalid(&mut mutator)
+        };
+        let mut buf = vec![];
+        args.binary_serialize::<_, lain::byteorder::BigEndian>(&mut buf);
+        let parsed = target.parse(&buf).unwrap();
+        match args.check() {
+            Ok(one) => {
+                assert_eq!(one, args.kind == eip2537::pairing::PairingKind::One);
+                assert_eq!(one, parsed.kind == eip2537::pairing::PairingKind::One);
+            }
+            Err(e) if args.pairs.is_empty() || !args.tail.is_empty() => {
+                assert_eq!(e, eip2537::INVALID_LENGTH)
+            }
+            Err(_) => assert!(!args.point_kind().is_in_subgroup()),
+        }
+    }
+
+    let twisted = eip2537::random_outside_subgroup::<g2::Config, _>(&mut mutator);
+    let g = eip2537::encode_point(&G1Affine::generator());
+    let args = target.parse(&[g, twisted].concat()).unwrap();
+    assert_eq!(args.check().unwrap_err(), g2::Config::SUBGROUP_ERROR);
+    assert_eq!(
+        eip2537::point_kind::<g1::Config>(&args.pairs[0].0),
+        eip2537::PointKind::Valid
+    );
+}
+
+/// Encode comma-separated hex field elements, as in the hash-to-curve
+/// vectors.
+fn fp(hex: &str) -> Vec<u8> {
+    hex.split(',')
+        .flat_map(|element| {
+            let mut encoded = vec![0u8; eip2537::FIELD_PADDING];
+            encoded.extend(hex::decode(element.trim_start_matches("0x")).unwrap());
+            encoded
+        })
+        .collect()
+}
+
+/// Map `u` through a map target's control, and add the two points with an
+/// add target's control.
+fn map_sum<M, A>(u: [&str; 2]) -> Vec<u8>
+where
+    M: TargetWithControl,
+    A: TargetWithControl,
+{
+    let map = M::new(None);
+    let points: Vec<_> = u
+        .iter()
+        .map(|u| map.run_control(&map.parse(&fp(u)).unwrap()).unwrap())
+        .collect();
+    let add = A::new(None);
+    add.run_control(&add.parse(&points.concat()).unwrap()).unwrap()
+}
+
+#[test]
+fn eip2537_map_matches_hash_to_curve_vectors() {
+    let target = Bls12381MapFp2ToG2Precompile::new(None);
+    let mut mutator = Mutator::new(StdRng::seed_from_u64(0));
+    for _ in 0..5 {
+        let output = target.run_control(&target.generate(&mut mutator)).unwrap();
+        assert_eq!(
+            eip2537::point_kind::<g2::Config>(&output),
+            eip2537::PointKind::Valid
+        );
+    }
+
+    // the "abc" vectors of RFC 9380, appendix J.9. Mapping is followed by
+    // cofactor clearing, which is linear, so the maps of u[0] and u[1] add
+    // up to P
+    let g1 = map_sum::<Bls12381MapFpToG1Precompile, Bls12381G1AddPrecompile>([
+        "0x0d921c33f2bad966478a03ca35d05719bdf92d347557ea166e5bba579eea9b83e9afa5c088573c2281410369fbd32951",
+        "0x003574a00b109ada2f26a37a91f9d1e740dffd8d69ec0c35e1e9f4652c7dba61123e9dd2e76c655d956e2b3462611139",
+    ]);
+    assert_eq!(
+        g1,
+        fp("0x03567bc5ef9c690c2ab2ecdf6a96ef1c139cc0b2f284dca0a9a7943388a49a3aee664ba5379a7655d3c68900be2f6903,\
+            0x0b9c15f3fe6e5cf4211f346271d7b01c8f3b28be689c8429c85b67af215533311f0b8dfaaa154fa6b88176c229f2885d")
+    );
+
+    let g2 = map_sum::<Bls12381MapFp2ToG2Precompile, Bls12381G2AddPrecompile>([
+        "0x15f7c0aa8f6b296ab5ff9c2c7581ade64f4ee6f1bf18f55179ff44a2cf355fa53dd2a2158c5ecb17d7c52f63e7195771,\
+         0x01c8067bf4c0ba709aa8b9abc3d1cef589a4758e09ef53732d670fd8739a7274e111ba2fcaa71b3d33df2a3a0c8529dd",
+        "0x187111d5e088b6b9acfdfad078c4dacf72dcd17ca17c82be35e79f8c372a693f60a033b461d81b025864a0ad051a06e4,\
+         0x08b852331c96ed983e497ebc6dee9b75e373d923b729194af8e72a051ea586f3538a6ebb1e80881a082fa2b24df9f566",
+    ]);
+    assert_eq!(
+        g2,
+        fp("0x02c2d18e033b960562aae3cab37a27ce00d80ccd5ba4b7fe0e7a210245129dbec7780ccc7954725f4168aff2787776e6,\
+            0x139cddbccdc5e91b9623efd38c49f81a6f83f175e80b06fc374de9eb4b41dfe4ca3a230ed250fbe3a2acf73a41177fd8,\
+            0x1787327b68159716a37440985269cf584bcb1e621d3a7202be6ea05c4cfe244aeb197642555a0645fb87bf7466b2ba48,\
+            0x00aa65dae3c8d732d10ecd2c50f8a1baf3001578f71c694e03866e9f3d49ac1e1ce70dd94a733534f106d4cec0eddd16")
+    );
+}
+
+/// A fractionMulExp input from small values.
+fn fraction(values: [u64; 6]) -> FractionMulExpArgs {
+    let input: Vec<u8> = values
+        .iter()
+        .flat_map(|v| {
+            let mut word = [0u8; 32];
+            word[24..].copy_from_slice(&v.to_be_bytes());
+            word.to_vec()
+        })
+        .collect();
+    FractionMulExpArgs::parse(&input)
+}
+
+#[test]
+fn fraction_mul_exp_control_truncates() {
+    let target = FractionMulExpPrecompile::new(None);
+
+    // 1/1 * (3/2)^2 = 2.25
+    let args = fraction([1, 1, 3, 2, 2, 2]);
+    assert_eq!(
+        target.display_output(&target.run_control(&args).unwrap()),
+        "225/100"
+    );
+    assert_eq!(target.expected_gas(&args), Some(fraction_mul_exp::BASE_GAS));
+    // 2/3 at 3 decimals truncates to 0.666
+    let args = fraction([2, 3, 5, 7, 0, 3]);
+    assert_eq!(
+        target.display_output(&target.run_control(&args).unwrap()),
+        "666/1000"
+    );
+
+    let args = fraction([1, 1, 1, 0, 1, 0]);
+    assert_eq!(args.run().unwrap_err(), fraction_mul_exp::ZERO_DENOMINATOR);
+    let args = fraction([1, 1, 1, 1, fraction_mul_exp::MAX_EXPONENT, 0]);
+    assert_eq!(target.expected_gas(&args), Some(10_050));
+    let args = fraction([1, 1, 1, 1, fraction_mul_exp::MAX_EXPONENT + 1, 0]);
+    assert_eq!(args.run().unwrap_err(), fraction_mul_exp::TOO_LARGE);
+    assert_eq!(target.expected_gas(&args), Some(fraction_mul_exp::BASE_GAS));
+}
+
+#[test]
+fn proof_of_possession_control_verifies_generated_proofs() {
+    let target = ProofOfPossessionPrecompile::new(None);
+    let mut mutator = Mutator::new(StdRng::seed_from_u64(0));
+
+    let mut args = target.generate(&mut mutator);
+    assert_eq!(args.kind, PopKind::Valid);
+    assert_eq!(
+        hex::encode(target.run_control(&args).unwrap()),
+        format!("{:064x}", 1)
+    );
+    args.address[0] ^= 1;
+    assert_eq!(
+        args.verify().unwrap_err(),
+        proof_of_possession::NOT_VERIFIED
+    );
+
+    for _ in 0..10 {
+        let args = target.generate_invalid(&mut mutator);
+        let mut buf = vec![];
+        args.binary_serialize::<_, lain::byteorder::BigEndian>(&mut buf);
+        assert_eq!(PopArgs::parse(&buf).kind, args.kind);
+        match args.kind {
+            PopKind::Valid | PopKind::Infinity => assert!(args.verify().is_ok()),
+            _ => assert!(args.verify().is_err()),
+        }
+    }
+}
+
+#[test]
+fn hash_header_control_clears_only_the_aggregated_seal() {
+    let target = HashHeaderPrecompile::new(None);
+    let mut mutator = Mutator::new(StdRng::seed_from_u64(0));
+    let hash = |header: &smash::targets::celo_header::Header| {
+        target
+            .run_control(&HeaderArgs {
+                input: header.encode(),
+            })
+            .unwrap()
+    };
+
+    let mut seen = (false, false);
+    while seen != (true, true) {
+        let args = target.generate(&mut mutator);
+        let header = args.header().unwrap();
+        assert_eq!(header.encode(), args.input);
+        match args.kind() {
+            HeaderKind::Istanbul => {
+                seen.0 = true;
+                let mut extra = IstanbulExtra::extract(&header.extra).unwrap();
+                let mut other = header.clone();
+                extra.aggregated_seal.round.push(1);
+                other.extra.truncate(VANITY_LENGTH);
+                other.extra.extend_from_slice(&extra.encode());
+                assert_eq!(hash(&other), hash(&header));
+
+                extra.parent_aggregated_seal.round.push(1);
+                other.extra.truncate(VANITY_LENGTH);
+                other.extra.extend_from_slice(&extra.encode());
+                assert_ne!(hash(&other), hash(&header));
+            }
+            HeaderKind::Plain => {
+                seen.1 = true;
+                assert_eq!(hash(&header), sha3::Keccak256::digest(&args.input).to_vec());
+            }
+            _ => {}
+        }
+    }
+
+    let block_number = BlockNumberFromHeaderPrecompile::new(None);
+    for _ in 0..50 {
+        let args = target.generate_invalid(&mut mutator);
+        assert_eq!(args.kind(), HeaderKind::Malformed);
+        assert!(block_number.run_control(&args).is_err());
+    }
+}